@@ -29,11 +29,15 @@ func _ready():
 	# Ideally this should be a singleton or passed in, but this is robust for now.
 	nt = NT4.new()
 	add_child(nt)
+	nt.values_changed.connect(_on_values_changed)
 	nt.start_client("127.0.0.1") # Connects to localhost (Simulation)
 
-func _process(delta):
-	if not nt: return
-	
+func _on_values_changed(topics: PackedStringArray):
+	# Only rebuild the instances when the fuel topic actually changed
+	if NT_TOPIC_PATH in topics:
+		_update_instances()
+
+func _update_instances():
 	# Fetch the array of Transform3D from NT (using our new C++ helper)
 	var poses = nt.get_pose3d_array(NT_TOPIC_PATH, [])
 	var count = poses.size()
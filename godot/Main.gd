@@ -24,7 +24,30 @@ var menu_bar: MenuBar
 var file_menu: PopupMenu
 var connect_dialog: AcceptDialog
 var team_num_edit: LineEdit
+var connect_rlog = false # Whether the connect dialog targets the RLOG server
 var file_dialog: FileDialog
+var export_dialog: FileDialog
+var csv_dialog: FileDialog
+var import_file_dialog: FileDialog
+var import_dialog: ConfirmationDialog
+var import_path = ""
+var import_prefix_edit: LineEdit
+var import_offset_edit: LineEdit
+var import_reference_edit: LineEdit
+var import_match_edit: LineEdit
+var import_enabled_check: CheckBox
+var load_dialog: AcceptDialog
+var load_progress: ProgressBar
+var topics_menu: PopupMenu
+var computed_dialog: ConfirmationDialog
+var computed_name_edit: LineEdit
+var computed_expression_edit: LineEdit
+var computed_error_label: Label
+var search_dialog: ConfirmationDialog
+var search_condition_edit: LineEdit
+var search_duration_edit: LineEdit
+var search_edge_option: OptionButton
+var search_error_label: Label
 
 func _ready():
 	# Wired up dependencies
@@ -32,6 +55,12 @@ func _ready():
 	topic_dock.set_nt_instance(nt)
 	
 	_setup_menus()
+	
+	nt.log_load_progress.connect(_on_log_load_progress)
+	nt.log_loaded.connect(_on_log_load_finished)
+	nt.log_load_failed.connect(_on_log_load_failed)
+	nt.export_finished.connect(_on_export_finished)
+	nt.export_failed.connect(_on_export_failed)
 
 	
 	# Connect TabBar
@@ -136,6 +165,9 @@ func save_ui_state():
 	data.split_offset = h_split.split_offset
 	data.v_split_offset = v_split.split_offset
 	
+	# Computed topics
+	data.computed_topics.assign(nt_tree_view.nt.get_computed_topics())
+	
 	var err = ResourceSaver.save(data, SAVE_PATH)
 	if err != OK:
 		print("Failed to save UI state: ", err)
@@ -172,6 +204,10 @@ func load_ui_state():
 			nt_tree_view.set_filter_text(data.search_filter)
 			h_split.split_offset = data.split_offset
 			v_split.split_offset = data.v_split_offset
+			
+			# Restore Computed Topics
+			for computed in data.computed_topics:
+				nt_tree_view.nt.add_computed_topic(computed["name"], computed["expression"])
 	else:
 		print("No saved UI state found.")
 
@@ -192,9 +228,21 @@ func _setup_menus():
 	
 	file_menu.add_item("Connect to Simulator", 1)
 	file_menu.add_item("Connect to Robot...", 2)
+	file_menu.add_item("Connect to Robot (RLOG)...", 6)
 	file_menu.add_item("Load Log File...", 3)
+	file_menu.add_item("Import Log File...", 7)
+	file_menu.add_item("Export Log File...", 4)
+	file_menu.add_item("Export CSV...", 5)
 	file_menu.id_pressed.connect(_on_file_menu_item)
 	
+	topics_menu = PopupMenu.new()
+	topics_menu.name = "Topics"
+	menu_bar.add_child(topics_menu)
+	topics_menu.add_item("Add Computed Topic...", 1)
+	topics_menu.add_item("Find Events...", 2)
+	topics_menu.add_item("Clear Found Events", 3)
+	topics_menu.id_pressed.connect(_on_topics_menu_item)
+	
 	# Dialogs
 	connect_dialog = AcceptDialog.new()
 	connect_dialog.title = "Connect to Robot"
@@ -217,24 +265,175 @@ func _setup_menus():
 	file_dialog = FileDialog.new()
 	file_dialog.file_mode = FileDialog.FILE_MODE_OPEN_FILE
 	file_dialog.access = FileDialog.ACCESS_FILESYSTEM
-	file_dialog.filters = ["*.wpilog ; WPILog Files", "*.datalog ; DataLog Files"]
+	file_dialog.filters = ["*.wpilog ; WPILog Files", "*.datalog ; DataLog Files", "*.rlog ; AdvantageKit RLOG Files", "*.mcap ; MCAP Files", "*.revlog ; REV Logs"]
 	file_dialog.file_selected.connect(_on_log_file_selected)
 	file_dialog.title = "Open Log File"
 	file_dialog.min_size = Vector2(600, 400)
 	add_child(file_dialog)
+	
+	export_dialog = FileDialog.new()
+	export_dialog.file_mode = FileDialog.FILE_MODE_SAVE_FILE
+	export_dialog.access = FileDialog.ACCESS_FILESYSTEM
+	export_dialog.filters = ["*.wpilog ; WPILog Files", "*.mcap ; MCAP Files"]
+	export_dialog.file_selected.connect(_on_export_file_selected)
+	export_dialog.title = "Export Log File"
+	export_dialog.min_size = Vector2(600, 400)
+	add_child(export_dialog)
+	
+	csv_dialog = FileDialog.new()
+	csv_dialog.file_mode = FileDialog.FILE_MODE_SAVE_FILE
+	csv_dialog.access = FileDialog.ACCESS_FILESYSTEM
+	csv_dialog.filters = ["*.csv ; CSV Files"]
+	csv_dialog.file_selected.connect(_on_csv_file_selected)
+	csv_dialog.title = "Export CSV"
+	csv_dialog.min_size = Vector2(600, 400)
+	add_child(csv_dialog)
+	
+	import_file_dialog = FileDialog.new()
+	import_file_dialog.file_mode = FileDialog.FILE_MODE_OPEN_FILE
+	import_file_dialog.access = FileDialog.ACCESS_FILESYSTEM
+	import_file_dialog.filters = ["*.wpilog ; WPILog Files (incl. converted Phoenix logs)", "*.rlog ; AdvantageKit RLOG Files", "*.mcap ; MCAP Files", "*.revlog ; REV Logs"]
+	import_file_dialog.file_selected.connect(_on_import_file_selected)
+	import_file_dialog.title = "Import Log File"
+	import_file_dialog.min_size = Vector2(600, 400)
+	add_child(import_file_dialog)
+	
+	# Prefix and time alignment for the imported log
+	import_dialog = ConfirmationDialog.new()
+	import_dialog.title = "Import Log"
+	var grid = GridContainer.new()
+	grid.columns = 2
+	import_dialog.add_child(grid)
+	import_prefix_edit = _add_import_field(grid, "Topic prefix:", "/Phoenix")
+	import_offset_edit = _add_import_field(grid, "Time offset (s):", "0")
+	import_reference_edit = _add_import_field(grid, "Align: session topic:", "e.g. /RealOutputs/Drive/LeftVelocity")
+	import_match_edit = _add_import_field(grid, "Align: imported topic:", "e.g. Phoenix6/TalonFX-1/Velocity")
+	var enabled_label = Label.new()
+	enabled_label.text = "Align: robot enable:"
+	grid.add_child(enabled_label)
+	import_enabled_check = CheckBox.new()
+	import_enabled_check.text = "Match DriverStation/Enabled edges"
+	grid.add_child(import_enabled_check)
+	import_dialog.confirmed.connect(_on_import_confirmed)
+	add_child(import_dialog)
+	
+	# Shown while a log streams in; the button cancels the load
+	load_dialog = AcceptDialog.new()
+	load_dialog.title = "Loading Log"
+	load_dialog.ok_button_text = "Cancel"
+	load_dialog.confirmed.connect(func(): nt_tree_view.nt.cancel_log_load())
+	load_dialog.canceled.connect(func(): nt_tree_view.nt.cancel_log_load())
+	
+	load_progress = ProgressBar.new()
+	load_progress.max_value = 1.0
+	load_progress.step = 0.01
+	load_progress.custom_minimum_size.x = 300
+	load_dialog.add_child(load_progress)
+	add_child(load_dialog)
+	
+	# Name and expression of a computed topic
+	computed_dialog = ConfirmationDialog.new()
+	computed_dialog.title = "Computed Topic"
+	var computed_box = VBoxContainer.new()
+	computed_dialog.add_child(computed_box)
+	var computed_grid = GridContainer.new()
+	computed_grid.columns = 2
+	computed_box.add_child(computed_grid)
+	computed_name_edit = _add_import_field(computed_grid, "Topic name:", "/Computed/Speed")
+	computed_expression_edit = _add_import_field(computed_grid, "Expression:", "hypot(/Drive/VelocityX, /Drive/VelocityY)")
+	var computed_help = Label.new()
+	computed_help.text = "Topics as /Paths or \"Quoted Names\"; + - * / ^, comparisons, ? :,\nabs sqrt sin cos atan2 hypot min max sum avg clamp if deriv integral ...\nLeave the expression empty to remove the topic."
+	computed_box.add_child(computed_help)
+	computed_error_label = Label.new()
+	computed_error_label.modulate = Color(1.0, 0.4, 0.4)
+	computed_box.add_child(computed_error_label)
+	computed_dialog.confirmed.connect(_on_computed_confirmed)
+	add_child(computed_dialog)
+	
+	# Condition to find in the log, shown as timeline markers
+	search_dialog = ConfirmationDialog.new()
+	search_dialog.title = "Find Events"
+	var search_box = VBoxContainer.new()
+	search_dialog.add_child(search_box)
+	var search_grid = GridContainer.new()
+	search_grid.columns = 2
+	search_box.add_child(search_grid)
+	search_condition_edit = _add_import_field(search_grid, "Condition:", "/Shooter/RPM < 3000 && enabled")
+	search_duration_edit = _add_import_field(search_grid, "Min duration (s):", "0")
+	var edge_label = Label.new()
+	edge_label.text = "Mark:"
+	search_grid.add_child(edge_label)
+	search_edge_option = OptionButton.new()
+	search_edge_option.add_item("Whole span")
+	search_edge_option.add_item("Rising edge")
+	search_edge_option.add_item("Falling edge")
+	search_grid.add_child(search_edge_option)
+	var search_help = Label.new()
+	search_help.text = "Same syntax as computed topics, plus enabled, disabled, auto, teleop, test, estop.\nJump between matches with [ and ] on the timeline."
+	search_box.add_child(search_help)
+	search_error_label = Label.new()
+	search_error_label.modulate = Color(1.0, 0.4, 0.4)
+	search_box.add_child(search_error_label)
+	search_dialog.confirmed.connect(_on_search_confirmed)
+	add_child(search_dialog)
 
 func _on_file_menu_item(id):
 	if id == 1:
 		# Connect Simulator
 		nt_tree_view.clear_tree()
 		nt_tree_view.nt.start_client("127.0.0.1")
-	elif id == 2:
-		# Connect Robot
+	elif id == 2 or id == 6:
+		# Connect Robot, over NT4 or AdvantageKit's RLOG server
+		connect_rlog = id == 6
 		connect_dialog.popup_centered()
 		team_num_edit.grab_focus()
 	elif id == 3:
 		# Load Log
 		file_dialog.popup_centered_ratio(0.6)
+	elif id == 4:
+		# Export Log
+		export_dialog.popup_centered_ratio(0.6)
+	elif id == 5:
+		# Export CSV
+		csv_dialog.popup_centered_ratio(0.6)
+	elif id == 7:
+		# Import Log (added to the current session)
+		import_file_dialog.popup_centered_ratio(0.6)
+
+func _on_topics_menu_item(id):
+	if id == 1:
+		# Add Computed Topic
+		computed_error_label.text = ""
+		computed_dialog.popup_centered()
+		computed_name_edit.grab_focus()
+	elif id == 2:
+		# Find Events
+		search_error_label.text = ""
+		search_dialog.popup_centered()
+		search_condition_edit.grab_focus()
+	elif id == 3:
+		nt_tree_view.nt.clear_search()
+
+func _on_computed_confirmed():
+	var topic_name = computed_name_edit.text.strip_edges()
+	var expression = computed_expression_edit.text.strip_edges()
+	if expression == "":
+		nt_tree_view.nt.remove_computed_topic(topic_name)
+		return
+	var error = nt_tree_view.nt.add_computed_topic(topic_name, expression)
+	if error != "":
+		# Keep the dialog up so the expression can be fixed
+		computed_error_label.text = error
+		computed_dialog.popup_centered()
+
+func _on_search_confirmed():
+	var condition = search_condition_edit.text.strip_edges()
+	var min_duration = int(search_duration_edit.text.to_float() * 1000000.0)
+	var edge = ["", "rising", "falling"][search_edge_option.selected]
+	var error = nt_tree_view.nt.search_log(condition, min_duration, edge)
+	if error != "":
+		search_error_label.text = error
+		search_dialog.popup_centered()
 
 func _on_connect_confirmed():
 	var txt = team_num_edit.text
@@ -243,10 +442,75 @@ func _on_connect_confirmed():
 		var ip = "10.%d.%d.2" % [team / 100, team % 100]
 		print("Connecting to Robot IP: ", ip)
 		nt_tree_view.clear_tree()
-		nt_tree_view.nt.start_client(ip)
+		if connect_rlog:
+			nt_tree_view.nt.start_rlog_client(ip)
+		else:
+			nt_tree_view.nt.start_client(ip)
 	else:
 		print("Invalid Team Number")
 
 func _on_log_file_selected(path):
 	nt_tree_view.clear_tree()
-	nt_tree_view.nt.load_log_file(path)
+	if nt_tree_view.nt.load_log_file(path):
+		load_progress.value = 0.0
+		load_dialog.popup_centered()
+
+func _on_log_load_progress(fraction: float):
+	load_progress.value = fraction
+
+func _on_log_load_finished():
+	load_dialog.hide()
+
+func _on_log_load_failed(reason: String):
+	print("Log load failed: ", reason)
+	load_dialog.hide()
+
+func _on_export_file_selected(path):
+	# Every topic over the whole log, in the format picked by the extension
+	if path.get_extension().to_lower() == "mcap":
+		nt_tree_view.nt.export_mcap(path, PackedStringArray(), 0, 0)
+	else:
+		nt_tree_view.nt.export_wpilog(path, PackedStringArray(), 0, 0)
+
+func _on_csv_file_selected(path):
+	# Topics shown in the dock (everything if it's empty), one row every 20ms
+	var topics = PackedStringArray()
+	for item in topic_dock.get_topics():
+		topics.append(item["path"])
+	nt_tree_view.nt.export_csv_wide(path, topics, 0, 0, 20000, "")
+
+func _on_export_finished(path: String, count: int):
+	print("Exported ", count, " records to ", path)
+
+func _on_export_failed(path: String, reason: String):
+	print("Failed to export to ", path, ": ", reason)
+
+func _add_import_field(grid: GridContainer, label_text: String, placeholder: String) -> LineEdit:
+	var label = Label.new()
+	label.text = label_text
+	grid.add_child(label)
+	var edit = LineEdit.new()
+	edit.placeholder_text = placeholder
+	edit.custom_minimum_size.x = 300
+	grid.add_child(edit)
+	return edit
+
+func _on_import_file_selected(path):
+	import_path = path
+	if import_prefix_edit.text == "":
+		import_prefix_edit.text = "/" + path.get_file().get_basename()
+	elif import_prefix_edit.text == "/Phoenix" and path.get_extension().to_lower() == "revlog":
+		import_prefix_edit.text = "/REV"
+	import_dialog.popup_centered()
+
+func _on_import_confirmed():
+	# Enable edges or both align topics -> auto offset, otherwise the typed offset is used
+	var offset_micros = int(import_offset_edit.text.to_float() * 1000000.0)
+	var started
+	if import_enabled_check.button_pressed:
+		started = nt_tree_view.nt.add_log_file_auto_aligned(import_path, import_prefix_edit.text)
+	else:
+		started = nt_tree_view.nt.import_log_file(import_path, import_prefix_edit.text, offset_micros, import_reference_edit.text, import_match_edit.text)
+	if started:
+		load_progress.value = 0.0
+		load_dialog.popup_centered()
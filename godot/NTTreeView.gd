@@ -5,7 +5,6 @@ var tree: Tree
 var search_bar: LineEdit
 
 var topic_map = {} # Path String -> TreeItem
-var update_timer = 0.0
 var saved_expansion_set = {} # Path -> bool
 
 var tuning_root_item: TreeItem = null
@@ -13,9 +12,15 @@ var tuning_map = {} # RelPath -> TreeItem
 
 var server_ip = "127.0.0.1"
 
+const SMOOTHED_DERIVATIVE_WINDOW = 5 # Samples on each side
+
 func _ready():
 	add_child(nt)
 	# nt.start_client(server_ip) # Disabled autoconnect
+	
+	# NT4 batches these once per frame, so we only touch rows that changed
+	nt.topics_added.connect(_on_topics_changed)
+	nt.values_changed.connect(_on_topics_changed)
 
 	nt.subscribe_to_all()
 	
@@ -139,43 +144,40 @@ func _recursive_filter(item: TreeItem, filter: String) -> bool:
 	item.visible = visible
 	return visible
 
-func _process(delta):
-	update_timer += delta
-	if update_timer > 0.033:
-		update_timer = 0.0
-		_refresh_tree()
+func _on_topics_changed(topics: PackedStringArray):
+	for topic_name in topics:
+		_refresh_topic(topic_name)
 
-func _refresh_tree():
-	var info_array = nt.get_topic_info()
+func _refresh_topic(topic_name: String):
+	var topic_type = nt.get_topic_type(topic_name)
+	if topic_type == "":
+		_remove_item(topic_name)
+		return
 	
-	for topic in info_array:
-		var topic_name = String(topic["name"])
-		var topic_type = String(topic["type"])
-		
-		# 1. Normal Tree
-		var item = _get_or_create_item(topic_name)
-		
-		# Ensure metadata[0] is always set to full path
-		if item.get_metadata(0) == null:
-			item.set_metadata(0, topic_name)
-			
-		_update_item_value(item, topic_name, topic_type, false, "")
+	# 1. Normal Tree
+	var item = _get_or_create_item(topic_name)
+	
+	# Ensure metadata[0] is always set to full path
+	if item.get_metadata(0) == null:
+		item.set_metadata(0, topic_name)
 		
-		# 2. Tuning Mirror
-		if topic_name.begins_with("/AdvantageKit/NetworkInputs/Tuning/"):
-			# Enable the tuning root if we see any tuning topic
-			if tuning_root_item:
-				tuning_root_item.visible = true
-				
-			var rel_path = topic_name.replace("/AdvantageKit/NetworkInputs/Tuning/", "")
-			var tuning_item = _get_or_create_tuning_item(rel_path)
+	_update_item_value(item, topic_name, topic_type, false, "")
+	
+	# 2. Tuning Mirror
+	if topic_name.begins_with("/AdvantageKit/NetworkInputs/Tuning/"):
+		# Enable the tuning root if we see any tuning topic
+		if tuning_root_item:
+			tuning_root_item.visible = true
 			
-			tuning_item.set_metadata(0, topic_name)
-			_update_item_value(tuning_item, topic_name, topic_type, true, "")
+		var rel_path = topic_name.replace("/AdvantageKit/NetworkInputs/Tuning/", "")
+		var tuning_item = _get_or_create_tuning_item(rel_path)
 		
-		# Also check if the raw input topic exists to enable the folder?
-		if topic_name == "/AdvantageKit/NetworkInputs/Tuning":
-			if tuning_root_item: tuning_root_item.visible = true
+		tuning_item.set_metadata(0, topic_name)
+		_update_item_value(tuning_item, topic_name, topic_type, true, "")
+	
+	# Also check if the raw input topic exists to enable the folder?
+	if topic_name == "/AdvantageKit/NetworkInputs/Tuning":
+		if tuning_root_item: tuning_root_item.visible = true
 
 func _update_item_value(item: TreeItem, topic_name: String, topic_type: String, editable: bool, struct_subpath: String):
 	var raw_val = null
@@ -360,6 +362,13 @@ func _get_or_create_item(full_path: String) -> TreeItem:
 			current = n
 	return current
 	
+# Drops the row of a topic that is gone (a removed computed topic)
+func _remove_item(full_path: String):
+	var item = topic_map.get(full_path)
+	if item == null or item.get_child_count() > 0: return
+	topic_map.erase(full_path)
+	item.free()
+
 func _get_or_create_tuning_item(rel_path: String) -> TreeItem:
 	if tuning_map.has(rel_path): return tuning_map[rel_path]
 	var parts = rel_path.split("/", false)
@@ -388,7 +397,17 @@ func _on_item_mouse_selected(pos, btn):
 			popup.add_item("Copy Key", 0)
 			popup.add_item("Copy Value (JSON)", 1)
 			popup.add_item("Copy Path", 2)
+			var topic = item.get_metadata(0)
+			if typeof(topic) != TYPE_STRING: topic = ""
+			if item.get_metadata(1) in ["double", "float", "int64", "boolean"] and topic != "":
+				popup.add_separator()
+				popup.add_item("Add Derivative", 3)
+				popup.add_item("Add Smoothed Derivative", 4)
+				popup.add_item("Add Integral", 5)
 			popup.id_pressed.connect(func(id):
+				if id >= 3:
+					_add_derived_topic(topic, id)
+					return
 				var txt = ""
 				if id == 0:
 					txt = item.get_text(0)
@@ -424,3 +443,10 @@ func _on_item_mouse_selected(pos, btn):
 			add_child(popup)
 			popup.position = get_global_mouse_position()
 			popup.popup()
+
+# Numeric derivative/integral of a topic, added next to it in the tree.
+func _add_derived_topic(topic: String, id: int):
+	match id:
+		3: nt.differentiate_topic(topic, topic + " (derivative)", 0)
+		4: nt.differentiate_topic(topic, topic + " (smoothed derivative)", SMOOTHED_DERIVATIVE_WINDOW)
+		5: nt.integrate_topic(topic, topic + " (integral)")
@@ -16,6 +16,10 @@ const COLOR_TEST = Color(0.6, 0.0, 0.6, 0.2) # Purple
 const COLOR_DISABLED = Color(0.5, 0.5, 0.5, 0.0) # Transparent/Gray
 const COLOR_ESTOP = Color(1.0, 0.0, 0.0, 0.3) # Red
 
+# Found events (NT4.search_log)
+const SEARCH_COLOR = Color(1.0, 0.5, 0.0, 0.9)
+const SEARCH_MARKER_HEIGHT = 6.0
+
 # Zoom Settings
 var MIN_ZOOM = 0.001 # Pixels per second (Zoomed out) - Updated dynamically
 const MAX_ZOOM = 5000.0 # Pixels per second (Zoomed in)
@@ -45,6 +49,9 @@ var view_latched_to_live: bool = true # Sticky scroll behavior
 var mode_intervals: Array = []
 var _last_mode_update_time: float = -1.0
 
+# List of { "start": t1, "end": t2 } in seconds, from the last search
+var search_results: Array = []
+
 # Input State
 var _hover_time: float = -1.0 # -1 if not hovering
 var _last_mouse_pos: Vector2 = Vector2.ZERO
@@ -57,6 +64,8 @@ func _init():
 
 func _ready():
 	_find_nt_instance()
+	if nt:
+		nt.search_results_changed.connect(_update_search_cache)
 	set_process(true)
 	focus_mode = Control.FOCUS_CLICK
 
@@ -127,6 +136,15 @@ func _input(event):
 					tracking_live = false
 			handled = true
 			handled = true
+		KEY_BRACKETLEFT, KEY_BRACKETRIGHT:
+			# Previous / next found event
+			var now = int(round(current_time * 1000000.0))
+			var t = nt.get_next_search_result(now) if event.keycode == KEY_BRACKETRIGHT else nt.get_previous_search_result(now)
+			if t >= 0:
+				current_time = t / 1000000.0
+				tracking_live = false
+				_stop_playback()
+			handled = true
 			
 	if handled:
 		# Clamp and Apply
@@ -151,108 +169,34 @@ func _update_mode_cache():
 	if nt == null: return
 	mode_intervals.clear()
 	
-	# Fetch full series with fallbacks
-	var enabled_data = _get_series_any(["/AdvantageKit/DriverStation/Enabled", "/DriverStation/Enabled"])
-	var auto_data = _get_series_any(["/AdvantageKit/DriverStation/Autonomous", "/DriverStation/Autonomous"])
-	var test_data = _get_series_any(["/AdvantageKit/DriverStation/Test", "/DriverStation/Test"])
-	var estop_data = _get_series_any(["/AdvantageKit/DriverStation/EmergencyStop", "/DriverStation/EStop"])
-	
-	var en_count = enabled_data.get("timestamps", []).size()
-	if en_count > 0:
-		print("Timeline Mode Update: Found %d enabled events. Time range: %s to %s" % [
-			en_count,
-			enabled_data.get("timestamps")[0],
-			enabled_data.get("timestamps")[-1]
-		])
-	else:
-		print("Timeline Mode Update: NO DATA FOUND for enabled topics.")
-		return
-
-	# Prepare for Sweep Line
-	# Struct: { "ts": [], "vals": [], "idx": 0, "curr": false }
-	var series = [
-		_prep_series(enabled_data),
-		_prep_series(auto_data),
-		_prep_series(test_data),
-		_prep_series(estop_data)
-	]
-	
-	# Collect all unique timestamps
-	var all_ts = []
-	for s in series:
-		all_ts.append_array(s.ts)
-	all_ts.sort()
-	
-	if all_ts.is_empty(): return
-	
-	# Deduplicate timestamps
-	var unique_ts = []
-	var last_seen_t = -2
-	for t in all_ts:
-		if t != last_seen_t:
-			unique_ts.append(t)
-			last_seen_t = t
-			
-	# Sweep
-	var last_t = unique_ts[0]
-	
-	# print("Generating Intervals from %d unique timestamps..." % unique_ts.size())
-	
-	for t in unique_ts:
-		var draw_len = (t - last_t) / 1000000.0
-		if draw_len > 0:
-			var color = _determine_color(series[0].curr, series[1].curr, series[2].curr, series[3].curr)
-			_add_interval(last_t, t, color)
-		
-		# Update current values for all series matching t
-		# IMPORTANT: These values take effect starting at t
-		for s in series:
-			while s.idx < s.ts.size() and s.ts[s.idx] <= t:
-				s.curr = s.vals[s.idx]
-				s.idx += 1
-		
-		last_t = t
-		
-	# Add final interval
-	var final_color = _determine_color(series[0].curr, series[1].curr, series[2].curr, series[3].curr)
-	var log_end_micros = nt.get_last_timestamp()
-	if log_end_micros > last_t:
-		_add_interval(last_t, log_end_micros, final_color)
-		
-	# print("Generated %d mode intervals." % mode_intervals.size())
+	# Intervals are swept (and kept up to date) on the Rust side
+	for interval in nt.get_mode_intervals():
+		mode_intervals.append({
+			"start": interval.start / 1000000.0,
+			"end": interval.end / 1000000.0,
+			"color": _mode_color(interval.mode)
+		})
+	# Results go away when another log is loaded
+	if not search_results.is_empty() and nt.get_search_results().is_empty():
+		search_results.clear()
 	queue_redraw()
 
-func _prep_series(dict: Dictionary) -> Dictionary:
-	var ts = dict.get("timestamps", [])
-	var vals = dict.get("values", [])
-	# IMPORTANT: We assume default state (before first data point) is FALSE.
-	return {"ts": ts, "vals": vals, "idx": 0, "curr": false}
-
-func _get_series_any(topics: Array) -> Dictionary:
-	for t in topics:
-		var d = nt.get_boolean_series(t)
-		if not d.is_empty() and not d.get("timestamps", []).is_empty():
-			# print("Found data for: %s, events: %d" % [t, d.get("timestamps").size()])
-			return d
-	return {}
-
-func _determine_color(en: bool, auto: bool, test: bool, estop: bool) -> Color:
-	if estop: return COLOR_ESTOP
-	if not en: return COLOR_DISABLED
-	if auto: return COLOR_AUTO
-	if test: return COLOR_TEST
-	return COLOR_TELEOP
-
-func _add_interval(start_micros, end_micros, color):
-	if start_micros >= end_micros: return
-	if not mode_intervals.is_empty() and mode_intervals.back().color == color:
-		mode_intervals.back().end = end_micros / 1000000.0
-	else:
-		mode_intervals.append({
-			"start": start_micros / 1000000.0,
-			"end": end_micros / 1000000.0,
-			"color": color
+func _update_search_cache():
+	search_results.clear()
+	for result in nt.get_search_results():
+		search_results.append({
+			"start": result.start / 1000000.0,
+			"end": result.end / 1000000.0
 		})
+	queue_redraw()
+
+func _mode_color(mode: String) -> Color:
+	match mode:
+		"estop": return COLOR_ESTOP
+		"auto": return COLOR_AUTO
+		"test": return COLOR_TEST
+		"teleop": return COLOR_TELEOP
+	return COLOR_DISABLED
 
 # --- Drawing ---
 
@@ -275,6 +219,14 @@ func _draw():
 		# Clip to bounds?
 		draw_rect(Rect2(sx, 0, w, size.y), interval.color)
 
+	# Found events along the top; edges are single times
+	for result in search_results:
+		if result.end < x_to_time(0) or result.start > x_to_time(size.x):
+			continue
+		var rx = time_to_x(result.start)
+		var rw = max(time_to_x(result.end) - rx, 2.0)
+		draw_rect(Rect2(rx, 0, rw, SEARCH_MARKER_HEIGHT), SEARCH_COLOR)
+
 	# 2. Draw Ticks
 	var target_time_step = 100.0 / zoom_level
 	var time_step = _calculate_nice_step(target_time_step)
@@ -521,6 +473,9 @@ func _snap_time(t: float) -> float:
 	for interval in mode_intervals:
 		if abs(interval.start - t) < snap_range: points.append(interval.start)
 		if abs(interval.end - t) < snap_range: points.append(interval.end)
+	for result in search_results:
+		if abs(result.start - t) < snap_range: points.append(result.start)
+		if abs(result.end - t) < snap_range: points.append(result.end)
 		
 	# Find closest
 	var best_t = t
@@ -243,6 +243,11 @@ func add_visualizer(path: String, viz_type: String, nt_type: String = "", option
 			else:
 				# 3D Trajectory implementation would go here (already handled by generic or specific)
 				viz_node = Node3D.new()
+
+		"Heatmap":
+			# Only offered in 2D, where it draws over the field image
+			viz_node = Node2D.new()
+			viz_node.set_script(load("res://visualizers/HeatmapVisualizer2D.gd"))
 				
 		_:
 			# Generic/Placeholder for unimplemented types from Registry
@@ -302,6 +307,8 @@ func _apply_options(node: Node, options: Dictionary):
 		node.set_model_rotation(options["Rotation"])
 	if options.has("Color") and node.has_method("set_color"):
 		node.set_color(options["Color"])
+	if options.has("Time Range") and node.has_method("set_time_range"):
+		node.set_time_range(options["Time Range"])
 
 func _on_options_changed(path: String, options: Dictionary):
 	# Update Cache
@@ -14,3 +14,6 @@ class_name UISaveData
 # Tab Persistence
 @export var active_tab: int = 0
 @export var monitored_topics_by_tab: Dictionary = {} # int (tab_idx) -> Array[Dictionary]
+
+# Computed topics: {name, expression}
+@export var computed_topics: Array[Dictionary] = []
@@ -104,12 +104,6 @@ const RULES_3D = {
 			"Thickness": ["Normal", "Bold"]
 		}
 	},
-	"Heatmap": {
-		"sources": ["Pose2d", "Pose3d", "Pose2d[]", "Pose3d[]", "Transform2d", "Transform3d", "Transform2d[]", "Transform3d[]", "Translation2d", "Translation3d", "Translation2d[]", "Translation3d[]", "number[]"],
-		"options": {
-			"Time Range": ["Enabled", "Auto", "Teleop", "Teleop (No Endgame)", "Full Log", "Visible Range"]
-		}
-	},
 	"AprilTag": {
 		"sources": ["Pose3d", "Pose3d[]", "Transform3d", "Transform3d[]", "Trajectory", "number[]"],
 		"options": {
@@ -0,0 +1,114 @@
+extends Node2D
+
+var nt_instance = null
+var topic_path: String = ""
+var field_2d = null
+var timeline = null
+
+const GRID_RESOLUTION = 0.25 # Meters per cell
+const REFRESH_INTERVAL = 1.0 # Seconds between recomputes while data streams in
+const OPACITY = 0.6
+
+var time_range: String = "Enabled"
+var heat_texture: ImageTexture = null
+var _refresh_timer: float = 0.0
+var _last_visible_range: Array = []
+
+var gradient: Gradient = null
+
+func setup(nt: Node, path: String, context: Dictionary = {}):
+	nt_instance = nt
+	topic_path = path
+
+	# Find Field2D parent
+	var p = get_parent()
+	while p and not p.has_method("field_to_pixel"):
+		p = p.get_parent()
+	field_2d = p
+
+	var main = get_tree().current_scene
+	if main:
+		timeline = main.find_child("Timeline", true, false)
+
+	gradient = Gradient.new()
+	gradient.set_color(0, Color(0.1, 0.2, 1.0))
+	gradient.set_color(1, Color(1.0, 0.1, 0.0))
+	gradient.add_point(0.5, Color(1.0, 0.9, 0.1))
+
+	if context.has("options"):
+		var opts = context["options"]
+		if opts.has("Time Range"):
+			time_range = opts["Time Range"]
+
+	_recompute()
+
+func set_time_range(value: String):
+	if value == time_range:
+		return
+	time_range = value
+	_recompute()
+
+func _process(delta):
+	if not nt_instance or topic_path == "" or not field_2d:
+		return
+
+	_refresh_timer += delta
+	var view_range = _visible_range()
+	if time_range == "Visible Range" and view_range != _last_visible_range:
+		_recompute()
+	elif _refresh_timer >= REFRESH_INTERVAL:
+		_recompute()
+
+	# The field may be resized or panned under us
+	queue_redraw()
+
+# Timeline view in microseconds, used by the "Visible Range" option
+func _visible_range() -> Array:
+	if not timeline or not timeline.has_method("x_to_time"):
+		return [0, nt_instance.get_last_timestamp()]
+	var start = int(timeline.x_to_time(0) * 1000000.0)
+	var end = int(timeline.x_to_time(timeline.size.x) * 1000000.0)
+	return [max(start, 0), max(end, 0)]
+
+func _recompute():
+	_refresh_timer = 0.0
+	if not nt_instance or topic_path == "" or not field_2d:
+		return
+
+	var field_size = Vector2(field_2d.field_length_m, field_2d.field_width_m)
+	var view_range = _visible_range()
+	_last_visible_range = view_range
+	var cells: PackedFloat32Array = nt_instance.compute_heatmap(topic_path, time_range, GRID_RESOLUTION, field_size, view_range[0], view_range[1])
+
+	var width = int(ceil(field_size.x / GRID_RESOLUTION))
+	var height = int(ceil(field_size.y / GRID_RESOLUTION))
+	if cells.size() != width * height or width == 0:
+		heat_texture = null
+		return
+
+	# Image rows run from the field's y = 0 edge, like the grid
+	var image = Image.create(width, height, false, Image.FORMAT_RGBA8)
+	for row in range(height):
+		for col in range(width):
+			var value = cells[row * width + col]
+			if value <= 0.0:
+				continue
+			var color = gradient.sample(value)
+			color.a = OPACITY
+			image.set_pixel(col, row, color)
+
+	if heat_texture and heat_texture.get_size() == Vector2(width, height):
+		heat_texture.update(image)
+	else:
+		heat_texture = ImageTexture.create_from_image(image)
+
+func _draw():
+	if not heat_texture or not field_2d:
+		return
+
+	# Corners of the grid, which may run past the far field edges by part of a cell
+	var grid_size = Vector2(heat_texture.get_size()) * GRID_RESOLUTION
+	var p0 = field_2d.field_to_pixel(Vector2.ZERO)
+	var p1 = field_2d.field_to_pixel(grid_size)
+	# A negative size flips the texture to match the field's axes
+	draw_texture_rect(heat_texture, Rect2(p0, p1 - p0), false)
@@ -0,0 +1,132 @@
+//! Getter latency while a synthetic writer pushes 10k msg/s into the store.
+//!
+//! Compares the old write path (one write lock per message, decoding inside the
+//! lock) with the batched committer used by `NetworkManager`.
+//!
+//!     cargo bench --bench store_contention
+
+use nt4_logging::batch::{BatchWriter, Sample, StoreOp};
+use nt4_logging::log_store::LogStore;
+use parking_lot::RwLock;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const MESSAGES_PER_SECOND: u64 = 10_000;
+const TOPICS: usize = 200;
+const RUN_TIME: Duration = Duration::from_secs(3);
+const GETTERS_PER_FRAME: usize = 40;
+
+/// One synthetic NT4 update: alternating doubles and Pose3d-sized struct blobs.
+fn make_ops(seq: u64) -> Vec<StoreOp> {
+    let topic_idx = (seq as usize) % TOPICS;
+    let timestamp = 1 + seq * 1_000_000 / MESSAGES_PER_SECOND;
+    let value = if topic_idx.is_multiple_of(2) {
+        Sample::Double(seq as f64)
+    } else {
+        let mut bytes = Vec::with_capacity(56);
+        for i in 0..7 {
+            bytes.extend_from_slice(&((seq + i) as f64).to_le_bytes());
+        }
+        Sample::Raw(bytes)
+    };
+    vec![StoreOp::sample(
+        format!("/Bench/{}", topic_idx),
+        timestamp,
+        value,
+    )]
+}
+
+fn run_writer(running: Arc<AtomicBool>, mut write: impl FnMut(Vec<StoreOp>)) {
+    let start = Instant::now();
+    let mut seq = 0;
+    while running.load(Ordering::Relaxed) {
+        let due = start.elapsed().as_micros() as u64 * MESSAGES_PER_SECOND / 1_000_000;
+        while seq < due {
+            write(make_ops(seq));
+            seq += 1;
+        }
+        std::thread::sleep(Duration::from_micros(200));
+    }
+}
+
+fn measure_getters(store: &Arc<RwLock<LogStore>>) -> Vec<Duration> {
+    let mut latencies = Vec::new();
+    let start = Instant::now();
+    let mut frame = 0usize;
+    while start.elapsed() < RUN_TIME {
+        for i in 0..GETTERS_PER_FRAME {
+            let topic = format!("/Bench/{}", (frame + i) % TOPICS);
+            let t0 = Instant::now();
+            {
+                let store = store.read();
+                if i % 2 == 0 {
+                    std::hint::black_box(store.get_double(&topic, u64::MAX, 0.0));
+                } else {
+                    std::hint::black_box(store.get_raw(&topic, u64::MAX).map(|b| b.len()));
+                }
+            }
+            latencies.push(t0.elapsed());
+        }
+        frame += 1;
+        // Roughly a 240 Hz render loop
+        std::thread::sleep(Duration::from_micros(4_000));
+    }
+    latencies
+}
+
+fn report(name: &str, mut latencies: Vec<Duration>) {
+    latencies.sort();
+    let pct = |p: f64| latencies[((latencies.len() - 1) as f64 * p) as usize];
+    println!(
+        "{:<22} n={:<7} p50={:>9.2?} p99={:>9.2?} p99.9={:>9.2?} max={:>9.2?}",
+        name,
+        latencies.len(),
+        pct(0.50),
+        pct(0.99),
+        pct(0.999),
+        latencies[latencies.len() - 1],
+    );
+}
+
+fn bench(name: &str, batched: bool) {
+    let store = Arc::new(RwLock::new(LogStore::new()));
+    let running = Arc::new(AtomicBool::new(true));
+
+    let writer = {
+        let store = store.clone();
+        let running = running.clone();
+        std::thread::spawn(move || {
+            if batched {
+                let generation = store.read().generation;
+                let batch = BatchWriter::spawn(store, generation);
+                run_writer(running, |ops| {
+                    batch.send(ops);
+                });
+            } else {
+                // The old path decoded while holding the lock
+                let mut seq = 0;
+                run_writer(running, |_| {
+                    let mut store = store.write();
+                    store.apply_all(make_ops(seq));
+                    seq += 1;
+                });
+            }
+        })
+    };
+
+    let latencies = measure_getters(&store);
+    running.store(false, Ordering::Relaxed);
+    writer.join().unwrap();
+
+    report(name, latencies);
+}
+
+fn main() {
+    println!(
+        "{} msg/s across {} topics, {} getters per frame, {:?} per run",
+        MESSAGES_PER_SECOND, TOPICS, GETTERS_PER_FRAME, RUN_TIME
+    );
+    bench("lock per message", false);
+    bench("batched commits", true);
+}
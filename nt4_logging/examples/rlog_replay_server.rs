@@ -0,0 +1,48 @@
+//! Stand-in for an AdvantageKit robot's RLOG server: replays a captured .rlog
+//! to whoever connects, one cycle per message at the recorded pace.
+//!
+//!     cargo run --example rlog_replay_server -- path/to/log.rlog [port]
+//!
+//! Then use "Connect to Robot (RLOG)" with 127.0.0.1 (or call
+//! `NT4.start_rlog_client("127.0.0.1")`).
+
+use nt4_logging::rlog::{RLOG_PORT, serve_replay, split_cycles};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest pause between cycles, so gaps in the log don't stall the replay.
+const MAX_GAP: Duration = Duration::from_secs(1);
+
+fn main() -> std::io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage: rlog_replay_server <file.rlog> [port]");
+        std::process::exit(2);
+    };
+    let port = args
+        .next()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(RLOG_PORT);
+
+    let data = Arc::new(std::fs::read(&path)?);
+    let cycles = Arc::new(split_cycles(&data)?);
+    println!("Loaded {} cycles from {}", cycles.len(), path);
+
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    println!("Serving RLOG on port {}", port);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let data = data.clone();
+        let cycles = cycles.clone();
+        std::thread::spawn(move || {
+            let peer = stream.peer_addr().ok();
+            println!("Client connected: {:?}", peer);
+            match serve_replay(stream, &data, &cycles, MAX_GAP) {
+                Ok(()) => println!("Client {:?} left", peer),
+                Err(e) => println!("Client {:?} left: {}", peer, e),
+            }
+        });
+    }
+    Ok(())
+}
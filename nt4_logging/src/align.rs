@@ -0,0 +1,318 @@
+use crate::dsp::{fft, resample_hold};
+use crate::log_store::{LogStore, TopicData};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+
+/// Finest grid used for correlation; 100 Hz is plenty for lining up logs.
+const MIN_PERIOD: u64 = 10_000;
+/// Upper bound on grid points per signal, to keep the FFT small.
+const MAX_POINTS: u64 = 1 << 16;
+/// Lags where the signals overlap less than this fraction are ignored, so
+/// a couple of samples at the edges can't win.
+const MIN_OVERLAP: f64 = 0.25;
+/// Two logs of the same match see the enable edges this close together once
+/// aligned; the DS packet that carries them is the only source of jitter.
+const EDGE_TOLERANCE: i64 = 250_000;
+
+/// Numeric view of a topic: doubles as-is, booleans as 0/1.
+fn numeric(data: &TopicData) -> Option<(&[u64], Vec<f64>)> {
+    match data {
+        TopicData::Double(ts, vals) => Some((ts, vals.clone())),
+        TopicData::Boolean(ts, vals) => Some((
+            ts,
+            vals.iter().map(|&b| if b { 1.0 } else { 0.0 }).collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// Microseconds to add to `other`'s timestamps so it lines up with
+/// `reference`, found by cross-correlating the two signals on a common grid.
+/// Both topics should record the same quantity (a motor velocity seen by the
+/// robot log and by the motor controller log, the enable state, ...).
+pub fn estimate_offset(reference: &TopicData, other: &TopicData) -> Option<i64> {
+    let (ref_ts, ref_vals) = numeric(reference)?;
+    let (other_ts, other_vals) = numeric(other)?;
+    let (ref_start, ref_end) = (*ref_ts.first()?, *ref_ts.last()?);
+    let (other_start, other_end) = (*other_ts.first()?, *other_ts.last()?);
+
+    let span = (ref_end - ref_start).max(other_end - other_start);
+    let period = (span / MAX_POINTS).max(MIN_PERIOD);
+    let na = ((ref_end - ref_start) / period + 1) as usize;
+    let nb = ((other_end - other_start) / period + 1) as usize;
+
+    let a = normalize(resample_hold(ref_ts, &ref_vals, ref_start, period, na))?;
+    let b = normalize(resample_hold(
+        other_ts,
+        &other_vals,
+        other_start,
+        period,
+        nb,
+    ))?;
+
+    // c[k] = sum_i a[i + k] * b[i], via FFT(a) * conj(FFT(b))
+    let n = (na + nb).next_power_of_two();
+    let (mut a_re, mut a_im) = (a, vec![0.0; n]);
+    let (mut b_re, mut b_im) = (b, vec![0.0; n]);
+    a_re.resize(n, 0.0);
+    b_re.resize(n, 0.0);
+    fft(&mut a_re, &mut a_im, false);
+    fft(&mut b_re, &mut b_im, false);
+    let mut c_re = vec![0.0; n];
+    let mut c_im = vec![0.0; n];
+    for i in 0..n {
+        c_re[i] = a_re[i] * b_re[i] + a_im[i] * b_im[i];
+        c_im[i] = a_im[i] * b_re[i] - a_re[i] * b_im[i];
+    }
+    fft(&mut c_re, &mut c_im, true);
+
+    let min_overlap = ((na.min(nb) as f64) * MIN_OVERLAP).max(1.0) as i64;
+    let mut best: Option<(i64, f64)> = None;
+    for lag in -(nb as i64 - 1)..na as i64 {
+        let overlap = (na as i64 - lag).min(nb as i64) - (-lag).max(0);
+        if overlap < min_overlap {
+            continue;
+        }
+        let score = c_re[lag.rem_euclid(n as i64) as usize] / overlap as f64;
+        if best.is_none_or(|(_, s)| score > s) {
+            best = Some((lag, score));
+        }
+    }
+
+    let (lag, _) = best?;
+    Some(ref_start as i64 + lag * period as i64 - other_start as i64)
+}
+
+/// Zero mean, unit variance; `None` for a flat signal, which can't be aligned.
+fn normalize(mut values: Vec<f64>) -> Option<Vec<f64>> {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    if std < f64::EPSILON {
+        return None;
+    }
+    values.iter_mut().for_each(|v| *v = (*v - mean) / std);
+    Some(values)
+}
+
+/// The robot enable state in `store`: AdvantageKit's `DriverStation/Enabled`
+/// or WPILib's `DS:enabled`. If the session already holds several logs, the
+/// shortest name (the one not under an import prefix) wins.
+pub fn enabled_topic(store: &LogStore) -> Option<Cow<'_, TopicData>> {
+    let topic = store
+        .topic_names()
+        .filter(|topic| {
+            let lower = topic.to_ascii_lowercase();
+            matches!(store.topic_shape(topic), Some(TopicData::Boolean(..)))
+                && (lower.ends_with("driverstation/enabled") || lower.ends_with("ds:enabled"))
+        })
+        .min_by_key(|topic| (topic.len(), *topic))?;
+    store.topic_data(topic)
+}
+
+/// Timestamps where a boolean topic goes from false to true.
+pub fn rising_edges(data: &TopicData) -> Vec<u64> {
+    let TopicData::Boolean(ts, vals) = data else {
+        return Vec::new();
+    };
+    (1..vals.len())
+        .filter(|&i| vals[i] && !vals[i - 1])
+        .map(|i| ts[i])
+        .collect()
+}
+
+/// Microseconds to add to `other`'s timestamps so its enable edges land on
+/// `reference`'s. Every pairing of edges is a candidate offset and the one
+/// that lines up the most edges wins (ties go to the smaller residual, then
+/// the earlier pairing); the result is the mean over the edges it matched.
+pub fn match_enabled_edges(reference: &TopicData, other: &TopicData) -> Option<i64> {
+    let ref_edges = rising_edges(reference);
+    let other_edges = rising_edges(other);
+
+    // Candidate offsets sorted, each with its place in pairing order for ties
+    let mut candidates: Vec<(i64, usize)> = ref_edges
+        .iter()
+        .flat_map(|&a| other_edges.iter().map(move |&b| a as i64 - b as i64))
+        .enumerate()
+        .map(|(order, candidate)| (candidate, order))
+        .collect();
+    candidates.sort_unstable();
+
+    // Pairings within the tolerance of a candidate bound how many edges it
+    // can match, so the candidates are scored best bound first until no
+    // remaining one can win
+    let mut votes = Vec::with_capacity(candidates.len());
+    let (mut low, mut high) = (0, 0);
+    for &(candidate, order) in &candidates {
+        while candidates[low].0 < candidate - EDGE_TOLERANCE {
+            low += 1;
+        }
+        while high < candidates.len() && candidates[high].0 <= candidate + EDGE_TOLERANCE {
+            high += 1;
+        }
+        votes.push((high - low, order, candidate));
+    }
+    votes.sort_unstable_by_key(|&(bound, order, _)| (Reverse(bound), order));
+
+    let mut best: Option<(usize, i64, usize, i64)> = None;
+    for (bound, order, candidate) in votes {
+        if best.is_some_and(|(matched, ..)| bound < matched) {
+            break;
+        }
+        let (matched, residual, sum) = score_offset(&ref_edges, &other_edges, candidate);
+        let offset = sum / matched.max(1) as i64;
+        if best.is_none_or(|(m, r, o, _)| (Reverse(matched), residual, order) < (Reverse(m), r, o))
+        {
+            best = Some((matched, residual, order, offset));
+        }
+    }
+    best.map(|(.., offset)| offset)
+}
+
+/// Edges of `other` shifted by `candidate` that land within the tolerance of
+/// an edge of `reference`, their total distance to it, and the sum of the
+/// offsets that would line each one up exactly. Both lists are sorted, so the
+/// nearest reference edge only ever moves forward.
+fn score_offset(reference: &[u64], other: &[u64], candidate: i64) -> (usize, i64, i64) {
+    let mut matched = 0;
+    let mut residual = 0;
+    let mut sum = 0;
+    let mut next = 0;
+    for &edge in other {
+        let shifted = edge as i64 + candidate;
+        while next + 1 < reference.len() && reference[next + 1] as i64 <= shifted {
+            next += 1;
+        }
+        // The nearest is the last edge at or before `shifted` or the one after
+        let nearest = reference[next..reference.len().min(next + 2)]
+            .iter()
+            .map(|&r| r as i64 - shifted)
+            .min_by_key(|d| d.abs());
+        if let Some(nearest) = nearest
+            && nearest.abs() <= EDGE_TOLERANCE
+        {
+            matched += 1;
+            residual += nearest.abs();
+            sum += candidate + nearest;
+        }
+    }
+    (matched, residual, sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(edges: &[u64]) -> TopicData {
+        let mut ts = Vec::new();
+        let mut vals = Vec::new();
+        for &edge in edges {
+            ts.extend([edge - 100_000, edge]);
+            vals.extend([false, true]);
+        }
+        TopicData::Boolean(ts, vals)
+    }
+
+    /// Scores `candidate` by searching every reference edge.
+    fn score_slow(reference: &[u64], other: &[u64], candidate: i64) -> (usize, i64, i64) {
+        let mut score = (0, 0, 0);
+        for &edge in other {
+            let shifted = edge as i64 + candidate;
+            let nearest = reference
+                .iter()
+                .map(|&r| r as i64 - shifted)
+                .min_by_key(|d| d.abs())
+                .unwrap();
+            if nearest.abs() <= EDGE_TOLERANCE {
+                score.0 += 1;
+                score.1 += nearest.abs();
+                score.2 += candidate + nearest;
+            }
+        }
+        score
+    }
+
+    #[test]
+    fn scores_like_a_full_search() {
+        let mut seed = 12345u64;
+        let mut random = |max: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % max
+        };
+        for _ in 0..20 {
+            let mut reference: Vec<u64> = (0..30).map(|_| 1_000_000 + random(60_000_000)).collect();
+            let mut other: Vec<u64> = (0..25).map(|_| 1_000_000 + random(60_000_000)).collect();
+            reference.sort_unstable();
+            other.sort_unstable();
+            for &a in &reference {
+                for &b in &other {
+                    let candidate = a as i64 - b as i64;
+                    assert_eq!(
+                        score_offset(&reference, &other, candidate),
+                        score_slow(&reference, &other, candidate)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn picks_like_trying_every_pairing() {
+        let mut seed = 777u64;
+        let mut random = |max: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % max
+        };
+        for round in 0..40 {
+            let reference: Vec<u64> = (0..12).map(|i| 2_000_000 + i * 9_000_000).collect();
+            let shift = random(20_000_000);
+            // Some edges missing, jittered, and a few unrelated ones
+            let mut other = std::collections::BTreeSet::new();
+            for &t in &reference {
+                if random(4) != 0 {
+                    other.insert(t + shift + random(200_000));
+                }
+            }
+            for _ in 0..round % 4 {
+                other.insert(1_000_000 + random(120_000_000));
+            }
+            let other: Vec<u64> = other.into_iter().collect();
+
+            let mut best: Option<(usize, i64, i64)> = None;
+            for &a in &reference {
+                for &b in &other {
+                    let candidate = a as i64 - b as i64;
+                    let (matched, residual, sum) = score_slow(&reference, &other, candidate);
+                    let offset = sum / matched.max(1) as i64;
+                    if best.is_none_or(|(m, r, _)| matched > m || (matched == m && residual < r)) {
+                        best = Some((matched, residual, offset));
+                    }
+                }
+            }
+            let expected = best.map(|(_, _, offset)| offset);
+            assert_eq!(
+                match_enabled_edges(&enabled(&reference), &enabled(&other)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn lines_up_many_edges() {
+        // A practice day: hundreds of enables, with a few missing from the
+        // other log and DS jitter on the rest
+        let reference: Vec<u64> = (0..300).map(|i| 5_000_000 + i * 7_300_000).collect();
+        let other: Vec<u64> = reference
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % 17 != 3)
+            .map(|(i, &t)| t - 2_500_000 + (i as u64 % 5) * 20_000)
+            .collect();
+        let offset = match_enabled_edges(&enabled(&reference), &enabled(&other)).unwrap();
+        assert!((offset - 2_460_000).abs() <= 20_000, "{}", offset);
+    }
+}
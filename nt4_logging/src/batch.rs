@@ -0,0 +1,112 @@
+use crate::log_store::LogStore;
+use parking_lot::{RwLock, RwLockWriteGuard};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::Duration;
+
+/// How long the committer waits between write-lock acquisitions. Readers never
+/// contend with more than one short write per interval, however fast data arrives.
+const COMMIT_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Ops applied before briefly handing the lock to any waiting reader.
+const COMMIT_CHUNK: usize = 64;
+
+/// A decoded value waiting to be written into the store.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sample {
+    Double(f64),
+    Boolean(bool),
+    String(String),
+    DoubleArray(Vec<f64>),
+    BooleanArray(Vec<bool>),
+    StringArray(Vec<String>),
+    Raw(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+pub enum StoreOp {
+    Sample {
+        topic: String,
+        timestamp: u64,
+        value: Sample,
+    },
+    SetType {
+        topic: String,
+        type_str: String,
+    },
+    SetMetadata {
+        topic: String,
+        metadata: String,
+    },
+}
+
+impl StoreOp {
+    pub fn sample(topic: String, timestamp: u64, value: Sample) -> Self {
+        StoreOp::Sample {
+            topic,
+            timestamp,
+            value,
+        }
+    }
+
+    pub fn set_type(topic: String, type_str: String) -> Self {
+        StoreOp::SetType { topic, type_str }
+    }
+
+    pub fn set_metadata(topic: String, metadata: String) -> Self {
+        StoreOp::SetMetadata { topic, metadata }
+    }
+}
+
+/// Decoders send their ops here instead of locking the store per message. A
+/// committer thread drains the channel and applies everything that queued up
+/// under a single write lock.
+pub struct BatchWriter {
+    tx: Sender<Vec<StoreOp>>,
+}
+
+impl BatchWriter {
+    pub fn spawn(store: Arc<RwLock<LogStore>>, generation: u32) -> Self {
+        let (tx, rx) = channel();
+        std::thread::spawn(move || commit_loop(store, generation, rx));
+        Self { tx }
+    }
+
+    /// Queues ops for the next commit. Returns false once the committer has
+    /// stopped (the store was cleared for a new session), so the producer
+    /// should stop too.
+    pub fn send(&self, ops: Vec<StoreOp>) -> bool {
+        ops.is_empty() || self.tx.send(ops).is_ok()
+    }
+}
+
+fn commit_loop(store: Arc<RwLock<LogStore>>, generation: u32, rx: Receiver<Vec<StoreOp>>) {
+    let mut pending = Vec::new();
+    // Blocks until there is something to commit; ends when every sender is gone.
+    while let Ok(ops) = rx.recv() {
+        pending.extend(ops);
+        while let Ok(ops) = rx.try_recv() {
+            pending.extend(ops);
+        }
+
+        {
+            let mut store = store.write();
+            for (i, op) in pending.drain(..).enumerate() {
+                if i % COMMIT_CHUNK == 0 {
+                    if i > 0 {
+                        // Let a waiting reader in between chunks
+                        store.merge_staged();
+                        RwLockWriteGuard::bump(&mut store);
+                    }
+                    if !store.check_generation(generation) {
+                        return;
+                    }
+                }
+                store.apply(op);
+            }
+            store.merge_staged();
+        }
+
+        std::thread::sleep(COMMIT_INTERVAL);
+    }
+}
@@ -0,0 +1,442 @@
+use std::ops::Range;
+
+/// Storage for the values of one topic, indexed in step with its timestamps.
+pub trait SampleColumn {
+    type Value: ?Sized;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the sample at `idx` holds `value` (used to drop repeats).
+    fn same(&self, idx: usize, value: &Self::Value) -> bool;
+
+    /// Whether sample `idx` holds the same value as sample `other_idx` of
+    /// `other`.
+    fn same_as(&self, idx: usize, other: &Self, other_idx: usize) -> bool;
+
+    fn push_value(&mut self, value: &Self::Value);
+
+    /// Appends a copy of sample `idx` of `other`.
+    fn push_from(&mut self, other: &Self, idx: usize);
+
+    /// Removes the samples from `at` on and returns them as a new column.
+    fn split_tail(&mut self, at: usize) -> Self;
+}
+
+impl SampleColumn for Vec<f64> {
+    type Value = f64;
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn same(&self, idx: usize, value: &f64) -> bool {
+        (self[idx] - value).abs() < f64::EPSILON
+    }
+
+    fn same_as(&self, idx: usize, other: &Self, other_idx: usize) -> bool {
+        self.same(idx, &other[other_idx])
+    }
+
+    fn push_value(&mut self, value: &f64) {
+        self.push(*value);
+    }
+
+    fn push_from(&mut self, other: &Self, idx: usize) {
+        self.push(other[idx]);
+    }
+
+    fn split_tail(&mut self, at: usize) -> Self {
+        self.split_off(at)
+    }
+}
+
+impl SampleColumn for Vec<bool> {
+    type Value = bool;
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn same(&self, idx: usize, value: &bool) -> bool {
+        self[idx] == *value
+    }
+
+    fn same_as(&self, idx: usize, other: &Self, other_idx: usize) -> bool {
+        self[idx] == other[other_idx]
+    }
+
+    fn push_value(&mut self, value: &bool) {
+        self.push(*value);
+    }
+
+    fn push_from(&mut self, other: &Self, idx: usize) {
+        self.push(other[idx]);
+    }
+
+    fn split_tail(&mut self, at: usize) -> Self {
+        self.split_off(at)
+    }
+}
+
+/// Variable-length samples stored back to back in one flat buffer (Arrow-style).
+/// Sample `i` occupies `values[offsets[i]..offsets[i + 1]]`, so appending never
+/// allocates per sample and reads hand out borrowed slices.
+#[derive(Debug, Clone)]
+pub struct ListColumn<T> {
+    offsets: Vec<usize>,
+    values: Vec<T>,
+}
+
+impl<T> Default for ListColumn<T> {
+    fn default() -> Self {
+        Self {
+            offsets: vec![0],
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<T> ListColumn<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn range(&self, idx: usize) -> Range<usize> {
+        self.offsets[idx]..self.offsets[idx + 1]
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&[T]> {
+        if idx < self.len() {
+            Some(&self.values[self.range(idx)])
+        } else {
+            None
+        }
+    }
+
+    pub fn last(&self) -> Option<&[T]> {
+        self.len().checked_sub(1).and_then(|idx| self.get(idx))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.len()).map(|idx| &self.values[self.range(idx)])
+    }
+
+    /// The flat value buffer shared by every sample.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Where each sample starts in [`Self::values`], plus the end.
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// Rebuilds a column from [`Self::offsets`] and [`Self::values`]. `None`
+    /// if the offsets don't describe `values`.
+    pub fn from_parts(offsets: Vec<usize>, values: Vec<T>) -> Option<Self> {
+        let valid = offsets.first() == Some(&0)
+            && offsets.last() == Some(&values.len())
+            && offsets.is_sorted();
+        valid.then_some(Self { offsets, values })
+    }
+}
+
+impl<T: Clone> ListColumn<T> {
+    pub fn push(&mut self, items: &[T]) {
+        self.values.extend_from_slice(items);
+        self.offsets.push(self.values.len());
+    }
+
+    /// Removes the samples from `at` on and returns them as a new column.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let start = self.offsets[at];
+        let offsets = self.offsets.drain(at + 1..).map(|o| o - start);
+        let offsets = std::iter::once(0).chain(offsets).collect();
+        Self {
+            offsets,
+            values: self.values.split_off(start),
+        }
+    }
+}
+
+impl<T: Clone + PartialEq> SampleColumn for ListColumn<T> {
+    type Value = [T];
+
+    fn len(&self) -> usize {
+        ListColumn::len(self)
+    }
+
+    fn same(&self, idx: usize, value: &[T]) -> bool {
+        self.get(idx) == Some(value)
+    }
+
+    fn same_as(&self, idx: usize, other: &Self, other_idx: usize) -> bool {
+        self.get(idx) == other.get(other_idx)
+    }
+
+    fn push_value(&mut self, value: &[T]) {
+        self.push(value);
+    }
+
+    fn push_from(&mut self, other: &Self, idx: usize) {
+        self.push(other.get(idx).unwrap_or_default());
+    }
+
+    fn split_tail(&mut self, at: usize) -> Self {
+        self.split_off(at)
+    }
+}
+
+/// Strings stored in one contiguous UTF-8 buffer.
+#[derive(Debug, Clone, Default)]
+pub struct StringColumn {
+    bytes: ListColumn<u8>,
+}
+
+impl StringColumn {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        // Only whole `&str`s are ever written, so every sample is valid UTF-8.
+        self.bytes
+            .get(idx)
+            .map(|b| std::str::from_utf8(b).unwrap_or_default())
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.len().checked_sub(1).and_then(|idx| self.get(idx))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        (0..self.len()).filter_map(|idx| self.get(idx))
+    }
+
+    pub fn push(&mut self, value: &str) {
+        self.bytes.push(value.as_bytes());
+    }
+
+    /// Removes the strings from `at` on and returns them as a new column.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        Self {
+            bytes: self.bytes.split_off(at),
+        }
+    }
+
+    /// The UTF-8 bytes of every string, back to back.
+    pub fn bytes(&self) -> &ListColumn<u8> {
+        &self.bytes
+    }
+
+    /// `None` unless every string is valid UTF-8.
+    pub fn from_bytes(bytes: ListColumn<u8>) -> Option<Self> {
+        let valid = bytes.iter().all(|b| std::str::from_utf8(b).is_ok());
+        valid.then_some(Self { bytes })
+    }
+}
+
+impl SampleColumn for StringColumn {
+    type Value = str;
+
+    fn len(&self) -> usize {
+        StringColumn::len(self)
+    }
+
+    fn same(&self, idx: usize, value: &str) -> bool {
+        self.get(idx) == Some(value)
+    }
+
+    fn same_as(&self, idx: usize, other: &Self, other_idx: usize) -> bool {
+        self.get(idx) == other.get(other_idx)
+    }
+
+    fn push_value(&mut self, value: &str) {
+        self.push(value);
+    }
+
+    fn push_from(&mut self, other: &Self, idx: usize) {
+        self.push(other.get(idx).unwrap_or_default());
+    }
+
+    fn split_tail(&mut self, at: usize) -> Self {
+        self.split_off(at)
+    }
+}
+
+/// String arrays: a flat [`StringColumn`] of elements plus per-sample offsets.
+#[derive(Debug, Clone)]
+pub struct StringArrayColumn {
+    offsets: Vec<usize>,
+    strings: StringColumn,
+}
+
+impl Default for StringArrayColumn {
+    fn default() -> Self {
+        Self {
+            offsets: vec![0],
+            strings: StringColumn::new(),
+        }
+    }
+}
+
+impl StringArrayColumn {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, idx: usize) -> Option<StrList<'_>> {
+        if idx < self.len() {
+            Some(StrList {
+                strings: &self.strings,
+                range: self.offsets[idx]..self.offsets[idx + 1],
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn last(&self) -> Option<StrList<'_>> {
+        self.len().checked_sub(1).and_then(|idx| self.get(idx))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = StrList<'_>> {
+        (0..self.len()).filter_map(|idx| self.get(idx))
+    }
+
+    pub fn push<S: AsRef<str>>(&mut self, items: &[S]) {
+        for item in items {
+            self.strings.push(item.as_ref());
+        }
+        self.offsets.push(self.strings.len());
+    }
+
+    /// Removes the samples from `at` on and returns them as a new column.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let start = self.offsets[at];
+        let offsets = self.offsets.drain(at + 1..).map(|o| o - start);
+        let offsets = std::iter::once(0).chain(offsets).collect();
+        Self {
+            offsets,
+            strings: self.strings.split_off(start),
+        }
+    }
+
+    /// Where each sample starts in [`Self::strings`], plus the end.
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// Elements of every sample, back to back.
+    pub fn strings(&self) -> &StringColumn {
+        &self.strings
+    }
+
+    /// Rebuilds a column from [`Self::offsets`] and [`Self::strings`].
+    pub fn from_parts(offsets: Vec<usize>, strings: StringColumn) -> Option<Self> {
+        let valid = offsets.first() == Some(&0)
+            && offsets.last() == Some(&strings.len())
+            && offsets.is_sorted();
+        valid.then_some(Self { offsets, strings })
+    }
+}
+
+impl SampleColumn for StringArrayColumn {
+    type Value = [String];
+
+    fn len(&self) -> usize {
+        StringArrayColumn::len(self)
+    }
+
+    fn same(&self, idx: usize, value: &[String]) -> bool {
+        self.get(idx).is_some_and(|list| {
+            list.len() == value.len() && list.iter().zip(value).all(|(a, b)| a == b)
+        })
+    }
+
+    fn same_as(&self, idx: usize, other: &Self, other_idx: usize) -> bool {
+        match (self.get(idx), other.get(other_idx)) {
+            (Some(a), Some(b)) => a.len() == b.len() && a.iter().eq(b.iter()),
+            _ => false,
+        }
+    }
+
+    fn push_value(&mut self, value: &[String]) {
+        self.push(value);
+    }
+
+    fn push_from(&mut self, other: &Self, idx: usize) {
+        if let Some(list) = other.get(idx) {
+            for item in list.iter() {
+                self.strings.push(item);
+            }
+        }
+        self.offsets.push(self.strings.len());
+    }
+
+    fn split_tail(&mut self, at: usize) -> Self {
+        self.split_off(at)
+    }
+}
+
+/// Borrowed view of one string-array sample.
+#[derive(Debug, Clone)]
+pub struct StrList<'a> {
+    strings: &'a StringColumn,
+    range: Range<usize>,
+}
+
+impl<'a> StrList<'a> {
+    pub fn len(&self) -> usize {
+        self.range.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&'a str> {
+        if idx < self.len() {
+            self.strings.get(self.range.start + idx)
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a str> + use<'a> {
+        let strings = self.strings;
+        self.range.clone().filter_map(move |idx| strings.get(idx))
+    }
+
+    pub fn to_vec(&self) -> Vec<String> {
+        self.iter().map(str::to_string).collect()
+    }
+}
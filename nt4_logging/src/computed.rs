@@ -0,0 +1,337 @@
+//! Topics computed from expressions over other topics (see [`crate::expr`]).
+//! Results are stored like logged topics and extended as the inputs receive
+//! samples, so live sessions only evaluate what is new.
+
+use crate::expr::{EvalState, Expression};
+use crate::log_store::{LogStore, TopicData};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+/// Earlier evaluation states kept (one per update) to resume from when an
+/// input sample lands before the end of what was computed.
+const CHECKPOINTS: usize = 8;
+
+pub struct ComputedTopic {
+    name: String,
+    source: String,
+    expr: Expression,
+    /// Store revision the inputs were last read at; `None` to start over.
+    seen_revision: Option<u64>,
+    progress: Option<Progress>,
+}
+
+struct Progress {
+    /// Samples of each input already evaluated.
+    consumed: Vec<usize>,
+    state: EvalState,
+    /// Earlier states, oldest first.
+    checkpoints: VecDeque<EvalState>,
+}
+
+impl ComputedTopic {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The expression as it was written.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Forgets the results, e.g. when the store is cleared.
+    pub fn reset(&mut self) {
+        self.seen_revision = None;
+        self.progress = None;
+    }
+
+    fn stale(&self, store: &LogStore) -> bool {
+        let Some(seen) = self.seen_revision else {
+            return true;
+        };
+        self.expr.inputs().iter().any(|topic| {
+            store
+                .topic_revisions
+                .get(topic)
+                .is_some_and(|&revision| revision > seen)
+        })
+    }
+
+    fn update(&mut self, store: &mut LogStore) {
+        if !self.stale(store) {
+            return;
+        }
+        let rewritten = self.seen_revision.is_some_and(|seen| {
+            self.expr.inputs().iter().any(|topic| {
+                store
+                    .topic_rewrites
+                    .get(topic)
+                    .is_some_and(|&revision| revision > seen)
+            })
+        });
+        self.seen_revision = Some(store.revision);
+        if rewritten || self.progress.is_none() {
+            store.truncate_output(&self.name, None);
+            self.progress = Some(Progress {
+                consumed: vec![0; self.expr.inputs().len()],
+                state: self.expr.new_state(),
+                checkpoints: VecDeque::new(),
+            });
+        }
+        let Some(progress) = &mut self.progress else {
+            return;
+        };
+
+        let (outputs, resume) = {
+            // Nothing to compute until every input exists
+            let Some(inputs) = self
+                .expr
+                .inputs()
+                .iter()
+                .map(|topic| store.topic_data(topic))
+                .collect::<Option<Vec<Cow<TopicData>>>>()
+            else {
+                return;
+            };
+
+            let new_from = inputs
+                .iter()
+                .zip(&progress.consumed)
+                .filter_map(|(data, &consumed)| data.timestamps().get(consumed).copied())
+                .min();
+            let Some(new_from) = new_from else {
+                return;
+            };
+
+            // A sample at or before the last evaluated time: go back to a state
+            // from before it and evaluate everything after that again
+            let mut start = progress.consumed.clone();
+            let mut resume = None;
+            if progress.state.time.is_some_and(|time| new_from <= time) {
+                while progress
+                    .checkpoints
+                    .back()
+                    .is_some_and(|state| state.time.is_some_and(|time| time >= new_from))
+                {
+                    progress.checkpoints.pop_back();
+                }
+                progress.state = match progress.checkpoints.back() {
+                    Some(state) => state.clone(),
+                    None => self.expr.new_state(),
+                };
+                let time = progress.state.time;
+                start = inputs
+                    .iter()
+                    .map(|data| time.map_or(0, |t| data.timestamps().partition_point(|&x| x <= t)))
+                    .collect();
+                resume = Some(time);
+            }
+
+            // Stable sort: an input's samples at one time keep their order
+            let mut samples = Vec::new();
+            for (idx, data) in inputs.iter().enumerate() {
+                for (i, &time) in data.timestamps().iter().enumerate().skip(start[idx]) {
+                    if let Some(value) = number(data, i) {
+                        samples.push((time, idx, value));
+                    }
+                }
+            }
+            samples.sort_by_key(|&(time, _, _)| time);
+
+            let mut outputs = Vec::new();
+            let mut group = Vec::new();
+            for chunk in samples.chunk_by(|a, b| a.0 == b.0) {
+                let time = chunk[0].0;
+                group.clear();
+                group.extend(chunk.iter().map(|&(_, idx, value)| (idx, value)));
+                if let Some(value) = self.expr.step(&mut progress.state, time, &group) {
+                    outputs.push((time, value));
+                }
+            }
+            progress.consumed = inputs.iter().map(|data| data.timestamps().len()).collect();
+            (outputs, resume)
+        };
+
+        if let Some(time) = resume {
+            store.truncate_output(&self.name, time);
+        }
+        for (time, value) in outputs {
+            store.update_double(self.name.clone(), time, value);
+        }
+
+        let newer = match progress.checkpoints.back() {
+            Some(last) => progress.state.time > last.time,
+            None => progress.state.time.is_some(),
+        };
+        if newer {
+            if progress.checkpoints.len() == CHECKPOINTS {
+                progress.checkpoints.pop_front();
+            }
+            progress.checkpoints.push_back(progress.state.clone());
+        }
+    }
+}
+
+/// Sample `idx` of a numeric or boolean topic as a number.
+pub(crate) fn number(data: &TopicData, idx: usize) -> Option<f64> {
+    match data {
+        TopicData::Double(_, vals) => vals.get(idx).copied(),
+        TopicData::Boolean(_, vals) => vals.get(idx).map(|&v| if v { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+impl LogStore {
+    /// Registers `name` as a double topic holding `source` evaluated over the
+    /// topics it names, replacing an earlier definition of `name`. Fails with
+    /// a message if the expression doesn't parse or `name` is a logged topic.
+    pub fn define_computed(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let expr = Expression::parse(source)?;
+        if expr.inputs().iter().any(|topic| topic == name) {
+            return Err(format!("{} can't be computed from itself", name));
+        }
+        let existing = self.computed.iter().position(|c| c.name == name);
+        if existing.is_none() && self.has_topic(name) {
+            return Err(format!("{} is already a logged topic", name));
+        }
+
+        let topic = ComputedTopic {
+            name: name.to_string(),
+            source: source.to_string(),
+            expr,
+            seen_revision: None,
+            progress: None,
+        };
+        match existing {
+            Some(idx) => self.computed[idx] = topic,
+            None => self.computed.push(topic),
+        }
+        self.set_type(name.to_string(), "double".to_string());
+        self.truncate_output(name, None);
+        Ok(())
+    }
+
+    /// Drops the computed topic `name` and its data.
+    pub fn remove_computed(&mut self, name: &str) -> bool {
+        let Some(idx) = self.computed.iter().position(|c| c.name == name) else {
+            return false;
+        };
+        self.computed.remove(idx);
+        self.topic_order.retain(|topic| topic != name);
+        self.data.remove(name);
+        self.topic_types.remove(name);
+        self.revision += 1;
+        self.topic_revisions.insert(name.to_string(), self.revision);
+        true
+    }
+
+    pub fn computed_topics(&self) -> impl Iterator<Item = &ComputedTopic> {
+        self.computed.iter()
+    }
+
+    pub fn is_computed(&self, topic: &str) -> bool {
+        self.computed.iter().any(|c| c.name == topic)
+    }
+
+    /// Whether [`Self::update_computed`] has anything to do, to check under
+    /// a read lock first.
+    pub fn computed_stale(&self) -> bool {
+        self.computed.iter().any(|c| c.stale(self))
+    }
+
+    /// Evaluates computed topics over the input samples added since the last
+    /// update, in definition order (so one may read an earlier one).
+    pub fn update_computed(&mut self) {
+        let mut computed = std::mem::take(&mut self.computed);
+        for topic in &mut computed {
+            topic.update(self);
+        }
+        self.computed = computed;
+    }
+
+    /// Creates the output column of a computed topic if needed, and drops
+    /// its samples after `time` (all of them for `None`).
+    fn truncate_output(&mut self, name: &str, time: Option<u64>) {
+        if !self.data.contains_key(name) {
+            self.data.insert(name.to_string(), TopicData::new_double());
+            if !self.topic_order.iter().any(|t| t == name) {
+                self.topic_order.push(name.to_string());
+            }
+        }
+        let Some(TopicData::Double(ts, vals)) = self.data.get_mut(name) else {
+            return;
+        };
+        let keep = time.map_or(0, |t| ts.partition_point(|&x| x <= t));
+        if keep < ts.len() {
+            ts.truncate(keep);
+            vals.truncate(keep);
+            self.revision += 1;
+            self.topic_revisions.insert(name.to_string(), self.revision);
+            self.topic_rewrites.insert(name.to_string(), self.revision);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(store: &LogStore, name: &str) -> (Vec<u64>, Vec<f64>) {
+        match &store.data[name] {
+            TopicData::Double(ts, vals) => (ts.clone(), vals.clone()),
+            _ => panic!("{} isn't a double topic", name),
+        }
+    }
+
+    #[test]
+    fn rolls_back_for_out_of_order_inputs() {
+        let source = "integral(/a) + /b";
+        let a = [(1, 1.0), (2, 2.0), (3, 3.0), (4, 4.0)].map(|(t, v)| (t * 1_000_000, v));
+        let b = [(1, 10.0), (3, 20.0)].map(|(t, v)| (t * 1_000_000, v));
+
+        let mut fresh = LogStore::new();
+        for &(t, v) in &a {
+            fresh.update_double("/a".into(), t, v);
+        }
+        for &(t, v) in &b {
+            fresh.update_double("/b".into(), t, v);
+        }
+        fresh.define_computed("/out", source).unwrap();
+        fresh.update_computed();
+
+        // /a runs ahead of /b, whose second sample lands inside what was computed
+        let mut store = LogStore::new();
+        store.define_computed("/out", source).unwrap();
+        store.update_double("/a".into(), a[0].0, a[0].1);
+        store.update_double("/a".into(), a[1].0, a[1].1);
+        store.update_double("/b".into(), b[0].0, b[0].1);
+        store.update_computed();
+        store.update_double("/a".into(), a[2].0, a[2].1);
+        store.update_double("/a".into(), a[3].0, a[3].1);
+        store.update_computed();
+        assert_eq!(output(&store, "/out").0.len(), 4);
+        store.update_double("/b".into(), b[1].0, b[1].1);
+        assert!(store.computed_stale());
+        store.update_computed();
+
+        assert_eq!(output(&store, "/out"), output(&fresh, "/out"));
+        assert_eq!(output(&store, "/out").1, [10.0, 11.0, 23.0, 26.0]);
+    }
+
+    #[test]
+    fn removing_drops_the_topic() {
+        let mut store = LogStore::new();
+        store.update_double("/a".into(), 1, 1.0);
+        store.define_computed("/twice", "/a * 2").unwrap();
+        store.update_double("/b".into(), 2, 1.0);
+        store.update_computed();
+        assert_eq!(store.topic_order, ["/a", "/twice", "/b"]);
+
+        assert!(store.remove_computed("/twice"));
+        assert!(!store.remove_computed("/twice"));
+        assert_eq!(store.topic_order, ["/a", "/b"]);
+        assert!(!store.has_topic("/twice"));
+        // The name is free for a logged topic again
+        store.update_double("/twice".into(), 3, 1.0);
+        assert_eq!(store.topic_order, ["/a", "/b", "/twice"]);
+    }
+}
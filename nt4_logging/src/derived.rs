@@ -0,0 +1,84 @@
+//! Topics derived from the current samples of another topic (derivative,
+//! integral). Unlike computed topics these are snapshots: they are stored as
+//! ordinary double topics and don't follow later samples of the source.
+
+use crate::dsp;
+use crate::log_store::{LogStore, TopicData};
+
+impl LogStore {
+    /// Stores the rate of change (per second) of the numeric topic `source`
+    /// as `target`, smoothed over `half_window` samples on each side (see
+    /// [`dsp::derivative`]).
+    pub fn differentiate(
+        &mut self,
+        source: &str,
+        target: &str,
+        half_window: usize,
+    ) -> Result<(), String> {
+        let (ts, vals) = self.derive_input(source, target)?;
+        let rates = dsp::derivative(&ts, &vals, half_window);
+        let units = self.get_topic_units(source).map(|u| format!("{}/s", u));
+        self.store_derived(target, &ts, &rates, units);
+        Ok(())
+    }
+
+    /// Stores the running integral (value × seconds) of the numeric topic
+    /// `source` as `target`, holding each value until the next sample.
+    pub fn integrate(&mut self, source: &str, target: &str) -> Result<(), String> {
+        let (ts, vals) = self.derive_input(source, target)?;
+        let totals = dsp::integral(&ts, &vals);
+        let units = self.get_topic_units(source).map(|u| format!("{}*s", u));
+        self.store_derived(target, &ts, &totals, units);
+        Ok(())
+    }
+
+    /// Samples of `source` as numbers (booleans as 0/1), after checking that
+    /// `target` may be written.
+    fn derive_input(&self, source: &str, target: &str) -> Result<(Vec<u64>, Vec<f64>), String> {
+        if source == target {
+            return Err(format!("{} can't replace its own source", target));
+        }
+        if self.is_computed(target) {
+            return Err(format!("{} is a computed topic", target));
+        }
+        let data = self
+            .topic_data(source)
+            .ok_or_else(|| format!("{} doesn't exist", source))?;
+        match data.as_ref() {
+            TopicData::Double(ts, vals) => Ok((ts.clone(), vals.clone())),
+            TopicData::Boolean(ts, vals) => Ok((
+                ts.clone(),
+                vals.iter().map(|&v| if v { 1.0 } else { 0.0 }).collect(),
+            )),
+            _ => Err(format!("{} isn't numeric", source)),
+        }
+    }
+
+    pub(crate) fn store_derived(
+        &mut self,
+        target: &str,
+        ts: &[u64],
+        vals: &[f64],
+        units: Option<String>,
+    ) {
+        // Repeats are dropped, as for logged samples
+        let (mut out_ts, mut out_vals) = (Vec::new(), Vec::new());
+        for (&t, &v) in ts.iter().zip(vals) {
+            if out_vals.last() != Some(&v) {
+                out_ts.push(t);
+                out_vals.push(v);
+            }
+        }
+        self.set_type(target.to_string(), "double".to_string());
+        match units {
+            Some(units) => {
+                let metadata = serde_json::json!({ "units": units });
+                self.set_metadata(target.to_string(), metadata.to_string())
+            }
+            None => {
+                self.topic_metadata.remove(target);
+            }
+        }
+        self.insert_topic(target.to_string(), TopicData::Double(out_ts, out_vals));
+    }
+}
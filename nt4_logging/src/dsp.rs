@@ -0,0 +1,288 @@
+use std::f64::consts::PI;
+
+/// In-place radix-2 FFT. `re` and `im` must have the same power-of-two
+/// length. The inverse transform is scaled by `1/n`.
+pub fn fft(re: &mut [f64], im: &mut [f64], inverse: bool) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+    if n <= 1 {
+        return;
+    }
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * PI / len as f64;
+        let (w_im, w_re) = angle.sin_cos();
+        for start in (0..n).step_by(len) {
+            let (mut cur_re, mut cur_im) = (1.0, 0.0);
+            for k in 0..len / 2 {
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cur_re - im[b] * cur_im;
+                let t_im = re[b] * cur_im + im[b] * cur_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+                let next_re = cur_re * w_re - cur_im * w_im;
+                cur_im = cur_re * w_im + cur_im * w_re;
+                cur_re = next_re;
+            }
+        }
+        len <<= 1;
+    }
+
+    if inverse {
+        let scale = 1.0 / n as f64;
+        re.iter_mut().for_each(|v| *v *= scale);
+        im.iter_mut().for_each(|v| *v *= scale);
+    }
+}
+
+/// Samples a step signal on a uniform grid (`count` points from `start`,
+/// `period` apart), holding each value until the next sample. Grid points
+/// before the first sample take the first value.
+pub fn resample_hold(
+    timestamps: &[u64],
+    values: &[f64],
+    start: u64,
+    period: u64,
+    count: usize,
+) -> Vec<f64> {
+    let mut out = Vec::with_capacity(count);
+    let mut idx = 0;
+    for i in 0..count {
+        let t = start + i as u64 * period;
+        while idx + 1 < timestamps.len() && timestamps[idx + 1] <= t {
+            idx += 1;
+        }
+        out.push(values.get(idx).copied().unwrap_or(0.0));
+    }
+    out
+}
+
+/// Derivative (per second) of samples at `timestamps` (microseconds), which
+/// need not be evenly spaced. Each point gets the slope of a least-squares
+/// quadratic through it and `half_window` neighbours on each side (fewer at
+/// the ends), which smooths like a Savitzky-Golay filter; a half-window of
+/// 1 is the three-point difference, and 0 the plain difference from the
+/// previous sample (the next one for the first).
+pub fn derivative(timestamps: &[u64], values: &[f64], half_window: usize) -> Vec<f64> {
+    let n = timestamps.len().min(values.len());
+    if half_window == 0 {
+        return (0..n)
+            .map(|i| {
+                let (a, b) = if i == 0 {
+                    (0, 1.min(n - 1))
+                } else {
+                    (i - 1, i)
+                };
+                let dt = (timestamps[b] as f64 - timestamps[a] as f64) / 1e6;
+                if dt == 0.0 {
+                    0.0
+                } else {
+                    (values[b] - values[a]) / dt
+                }
+            })
+            .collect();
+    }
+    let width = (2 * half_window + 1).min(n);
+    (0..n)
+        .map(|i| {
+            let lo = i.saturating_sub(half_window).min(n - width);
+            let window = lo..lo + width;
+            let dt = |j: usize| (timestamps[j] as f64 - timestamps[i] as f64) / 1e6;
+            // Offsets scaled to [-1, 1] keep the normal equations well conditioned
+            let h = window.clone().map(|j| dt(j).abs()).fold(0.0, f64::max);
+            if h == 0.0 {
+                return 0.0;
+            }
+            let mut s = [0.0; 5];
+            let mut t = [0.0; 3];
+            for j in window {
+                let x = dt(j) / h;
+                let y = values[j] - values[i];
+                let powers = [1.0, x, x * x, x * x * x, x * x * x * x];
+                for (sum, p) in s.iter_mut().zip(powers) {
+                    *sum += p;
+                }
+                for (sum, p) in t.iter_mut().zip(powers) {
+                    *sum += y * p;
+                }
+            }
+            slope(&s, &t) / h
+        })
+        .collect()
+}
+
+/// Linear coefficient of the quadratic (or, failing that, line) fitted from
+/// the power sums `s[k] = Σx^k` and `t[k] = Σy·x^k`.
+fn slope(s: &[f64; 5], t: &[f64; 3]) -> f64 {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let quad = det([[s[0], s[1], s[2]], [s[1], s[2], s[3]], [s[2], s[3], s[4]]]);
+    if quad.abs() > 1e-9 * s[0].powi(3) {
+        return det([[s[0], t[0], s[2]], [s[1], t[1], s[3]], [s[2], t[2], s[4]]]) / quad;
+    }
+    let line = s[0] * s[2] - s[1] * s[1];
+    if line.abs() > 1e-12 * s[0] * s[0] {
+        return (s[0] * t[1] - s[1] * t[0]) / line;
+    }
+    0.0
+}
+
+/// Running integral (value × seconds) of a step signal, each value held
+/// until the next sample as the store treats it; zero at the first sample.
+pub fn integral(timestamps: &[u64], values: &[f64]) -> Vec<f64> {
+    if timestamps.is_empty() || values.is_empty() {
+        return Vec::new();
+    }
+    let steps = timestamps
+        .windows(2)
+        .zip(values)
+        .scan(0.0, |total, (t, v)| {
+            *total += v * (t[1] - t[0]) as f64 / 1e6;
+            Some(*total)
+        });
+    std::iter::once(0.0).chain(steps).collect()
+}
+
+/// Window applied to each segment before its FFT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Window {
+    Rectangular,
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    /// `"rectangular"` (or `"none"`), `"hann"`, `"hamming"` or `"blackman"`;
+    /// empty for the default.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "" => Some(Window::default()),
+            "rectangular" | "none" => Some(Window::Rectangular),
+            "hann" | "hanning" => Some(Window::Hann),
+            "hamming" => Some(Window::Hamming),
+            "blackman" => Some(Window::Blackman),
+            _ => None,
+        }
+    }
+
+    /// `n` periodic window coefficients.
+    fn coefficients(self, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| {
+                let phase = 2.0 * PI * i as f64 / n as f64;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => 0.5 - 0.5 * phase.cos(),
+                    Window::Hamming => 0.54 - 0.46 * phase.cos(),
+                    Window::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// One-sided spectrum, one entry per frequency bin.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spectrum {
+    /// Bin frequencies in Hz, from 0 to half the sample rate.
+    pub frequencies: Vec<f64>,
+    /// Amplitude, so a sine of amplitude `a` on a bin reads `a`.
+    pub magnitudes: Vec<f64>,
+    /// Power spectral density (units² per Hz).
+    pub psd: Vec<f64>,
+}
+
+/// Spectrum of evenly spaced `values` by Welch's method: windowed segments
+/// of `segment` points (a power of two) overlapping by half, each with its
+/// mean removed, their periodograms averaged. A single segment is a plain
+/// windowed FFT.
+pub fn welch(values: &[f64], sample_rate: f64, segment: usize, window: Window) -> Spectrum {
+    if segment < 2 || values.len() < segment {
+        return Spectrum::default();
+    }
+    let coefficients = window.coefficients(segment);
+    let sum: f64 = coefficients.iter().sum();
+    let sum_sq: f64 = coefficients.iter().map(|w| w * w).sum();
+
+    let bins = segment / 2 + 1;
+    let mut power = vec![0.0; bins];
+    let mut segments = 0;
+    let (mut re, mut im) = (vec![0.0; segment], vec![0.0; segment]);
+    for start in (0..=values.len() - segment).step_by(segment / 2) {
+        let chunk = &values[start..start + segment];
+        let mean = chunk.iter().sum::<f64>() / segment as f64;
+        for (i, (&v, &w)) in chunk.iter().zip(&coefficients).enumerate() {
+            re[i] = (v - mean) * w;
+            im[i] = 0.0;
+        }
+        fft(&mut re, &mut im, false);
+        for (k, p) in power.iter_mut().enumerate() {
+            *p += re[k] * re[k] + im[k] * im[k];
+        }
+        segments += 1;
+    }
+
+    let mut spectrum = Spectrum::default();
+    for (k, p) in power.iter().enumerate() {
+        let p = p / segments as f64;
+        // Bins other than DC and Nyquist hold their negative frequency too
+        let one_sided = if k == 0 || k == segment / 2 { 1.0 } else { 2.0 };
+        spectrum
+            .frequencies
+            .push(k as f64 * sample_rate / segment as f64);
+        spectrum.magnitudes.push(one_sided * p.sqrt() / sum);
+        spectrum.psd.push(one_sided * p / (sample_rate * sum_sq));
+    }
+    spectrum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derivative_smoothing() {
+        let ts: Vec<u64> = (0..6).map(|i| i * 500_000).collect();
+        let ramp: Vec<f64> = (0..6).map(|i| i as f64).collect();
+        // A line comes out exact at every smoothing
+        for half_window in 0..4 {
+            assert!(
+                derivative(&ts, &ramp, half_window)
+                    .iter()
+                    .all(|&d| (d - 2.0).abs() < 1e-9)
+            );
+        }
+
+        let step = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        assert_eq!(derivative(&ts, &step, 0), [0.0, 0.0, 0.0, 2.0, 0.0, 0.0]);
+        // The three-point fit spreads the step over its neighbours
+        let smoothed = derivative(&ts, &step, 1);
+        assert!(smoothed[2] > 0.0 && smoothed[3] > 0.0 && smoothed[3] < 2.0);
+        assert_eq!(derivative(&ts[..1], &step[..1], 0), [0.0]);
+        assert!(derivative(&[], &[], 0).is_empty());
+    }
+}
@@ -0,0 +1,600 @@
+use crate::log_store::{LogStore, TopicData};
+use crate::mcap::{McapChannel, McapMessage, McapWriter};
+use crate::mcap_message::{METADATA_KEY, TYPE_KEY, sample_json};
+use crate::wpilog::{WpilogWriter, encode_payload};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::sync::mpsc::{Receiver, TryRecvError, channel};
+
+/// Picks the topics to export. An empty selection means every topic. Fields
+/// flattened out of a selected struct are dropped since they are rebuilt from
+/// the struct when the log is loaded again, and schemas are always included so
+/// struct entries stay decodable.
+pub fn select_topics<'a>(store: &'a LogStore, topics: &[String]) -> Vec<&'a str> {
+    let wanted: HashSet<&str> = topics.iter().map(String::as_str).collect();
+    let mut selected: Vec<&str> = store
+        .topic_names()
+        .filter(|t| wanted.is_empty() || wanted.contains(t))
+        .collect();
+    selected.sort_unstable();
+    selected.dedup();
+
+    let chosen: HashSet<&str> = selected.iter().copied().collect();
+    let is_struct_field = |topic: &str| {
+        topic.match_indices('/').any(|(i, _)| {
+            let parent = &topic[..i];
+            chosen.contains(parent)
+                && store
+                    .get_topic_type(parent)
+                    .is_some_and(|t| t.starts_with("struct:"))
+        })
+    };
+    let mut result: Vec<&str> = selected
+        .iter()
+        .copied()
+        .filter(|t| !is_struct_field(t))
+        .collect();
+
+    for topic in store.topic_names() {
+        if topic.contains("/.schema/") && !chosen.contains(topic) {
+            result.push(topic);
+        }
+    }
+    // Schemas first, so they are read before any struct sample that needs them
+    result.sort_by_key(|t| !t.contains("/.schema/"));
+    result
+}
+
+/// Samples of `data` to write for `start..=end`. The sample in effect at
+/// `start` is included so every topic starts out with its state at the cut.
+fn sample_range(data: &TopicData, start: u64, end: u64) -> Range<usize> {
+    let ts = data.timestamps();
+    let first = ts.partition_point(|&t| t <= start).saturating_sub(1);
+    let last = ts.partition_point(|&t| t <= end);
+    first..last.max(first)
+}
+
+/// Copies what an export of `topics` (every topic when empty) between `start`
+/// and `end` reads into a new store: the topics, the topics under them and
+/// the schemas, cut to the range plus the sample in effect at `start`.
+pub fn snapshot(store: &LogStore, topics: &[String], start: u64, end: u64) -> LogStore {
+    let wanted = |topic: &str| {
+        topics.is_empty()
+            || topic.contains("/.schema/")
+            || topics.iter().any(|t| {
+                topic == t
+                    || topic
+                        .strip_prefix(t.as_str())
+                        .is_some_and(|rest| rest.starts_with('/') || t.ends_with('/'))
+            })
+    };
+    let mut copy = LogStore::new();
+    for topic in store.topic_names().filter(|t| wanted(t)) {
+        let Some(data) = store.topic_range(topic, start, end) else {
+            continue;
+        };
+        let range = sample_range(&data, start, end);
+        copy.insert_topic(topic.to_string(), data.slice(range));
+        if let Some(type_str) = store.topic_types.get(topic) {
+            copy.set_type(topic.to_string(), type_str.clone());
+        }
+        if let Some(metadata) = store.topic_metadata.get(topic) {
+            copy.set_metadata(topic.to_string(), metadata.clone());
+        }
+    }
+    copy
+}
+
+/// An export writing from a [`snapshot`] on a background thread, so neither
+/// the UI nor the store's writers wait on the file.
+pub struct ExportJob {
+    pub path: String,
+    rx: Receiver<io::Result<usize>>,
+}
+
+impl ExportJob {
+    /// Runs `write(snapshot, path)` on a new thread.
+    pub fn spawn(
+        path: String,
+        snapshot: LogStore,
+        write: impl FnOnce(&LogStore, &str) -> io::Result<usize> + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = channel();
+        let thread_path = path.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(write(&snapshot, &thread_path));
+        });
+        Self { path, rx }
+    }
+
+    /// What `write` returned, once it has finished.
+    pub fn poll(&self) -> Option<io::Result<usize>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(io::Error::other("export thread stopped"))),
+        }
+    }
+}
+
+/// Visits the samples of several topics merged into one time-ordered stream,
+/// calling `f(range index, timestamp, sample index)`. Timestamps before `start`
+/// (a carried-in sample) are reported as `start`.
+fn for_each_in_time_order(
+    ranges: &[(&TopicData, Range<usize>)],
+    start: u64,
+    mut f: impl FnMut(usize, u64, usize) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut heap = BinaryHeap::new();
+    for (i, (data, range)) in ranges.iter().enumerate() {
+        if !range.is_empty() {
+            let ts = data.timestamps()[range.start].max(start);
+            heap.push(Reverse((ts, i, range.start)));
+        }
+    }
+    while let Some(Reverse((ts, i, idx))) = heap.pop() {
+        f(i, ts, idx)?;
+        let (data, range) = &ranges[i];
+        if idx + 1 < range.end {
+            heap.push(Reverse((data.timestamps()[idx + 1], i, idx + 1)));
+        }
+    }
+    Ok(())
+}
+
+/// Type string for the exported entry. Stored types are kept when they still
+/// describe the data; otherwise the type is inferred from the column.
+fn export_type(store: &LogStore, topic: &str, data: &TopicData) -> String {
+    let stored = store.get_topic_type(topic).unwrap_or_default();
+    let keep = match data {
+        TopicData::Double(..) => matches!(stored.as_str(), "double" | "float" | "int64"),
+        TopicData::DoubleArray(..) => {
+            matches!(stored.as_str(), "double[]" | "float[]" | "int64[]")
+        }
+        TopicData::String(..) => {
+            if topic.contains("/.schema/") {
+                // Live schemas arrive as NT strings
+                return "structschema".to_string();
+            }
+            matches!(stored.as_str(), "string" | "json")
+        }
+        TopicData::Raw(..) => !stored.is_empty(),
+        _ => false,
+    };
+    if keep {
+        return stored;
+    }
+    match data {
+        TopicData::Double(..) => "double",
+        TopicData::Boolean(..) => "boolean",
+        TopicData::String(..) => "string",
+        TopicData::DoubleArray(..) => "double[]",
+        TopicData::BooleanArray(..) => "boolean[]",
+        TopicData::StringArray(..) => "string[]",
+        TopicData::Raw(..) => "raw",
+    }
+    .to_string()
+}
+
+/// Writes `topics` between `start` and `end` (microseconds, inclusive) to a
+/// WPILib DataLog at `path`, in timestamp order. Returns the number of data
+/// records written.
+pub fn export_wpilog(
+    store: &LogStore,
+    path: &str,
+    topics: &[String],
+    start: u64,
+    end: u64,
+) -> io::Result<usize> {
+    let mut writer = WpilogWriter::new(BufWriter::new(File::create(path)?), "")?;
+
+    struct Entry<'a> {
+        id: u32,
+        type_str: String,
+        data: Cow<'a, TopicData>,
+        range: Range<usize>,
+    }
+
+    let mut entries = Vec::new();
+    for topic in select_topics(store, topics) {
+        let Some(data) = store.topic_data(topic) else {
+            continue;
+        };
+        let range = sample_range(&data, start, end);
+        if range.is_empty() {
+            continue;
+        }
+        let type_str = export_type(store, topic, &data);
+        let metadata = store
+            .topic_metadata
+            .get(topic)
+            .map(String::as_str)
+            .unwrap_or_default();
+        let id = writer.start_entry(topic, &type_str, metadata, start)?;
+        entries.push(Entry {
+            id,
+            type_str,
+            data,
+            range,
+        });
+    }
+
+    let ranges: Vec<_> = entries
+        .iter()
+        .map(|e| (e.data.as_ref(), e.range.clone()))
+        .collect();
+    let mut written = 0;
+    for_each_in_time_order(&ranges, start, |i, ts, idx| {
+        let entry = &entries[i];
+        if let Some(sample) = entry.data.sample(idx) {
+            writer.append(entry.id, ts, &encode_payload(&entry.type_str, &sample))?;
+            written += 1;
+        }
+        Ok(())
+    })?;
+
+    writer.finish()?;
+    Ok(written)
+}
+
+/// Writes `topics` between `start` and `end` (microseconds, inclusive) to an
+/// MCAP file at `path`, one JSON channel per topic. Each message is
+/// `{"value": ...}` with raw payloads as byte arrays; struct topics also carry
+/// their decoded `fields` so other tools can plot them. Returns the number of
+/// messages written.
+pub fn export_mcap(
+    store: &LogStore,
+    path: &str,
+    topics: &[String],
+    start: u64,
+    end: u64,
+) -> io::Result<usize> {
+    let mut writer = McapWriter::new(BufWriter::new(File::create(path)?), "frc-visualizer")?;
+
+    struct Channel<'a> {
+        id: u16,
+        data: Cow<'a, TopicData>,
+        range: Range<usize>,
+        /// Flattened struct fields as `(path below the topic, data)`.
+        fields: Vec<(&'a str, Cow<'a, TopicData>)>,
+    }
+
+    let mut channels = Vec::new();
+    for topic in select_topics(store, topics) {
+        let Some(data) = store.topic_data(topic) else {
+            continue;
+        };
+        let range = sample_range(&data, start, end);
+        if range.is_empty() {
+            continue;
+        }
+        let id = u16::try_from(channels.len() + 1)
+            .map_err(|_| io::Error::other("too many topics for one MCAP file"))?;
+
+        let type_str = export_type(store, topic, &data);
+        let mut fields = Vec::new();
+        if type_str.starts_with("struct:") {
+            let prefix = format!("{}/", topic);
+            fields = store
+                .topic_names()
+                .filter_map(|t| Some((t.strip_prefix(&prefix)?, store.topic_data(t)?)))
+                .collect();
+            fields.sort_unstable_by_key(|(t, _)| *t);
+        }
+
+        let mut metadata = BTreeMap::from([(TYPE_KEY.to_string(), type_str)]);
+        if let Some(entry_metadata) = store.topic_metadata.get(topic) {
+            metadata.insert(METADATA_KEY.to_string(), entry_metadata.clone());
+        }
+        writer.write_channel(&McapChannel {
+            id,
+            schema_id: 0,
+            topic: topic.to_string(),
+            message_encoding: "json".to_string(),
+            metadata,
+        })?;
+        channels.push(Channel {
+            id,
+            data,
+            range,
+            fields,
+        });
+    }
+
+    let ranges: Vec<_> = channels
+        .iter()
+        .map(|c| (c.data.as_ref(), c.range.clone()))
+        .collect();
+    let mut sequences = vec![0u32; channels.len()];
+    let mut written = 0;
+    for_each_in_time_order(&ranges, start, |i, ts, idx| {
+        let channel = &channels[i];
+        let Some(sample) = channel.data.sample(idx) else {
+            return Ok(());
+        };
+        let mut message = serde_json::Map::new();
+        message.insert("value".to_string(), sample_json(&sample));
+        if !channel.fields.is_empty() {
+            message.insert("fields".to_string(), struct_fields(&channel.fields, ts));
+        }
+
+        sequences[i] += 1;
+        writer.write_message(&McapMessage {
+            channel_id: channel.id,
+            sequence: sequences[i],
+            log_time: ts * 1000,
+            publish_time: ts * 1000,
+            data: serde_json::to_vec(&message)?,
+        })?;
+        written += 1;
+        Ok(())
+    })?;
+
+    writer.finish()?;
+    Ok(written)
+}
+
+/// Nests flattened struct fields (`translation/x`, ...) back into one JSON
+/// object, each holding its value at `time`.
+fn struct_fields(fields: &[(&str, Cow<TopicData>)], time: u64) -> serde_json::Value {
+    let mut root = serde_json::Map::new();
+    for (path, data) in fields {
+        let idx = data.timestamps().partition_point(|&t| t <= time);
+        let Some(sample) = idx.checked_sub(1).and_then(|idx| data.sample(idx)) else {
+            continue;
+        };
+        let mut node = &mut root;
+        let mut parts = path.split('/').peekable();
+        while let Some(part) = parts.next() {
+            if parts.peek().is_none() {
+                node.insert(part.to_string(), sample_json(&sample));
+                break;
+            }
+            let child = node
+                .entry(part)
+                .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+            match child {
+                serde_json::Value::Object(map) => node = map,
+                _ => break,
+            }
+        }
+    }
+    serde_json::Value::Object(root)
+}
+
+/// Row layout for [`export_csv`].
+#[derive(Debug, Clone)]
+pub enum CsvLayout {
+    /// One row per sample: `timestamp,topic,value`.
+    Long,
+    /// One column per topic, sampled every `period` microseconds.
+    Wide { period: u64 },
+    /// One column per topic, one row per sample of the reference topic.
+    Aligned { reference: String },
+}
+
+/// Picks the CSV columns. Each selected topic brings the topics under it, so a
+/// struct contributes its flattened fields; raw blobs and schemas are left out
+/// since they have no spreadsheet form.
+pub fn select_csv_topics<'a>(store: &'a LogStore, topics: &[String]) -> Vec<&'a str> {
+    let mut selected: Vec<&str> = store
+        .topic_names()
+        .filter(|topic| !matches!(store.topic_shape(topic), Some(TopicData::Raw(..)) | None))
+        .filter(|topic| !topic.contains("/.schema/"))
+        .filter(|topic| {
+            topics.is_empty()
+                || topics.iter().any(|t| {
+                    topic == t
+                        || topic
+                            .strip_prefix(t.as_str())
+                            .is_some_and(|rest| rest.starts_with('/') || t.ends_with('/'))
+                })
+        })
+        .collect();
+    selected.sort_unstable();
+    selected
+}
+
+/// Sample `idx` of `data` as one CSV field. Arrays are joined with `;`.
+fn csv_value(data: &TopicData, idx: usize) -> Option<String> {
+    fn join<T: ToString>(vals: impl Iterator<Item = T>) -> String {
+        vals.map(|v| v.to_string()).collect::<Vec<_>>().join(";")
+    }
+
+    let value = match data {
+        TopicData::Double(_, vals) => vals.get(idx)?.to_string(),
+        TopicData::Boolean(_, vals) => vals.get(idx)?.to_string(),
+        TopicData::String(_, vals) => vals.get(idx)?.to_string(),
+        TopicData::DoubleArray(_, vals) => join(vals.get(idx)?.iter()),
+        TopicData::BooleanArray(_, vals) => join(vals.get(idx)?.iter()),
+        TopicData::StringArray(_, vals) => join(vals.get(idx)?.iter()),
+        TopicData::Raw(..) => return None,
+    };
+    Some(csv_escape(&value))
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Microseconds as seconds, the unit spreadsheets plot against.
+fn csv_time(micros: u64) -> String {
+    format!("{:.6}", micros as f64 / 1_000_000.0)
+}
+
+/// Writes `topics` (every topic when empty) between `start` and `end`
+/// (microseconds, inclusive) to a CSV file. Returns the number of data rows.
+pub fn export_csv(
+    store: &LogStore,
+    path: &str,
+    topics: &[String],
+    start: u64,
+    end: u64,
+    layout: &CsvLayout,
+) -> io::Result<usize> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let (columns, data): (Vec<&str>, Vec<Cow<TopicData>>) = select_csv_topics(store, topics)
+        .into_iter()
+        .filter_map(|t| Some((t, store.topic_data(t)?)))
+        .unzip();
+
+    let reference = match layout {
+        CsvLayout::Long => {
+            writeln!(out, "timestamp,topic,value")?;
+            let ranges: Vec<_> = data
+                .iter()
+                .map(|d| {
+                    let ts = d.timestamps();
+                    (
+                        d.as_ref(),
+                        ts.partition_point(|&t| t < start)..ts.partition_point(|&t| t <= end),
+                    )
+                })
+                .collect();
+            let mut rows = 0;
+            for_each_in_time_order(&ranges, start, |i, ts, idx| {
+                if let Some(value) = csv_value(&data[i], idx) {
+                    writeln!(out, "{},{},{}", csv_time(ts), csv_escape(columns[i]), value)?;
+                    rows += 1;
+                }
+                Ok(())
+            })?;
+            out.flush()?;
+            return Ok(rows);
+        }
+        CsvLayout::Wide { .. } => None,
+        CsvLayout::Aligned { reference } => store.topic_data(reference),
+    };
+    // Row times are generated as they are written, however many there are
+    let row_times: Box<dyn Iterator<Item = u64>> = match (layout, &reference) {
+        (CsvLayout::Wide { period }, _) => {
+            Box::new((start..=end).step_by((*period).max(1) as usize))
+        }
+        (_, Some(reference)) => {
+            let ts = reference.timestamps();
+            let range = ts.partition_point(|&t| t < start)..ts.partition_point(|&t| t <= end);
+            Box::new(ts[range].iter().copied())
+        }
+        _ => Box::new(std::iter::empty()),
+    };
+
+    let header: Vec<String> = columns.iter().map(|t| csv_escape(t)).collect();
+    writeln!(out, "timestamp,{}", header.join(","))?;
+
+    // Row times only move forward, so each column keeps a cursor on the last
+    // sample at or before the current row (zero-order hold).
+    let mut cursors = vec![None::<usize>; columns.len()];
+    let mut fields = Vec::with_capacity(columns.len());
+    let mut rows = 0;
+    for time in row_times {
+        fields.clear();
+        for (d, cursor) in data.iter().zip(cursors.iter_mut()) {
+            let ts = d.timestamps();
+            let mut next = cursor.map_or(0, |c| c + 1);
+            while next < ts.len() && ts[next] <= time {
+                *cursor = Some(next);
+                next += 1;
+            }
+            fields.push(cursor.and_then(|c| csv_value(d, c)).unwrap_or_default());
+        }
+        writeln!(out, "{},{}", csv_time(time), fields.join(","))?;
+        rows += 1;
+    }
+
+    out.flush()?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> LogStore {
+        let mut store = LogStore::new();
+        for i in 0..50u64 {
+            store.update_double("/Drive/Speed".to_string(), 1_000 + i * 20_000, i as f64);
+            if i % 7 == 0 {
+                store.update_boolean("/Drive/Enabled".to_string(), 1_000 + i * 20_000, i % 2 == 0);
+            }
+        }
+        store.update_string("/Other".to_string(), 5_000, "x");
+        store
+    }
+
+    fn export_both(topics: &[String], start: u64, end: u64, layout: CsvLayout) -> (String, String) {
+        // Tests run in parallel; every export gets its own files
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let dir = std::env::temp_dir();
+        let id = format!(
+            "{}_{}",
+            std::process::id(),
+            NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        );
+        let direct = dir.join(format!("export_direct_{}.csv", id));
+        let job = dir.join(format!("export_job_{}.csv", id));
+        let store = store();
+
+        let rows = export_csv(
+            &store,
+            direct.to_str().unwrap(),
+            topics,
+            start,
+            end,
+            &layout,
+        )
+        .unwrap();
+        let topics = topics.to_vec();
+        let job = ExportJob::spawn(
+            job.to_str().unwrap().to_string(),
+            snapshot(&store, &topics, start, end),
+            move |store, path| export_csv(store, path, &topics, start, end, &layout),
+        );
+        let result = loop {
+            if let Some(result) = job.poll() {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(result.unwrap(), rows);
+
+        let read = |path: &std::path::Path| {
+            let text = std::fs::read_to_string(path).unwrap();
+            std::fs::remove_file(path).unwrap();
+            text
+        };
+        (read(&direct), read(std::path::Path::new(&job.path)))
+    }
+
+    #[test]
+    fn snapshot_exports_like_the_store() {
+        let drive = vec!["/Drive".to_string()];
+        for layout in [
+            CsvLayout::Long,
+            CsvLayout::Wide { period: 15_000 },
+            CsvLayout::Aligned {
+                reference: "/Drive/Enabled".to_string(),
+            },
+        ] {
+            let (direct, job) = export_both(&drive, 130_000, 700_000, layout);
+            assert!(direct.lines().count() > 2);
+            assert_eq!(direct, job);
+        }
+        let (direct, job) = export_both(&[], 0, 2_000_000, CsvLayout::Wide { period: 20_000 });
+        assert_eq!(direct, job);
+    }
+
+    #[test]
+    fn wide_rows_follow_the_period() {
+        let (direct, _) = export_both(&[], 0, 100_000, CsvLayout::Wide { period: 25_000 });
+        // Header plus rows at 0, 25, 50, 75 and 100 ms
+        assert_eq!(direct.lines().count(), 6);
+    }
+}
@@ -0,0 +1,697 @@
+//! Expressions over topics for computed topics, e.g.
+//! `hypot(/Drive/VelocityX, /Drive/VelocityY)` or
+//! `deriv("/Arm/Position (rad)") * 60`.
+//!
+//! Topics are written as paths starting with `/` (letters, digits, `_`, `.`,
+//! `:` and `/`; put spaces around a division that follows one) or as quoted
+//! names. Boolean topics read as 1 and 0. Operators, loosest first: `? :`,
+//! `||`, `&&`, comparisons, `+ -`, `* / %`, unary `- !`, `^`. Besides the
+//! usual math functions there are `hypot`, `min`, `max`, `sum` and `avg` over
+//! any number of arguments, `clamp(x, lo, hi)`, `if(c, a, b)`, and `deriv(x)`
+//! and `integral(x)` with respect to time in seconds.
+
+/// A parsed expression. Evaluation keeps state (for `deriv` and `integral`)
+/// in an [`EvalState`] that advances through time.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Node,
+    inputs: Vec<String>,
+    slots: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f64),
+    Input(usize),
+    Neg(Box<Node>),
+    Not(Box<Node>),
+    Binary(BinOp, Box<Node>, Box<Node>),
+    Cond(Box<Node>, Box<Node>, Box<Node>),
+    Call(Func, Vec<Node>),
+    /// Rate of change of `arg`, updated when one of `inputs` has a sample.
+    Derivative {
+        slot: usize,
+        inputs: Vec<usize>,
+        arg: Box<Node>,
+    },
+    Integral {
+        slot: usize,
+        arg: Box<Node>,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+/// Functions without state.
+#[derive(Debug, Clone, Copy)]
+enum Func {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Floor,
+    Ceil,
+    Round,
+    Sign,
+    Deg,
+    Rad,
+    Pow,
+    Hypot,
+    Min,
+    Max,
+    Sum,
+    Avg,
+    Clamp,
+    If,
+}
+
+impl Func {
+    /// The function and its argument count (`None`: one or more).
+    fn lookup(name: &str) -> Option<(Func, Option<usize>)> {
+        let func = match name {
+            "abs" => (Func::Abs, Some(1)),
+            "sqrt" => (Func::Sqrt, Some(1)),
+            "exp" => (Func::Exp, Some(1)),
+            "ln" | "log" => (Func::Ln, Some(1)),
+            "log10" => (Func::Log10, Some(1)),
+            "sin" => (Func::Sin, Some(1)),
+            "cos" => (Func::Cos, Some(1)),
+            "tan" => (Func::Tan, Some(1)),
+            "asin" => (Func::Asin, Some(1)),
+            "acos" => (Func::Acos, Some(1)),
+            "atan" => (Func::Atan, Some(1)),
+            "atan2" => (Func::Atan2, Some(2)),
+            "floor" => (Func::Floor, Some(1)),
+            "ceil" => (Func::Ceil, Some(1)),
+            "round" => (Func::Round, Some(1)),
+            "sign" => (Func::Sign, Some(1)),
+            "deg" => (Func::Deg, Some(1)),
+            "rad" => (Func::Rad, Some(1)),
+            "pow" => (Func::Pow, Some(2)),
+            "hypot" => (Func::Hypot, None),
+            "min" => (Func::Min, None),
+            "max" => (Func::Max, None),
+            "sum" => (Func::Sum, None),
+            "avg" | "mean" => (Func::Avg, None),
+            "clamp" => (Func::Clamp, Some(3)),
+            "if" => (Func::If, Some(3)),
+            _ => return None,
+        };
+        Some(func)
+    }
+
+    fn apply(self, args: &[f64]) -> f64 {
+        let x = args[0];
+        match self {
+            Func::Abs => x.abs(),
+            Func::Sqrt => x.sqrt(),
+            Func::Exp => x.exp(),
+            Func::Ln => x.ln(),
+            Func::Log10 => x.log10(),
+            Func::Sin => x.sin(),
+            Func::Cos => x.cos(),
+            Func::Tan => x.tan(),
+            Func::Asin => x.asin(),
+            Func::Acos => x.acos(),
+            Func::Atan => x.atan(),
+            Func::Atan2 => x.atan2(args[1]),
+            Func::Floor => x.floor(),
+            Func::Ceil => x.ceil(),
+            Func::Round => x.round(),
+            Func::Sign => {
+                if x == 0.0 {
+                    0.0
+                } else {
+                    x.signum()
+                }
+            }
+            Func::Deg => x.to_degrees(),
+            Func::Rad => x.to_radians(),
+            Func::Pow => x.powf(args[1]),
+            Func::Hypot => args.iter().map(|v| v * v).sum::<f64>().sqrt(),
+            Func::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+            Func::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Func::Sum => args.iter().sum(),
+            Func::Avg => args.iter().sum::<f64>() / args.len() as f64,
+            Func::Clamp => x.max(args[1]).min(args[2]),
+            Func::If => {
+                if x != 0.0 {
+                    args[1]
+                } else {
+                    args[2]
+                }
+            }
+        }
+    }
+}
+
+/// Progress of `deriv` or `integral`: the last point seen and the result.
+#[derive(Debug, Clone, Default)]
+struct Slot {
+    last: Option<(u64, f64)>,
+    value: f64,
+}
+
+/// Evaluation state at some time: the value held by each input and the
+/// progress of stateful functions.
+#[derive(Debug, Clone)]
+pub struct EvalState {
+    pub time: Option<u64>,
+    values: Vec<Option<f64>>,
+    slots: Vec<Slot>,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        Self::parse_with_names(source, &[])
+    }
+
+    /// Like [`Self::parse`], but the bare words in `names` are inputs too,
+    /// listed in [`Self::inputs`] under those names for the caller to feed.
+    pub fn parse_with_names(source: &str, names: &[&str]) -> Result<Self, String> {
+        let mut parser = Parser {
+            src: source.as_bytes(),
+            pos: 0,
+            names,
+            inputs: Vec::new(),
+            slots: 0,
+        };
+        let root = parser.ternary()?;
+        parser.skip_space();
+        if parser.pos < parser.src.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(Self {
+            root,
+            inputs: parser.inputs,
+            slots: parser.slots,
+        })
+    }
+
+    /// Topics the expression reads, indexed as in [`Self::step`].
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    pub fn new_state(&self) -> EvalState {
+        EvalState {
+            time: None,
+            values: vec![None; self.inputs.len()],
+            slots: vec![Slot::default(); self.slots],
+        }
+    }
+
+    /// Advances `state` to `time`, where `samples` holds the new `(input,
+    /// value)` samples at `time` in order. Returns the value at `time`, or
+    /// `None` while some input has no sample yet.
+    pub fn step(&self, state: &mut EvalState, time: u64, samples: &[(usize, f64)]) -> Option<f64> {
+        let mut updated = vec![false; self.inputs.len()];
+        for &(input, value) in samples {
+            state.values[input] = Some(value);
+            updated[input] = true;
+        }
+        state.time = Some(time);
+
+        let values: Option<Vec<f64>> = state.values.iter().copied().collect();
+        let mut eval = Eval {
+            values: &values?,
+            updated: &updated,
+            slots: &mut state.slots,
+            time,
+        };
+        Some(eval.node(&self.root))
+    }
+}
+
+struct Eval<'a> {
+    values: &'a [f64],
+    updated: &'a [bool],
+    slots: &'a mut [Slot],
+    time: u64,
+}
+
+impl Eval<'_> {
+    /// Every branch is evaluated, so `deriv` and `integral` inside a
+    /// conditional keep up with time whichever branch is taken.
+    fn node(&mut self, node: &Node) -> f64 {
+        let truth = |v: bool| if v { 1.0 } else { 0.0 };
+        match node {
+            Node::Number(v) => *v,
+            Node::Input(idx) => self.values[*idx],
+            Node::Neg(arg) => -self.node(arg),
+            Node::Not(arg) => truth(self.node(arg) == 0.0),
+            Node::Binary(op, a, b) => {
+                let (a, b) = (self.node(a), self.node(b));
+                match op {
+                    BinOp::Add => a + b,
+                    BinOp::Sub => a - b,
+                    BinOp::Mul => a * b,
+                    BinOp::Div => a / b,
+                    BinOp::Rem => a % b,
+                    BinOp::Pow => a.powf(b),
+                    BinOp::Lt => truth(a < b),
+                    BinOp::Le => truth(a <= b),
+                    BinOp::Gt => truth(a > b),
+                    BinOp::Ge => truth(a >= b),
+                    BinOp::Eq => truth(a == b),
+                    BinOp::Ne => truth(a != b),
+                    BinOp::And => truth(a != 0.0 && b != 0.0),
+                    BinOp::Or => truth(a != 0.0 || b != 0.0),
+                }
+            }
+            Node::Cond(cond, a, b) => {
+                let (cond, a, b) = (self.node(cond), self.node(a), self.node(b));
+                if cond != 0.0 { a } else { b }
+            }
+            Node::Call(func, args) => {
+                let args: Vec<f64> = args.iter().map(|arg| self.node(arg)).collect();
+                func.apply(&args)
+            }
+            Node::Derivative { slot, inputs, arg } => {
+                let value = self.node(arg);
+                let time = self.time;
+                let slot = &mut self.slots[*slot];
+                // Differentiated between the samples of its inputs, held in between
+                if inputs.iter().any(|&idx| self.updated[idx]) {
+                    if let Some((last_time, last_value)) = slot.last
+                        && time > last_time
+                    {
+                        slot.value = (value - last_value) / ((time - last_time) as f64 / 1e6);
+                    }
+                    slot.last = Some((time, value));
+                }
+                slot.value
+            }
+            Node::Integral { slot, arg } => {
+                let value = self.node(arg);
+                let time = self.time;
+                let slot = &mut self.slots[*slot];
+                // Each value holds until the next point
+                if let Some((last_time, last_value)) = slot.last {
+                    slot.value += last_value * (time - last_time) as f64 / 1e6;
+                }
+                slot.last = Some((time, value));
+                slot.value
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    names: &'a [&'a str],
+    inputs: Vec<String>,
+    slots: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.pos + 1)
+    }
+
+    fn skip_space(&mut self) {
+        while self.src.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        if self.src[self.pos..].starts_with(token.as_bytes()) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", token)))
+        }
+    }
+
+    fn ternary(&mut self) -> Result<Node, String> {
+        let cond = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let a = self.ternary()?;
+        self.expect(":")?;
+        let b = self.ternary()?;
+        Ok(Node::Cond(Box::new(cond), Box::new(a), Box::new(b)))
+    }
+
+    /// Left-associative binary operators, loosest level first.
+    fn binary(&mut self, level: usize) -> Result<Node, String> {
+        const LEVELS: &[&[(&str, BinOp)]] = &[
+            &[("||", BinOp::Or)],
+            &[("&&", BinOp::And)],
+            &[
+                ("==", BinOp::Eq),
+                ("!=", BinOp::Ne),
+                ("<=", BinOp::Le),
+                (">=", BinOp::Ge),
+                ("<", BinOp::Lt),
+                (">", BinOp::Gt),
+            ],
+            &[("+", BinOp::Add), ("-", BinOp::Sub)],
+            &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Rem)],
+        ];
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+
+        let mut node = self.binary(level + 1)?;
+        'outer: loop {
+            for &(token, op) in *ops {
+                if self.eat(token) {
+                    let rhs = self.binary(level + 1)?;
+                    node = Node::Binary(op, Box::new(node), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(node);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat("-") {
+            Ok(Node::Neg(Box::new(self.unary()?)))
+        } else if self.eat("!") {
+            Ok(Node::Not(Box::new(self.unary()?)))
+        } else if self.eat("+") {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    /// `^` binds tighter than unary minus on its left and is right-associative.
+    fn power(&mut self) -> Result<Node, String> {
+        let base = self.primary()?;
+        if self.eat("^") {
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(BinOp::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        self.skip_space();
+        let Some(&c) = self.src.get(self.pos) else {
+            return Err(self.error("unexpected end of expression"));
+        };
+        match c {
+            b'(' => {
+                self.pos += 1;
+                let node = self.ternary()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            b'"' => {
+                let start = self.pos + 1;
+                let len = self.src[start..]
+                    .iter()
+                    .position(|&c| c == b'"')
+                    .ok_or_else(|| self.error("unterminated topic name"))?;
+                self.pos = start + len + 1;
+                let name = String::from_utf8_lossy(&self.src[start..start + len]);
+                Ok(self.input(&name))
+            }
+            b'/' => {
+                let len = self.src[self.pos..]
+                    .iter()
+                    .position(|&c| !(c.is_ascii_alphanumeric() || b"_/.:".contains(&c)))
+                    .unwrap_or(self.src.len() - self.pos);
+                let name = String::from_utf8_lossy(&self.src[self.pos..self.pos + len]);
+                self.pos += len;
+                Ok(self.input(&name))
+            }
+            c if c.is_ascii_digit() || c == b'.' => self.number(),
+            c if c.is_ascii_alphabetic() || c == b'_' => self.call(),
+            _ => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        while self
+            .src
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || *c == b'.')
+        {
+            self.pos += 1;
+        }
+        // Exponent, only if digits follow
+        if matches!(self.src.get(self.pos), Some(b'e' | b'E')) {
+            let mut end = self.pos + 1;
+            if matches!(self.src.get(end), Some(b'+' | b'-')) {
+                end += 1;
+            }
+            if self.src.get(end).is_some_and(u8::is_ascii_digit) {
+                self.pos = end;
+                while self.src.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                    self.pos += 1;
+                }
+            }
+        }
+        let text = std::str::from_utf8(&self.src[start..self.pos]).unwrap_or_default();
+        text.parse()
+            .map(Node::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn call(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        while self
+            .src
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_')
+        {
+            self.pos += 1;
+        }
+        let name = std::str::from_utf8(&self.src[start..self.pos])
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if !self.eat("(") {
+            return match name.as_str() {
+                "pi" => Ok(Node::Number(std::f64::consts::PI)),
+                "e" => Ok(Node::Number(std::f64::consts::E)),
+                "true" => Ok(Node::Number(1.0)),
+                "false" => Ok(Node::Number(0.0)),
+                _ if self.names.contains(&name.as_str()) => Ok(self.input(&name)),
+                _ => Err(format!("unknown name '{}'", name)),
+            };
+        }
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.ternary()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        let arity = |expected: usize| {
+            if args.len() == expected {
+                Ok(())
+            } else {
+                Err(format!("{}() takes {} argument(s)", name, expected))
+            }
+        };
+        match name.as_str() {
+            "deriv" | "ddt" => {
+                arity(1)?;
+                let slot = self.slot();
+                // Updated when a topic the argument reads has a sample
+                let mut inputs = Vec::new();
+                collect_inputs(&args[0], &mut inputs);
+                let arg = Box::new(args.remove(0));
+                Ok(Node::Derivative { slot, inputs, arg })
+            }
+            "integral" | "integrate" => {
+                arity(1)?;
+                let slot = self.slot();
+                Ok(Node::Integral {
+                    slot,
+                    arg: Box::new(args.remove(0)),
+                })
+            }
+            _ => {
+                let (func, expected) =
+                    Func::lookup(&name).ok_or_else(|| format!("unknown function '{}'", name))?;
+                match expected {
+                    Some(expected) => arity(expected)?,
+                    None if args.is_empty() => {
+                        return Err(format!("{}() needs at least one argument", name));
+                    }
+                    None => {}
+                }
+                Ok(Node::Call(func, args))
+            }
+        }
+    }
+
+    fn input(&mut self, name: &str) -> Node {
+        let idx = match self.inputs.iter().position(|t| t == name) {
+            Some(idx) => idx,
+            None => {
+                self.inputs.push(name.to_string());
+                self.inputs.len() - 1
+            }
+        };
+        Node::Input(idx)
+    }
+
+    fn slot(&mut self) -> usize {
+        self.slots += 1;
+        self.slots - 1
+    }
+}
+
+fn collect_inputs(node: &Node, out: &mut Vec<usize>) {
+    match node {
+        Node::Number(_) => {}
+        Node::Input(idx) => {
+            if !out.contains(idx) {
+                out.push(*idx);
+            }
+        }
+        Node::Neg(arg) | Node::Not(arg) => collect_inputs(arg, out),
+        Node::Derivative { arg, .. } | Node::Integral { arg, .. } => collect_inputs(arg, out),
+        Node::Binary(_, a, b) => {
+            collect_inputs(a, out);
+            collect_inputs(b, out);
+        }
+        Node::Cond(cond, a, b) => {
+            collect_inputs(cond, out);
+            collect_inputs(a, out);
+            collect_inputs(b, out);
+        }
+        Node::Call(_, args) => args.iter().for_each(|arg| collect_inputs(arg, out)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Value of an expression without inputs.
+    fn eval(source: &str) -> f64 {
+        let expr = Expression::parse(source).unwrap();
+        expr.step(&mut expr.new_state(), 0, &[]).unwrap()
+    }
+
+    fn error(source: &str) -> String {
+        Expression::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+        assert_eq!(eval("12 / 3 / 2"), 2.0);
+        assert_eq!(eval("2 * 7 % 4"), 2.0);
+        assert_eq!(eval("1 + 1 == 2 && 3 < 2 || 4 >= 4"), 1.0);
+        assert_eq!(eval("!0 + 1"), 2.0);
+        assert_eq!(eval("0 ? 2 : 0 ? 3 : 4"), 4.0);
+        assert_eq!(eval("1 < 2 ? 5 : 6"), 5.0);
+        assert_eq!(eval("2e3 + .5"), 2000.5);
+        assert_eq!(eval("2e-1 * e"), 0.2 * std::f64::consts::E);
+        // An `e` without digits isn't an exponent
+        assert!(error("2e").contains("unexpected input"));
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("(2 ^ 3) ^ 2"), 64.0);
+        assert_eq!(eval("-2 ^ 2"), -4.0);
+        assert_eq!(eval("2 ^ -1"), 0.5);
+        assert_eq!(eval("2 * 3 ^ 2"), 18.0);
+    }
+
+    #[test]
+    fn paths_and_division() {
+        let expr = Expression::parse("/Drive/Speed").unwrap();
+        assert_eq!(expr.inputs(), ["/Drive/Speed"]);
+        let expr = Expression::parse("/Drive/2").unwrap();
+        assert_eq!(expr.inputs(), ["/Drive/2"]);
+
+        let expr = Expression::parse("/Drive / 2").unwrap();
+        assert_eq!(expr.inputs(), ["/Drive"]);
+        assert_eq!(expr.step(&mut expr.new_state(), 1, &[(0, 8.0)]), Some(4.0));
+
+        let expr = Expression::parse(r#""/Arm/Position (rad)" / /Arm/Ratio + /Arm/Ratio"#).unwrap();
+        assert_eq!(expr.inputs(), ["/Arm/Position (rad)", "/Arm/Ratio"]);
+        assert_eq!(
+            expr.step(&mut expr.new_state(), 1, &[(0, 6.0), (1, 2.0)]),
+            Some(5.0)
+        );
+    }
+
+    #[test]
+    fn arity_errors() {
+        assert!(error("atan2(1)").contains("takes 2"));
+        assert!(error("clamp(1, 2)").contains("takes 3"));
+        assert!(error("deriv(/a, /b)").contains("takes 1"));
+        assert!(error("max()").contains("at least one"));
+        assert!(error("nope(1)").contains("unknown function"));
+        assert!(error("nope").contains("unknown name"));
+        assert!(error("1 +").contains("end of expression"));
+        assert!(error("(1").contains("expected ')'"));
+        assert!(error("1 2").contains("unexpected input"));
+        assert_eq!(eval("max(1, 5, 3) + min(4) + avg(1, 3)"), 11.0);
+    }
+
+    #[test]
+    fn deriv_and_integral_follow_time() {
+        let expr = Expression::parse("deriv(/x) + integral(/y)").unwrap();
+        let mut state = expr.new_state();
+        // Nothing until both inputs have a sample
+        assert_eq!(expr.step(&mut state, 0, &[(0, 0.0)]), None);
+        assert_eq!(expr.step(&mut state, 0, &[(0, 0.0), (1, 2.0)]), Some(0.0));
+        assert_eq!(
+            expr.step(&mut state, 1_000_000, &[(0, 3.0)]),
+            Some(3.0 + 2.0)
+        );
+        // The derivative holds between samples of its inputs
+        assert_eq!(
+            expr.step(&mut state, 2_000_000, &[(1, 0.0)]),
+            Some(3.0 + 4.0)
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Time spent at each spot of the field, binned from a pose topic for the
+//! Heatmap visualizer.
+
+use crate::log_store::LogStore;
+use crate::pose::PoseFormat;
+
+/// Most cells a grid may have, so a tiny resolution can't exhaust memory.
+const MAX_CELLS: usize = 1 << 22;
+
+/// Grid over the field (WPILib axes, origin at a corner) of square cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeatmapGrid {
+    pub width: usize,
+    pub height: usize,
+    /// Cell size in meters.
+    pub resolution: f64,
+}
+
+impl HeatmapGrid {
+    /// Cells of `resolution` meters covering a field of `length` × `width`;
+    /// `None` unless all are positive and finite and the grid has at most
+    /// [`MAX_CELLS`] cells.
+    pub fn new(length: f64, width: f64, resolution: f64) -> Option<Self> {
+        if !(resolution > 0.0 && resolution.is_finite() && length > 0.0 && width > 0.0) {
+            return None;
+        }
+        let count = |meters: f64| {
+            let cells = (meters / resolution).ceil();
+            (cells.is_finite() && cells <= MAX_CELLS as f64).then_some(cells as usize)
+        };
+        let (cols, rows) = (count(length)?, count(width)?);
+        if cols.checked_mul(rows)? > MAX_CELLS {
+            return None;
+        }
+        Some(Self {
+            width: cols,
+            height: rows,
+            resolution,
+        })
+    }
+
+    fn cell(&self, x: f64, y: f64) -> Option<usize> {
+        let (col, row) = ((x / self.resolution).floor(), (y / self.resolution).floor());
+        if col < 0.0 || row < 0.0 || col >= self.width as f64 || row >= self.height as f64 {
+            return None;
+        }
+        Some(row as usize * self.width + col as usize)
+    }
+}
+
+impl LogStore {
+    /// Seconds each cell of `grid` was occupied over `spans`, scaled so the
+    /// busiest cell is 1; rows from `y = 0` up, `width` cells each. Every
+    /// pose of an array topic counts, and each sample holds until the next.
+    /// `None` if the topic doesn't hold poses (see [`PoseFormat::from_type`]
+    /// for `legacy`).
+    pub fn heatmap(
+        &self,
+        topic: &str,
+        legacy: bool,
+        spans: &[(u64, u64)],
+        grid: &HeatmapGrid,
+    ) -> Option<Vec<f32>> {
+        let format = PoseFormat::from_type(&self.get_topic_type(topic)?, legacy)?;
+        if !self.has_topic(topic) {
+            return None;
+        }
+
+        let mut cells = vec![0.0f64; grid.width * grid.height];
+        let mut poses = Vec::new();
+        for &(start, end) in spans {
+            let Some(data) = self.topic_range(topic, start, end) else {
+                continue;
+            };
+            let ts = data.timestamps();
+            // Samples in the span, plus the one in effect at its start
+            let mut first = ts.partition_point(|&t| t < start);
+            let last = ts.partition_point(|&t| t <= end);
+            if first > 0 && ts.get(first) != Some(&start) {
+                first -= 1;
+            }
+            for (idx, &time) in ts.iter().enumerate().take(last).skip(first) {
+                let from = time.max(start);
+                let to = if idx + 1 < last { ts[idx + 1] } else { end };
+                let seconds = to.saturating_sub(from) as f64 / 1e6;
+                poses.clear();
+                format.decode(&data, idx, &mut poses);
+                for pose in &poses {
+                    if let Some(cell) = grid.cell(pose.x, pose.y) {
+                        cells[cell] += seconds;
+                    }
+                }
+            }
+        }
+
+        let max = cells.iter().copied().fold(0.0, f64::max);
+        let scale = if max > 0.0 { 1.0 / max } else { 0.0 };
+        Some(cells.iter().map(|&v| (v * scale) as f32).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grids_stay_bounded() {
+        let grid = HeatmapGrid::new(16.5, 8.1, 0.5).unwrap();
+        assert_eq!((grid.width, grid.height), (33, 17));
+        assert_eq!(grid.cell(16.4, 8.0), Some(16 * 33 + 32));
+        assert_eq!(grid.cell(16.5, 0.0), None);
+
+        assert!(HeatmapGrid::new(16.5, 8.1, 1e-9).is_none());
+        assert!(HeatmapGrid::new(16.5, 8.1, f64::MIN_POSITIVE).is_none());
+        assert!(HeatmapGrid::new(f64::INFINITY, 8.1, 0.5).is_none());
+        assert!(HeatmapGrid::new(16.5, 8.1, f64::NAN).is_none());
+        assert!(HeatmapGrid::new(16.5, 8.1, 0.0).is_none());
+        assert!(HeatmapGrid::new(16.5, 8.1, f64::INFINITY).is_none());
+        let grid = HeatmapGrid::new(16.5, 8.1, 100.0).unwrap();
+        assert_eq!((grid.width, grid.height), (1, 1));
+    }
+}
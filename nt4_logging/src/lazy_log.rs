@@ -0,0 +1,440 @@
+//! Indexed access to .wpilog files too large to hold in memory. One pass over
+//! the file records where each entry's records sit; values are decoded a block
+//! of records at a time when a query needs them, and recently used blocks are
+//! kept in a small LRU cache.
+
+use crate::batch::{Sample, StoreOp};
+use crate::log_store::{LogStore, TopicData};
+use crate::schema::{Schema, decode_struct_entry, flatten_struct_entry};
+use crate::wpilog::{Record, WpilogReader, decode_payload, read_record};
+use godot::prelude::*;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, Seek};
+use std::sync::Arc;
+
+/// Records decoded together when a query lands in an entry.
+const BLOCK: usize = 1024;
+/// Blocks kept decoded, across all entries.
+const CACHE_BLOCKS: usize = 256;
+/// Records between progress reports during the index pass.
+const PROGRESS_INTERVAL: usize = 65_536;
+
+/// One log entry (by name, so a restarted entry continues the same index).
+struct Entry {
+    name: String,
+    type_str: String,
+    metadata: String,
+    /// Record timestamps, sorted, with the file offset of each record.
+    timestamps: Vec<u64>,
+    offsets: Vec<u64>,
+}
+
+/// A topic backed by an entry: the entry itself or a field flattened out of
+/// its struct payload.
+struct LazyTopic {
+    entry: usize,
+    /// Empty column of the topic's kind.
+    shape: TopicData,
+}
+
+/// Decoded topics of one block, the entry and its struct fields.
+type Block = Arc<HashMap<String, TopicData>>;
+
+struct BlockCache {
+    blocks: HashMap<(usize, usize), (u64, Block)>,
+    tick: u64,
+}
+
+/// Reader positioned somewhere in the file, so nearby records are read from
+/// its buffer instead of seeking.
+struct FileCursor {
+    reader: BufReader<File>,
+    position: u64,
+    len: u64,
+}
+
+pub struct LazyLog {
+    file: Mutex<FileCursor>,
+    entries: Vec<Entry>,
+    topics: HashMap<String, LazyTopic>,
+    /// Topics in the order they first appeared.
+    order: Vec<String>,
+    schemas: HashMap<String, Schema>,
+    cache: Mutex<BlockCache>,
+}
+
+impl LazyLog {
+    /// Indexes `file`. `progress` is called with the bytes read so far and
+    /// stops the pass (returning `None`) when it returns `false`.
+    pub fn open(file: File, mut progress: impl FnMut(u64) -> bool) -> io::Result<Option<Self>> {
+        let mut reads = file.try_clone()?;
+        let len = file.metadata()?.len();
+        let mut reader = WpilogReader::new(BufReader::new(file), len)?;
+
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut by_name: HashMap<String, usize> = HashMap::new();
+        let mut by_id: HashMap<u32, usize> = HashMap::new();
+        let mut topics = HashMap::new();
+        let mut order = Vec::new();
+        let mut schemas = HashMap::new();
+        // Struct entries whose fields are known
+        let mut flattened = HashSet::new();
+        let mut records = 0;
+
+        loop {
+            let offset = reader.position();
+            let Some(record) = reader.next_record()? else {
+                break;
+            };
+            match record {
+                Record::Start { entry, info } => {
+                    let idx = *by_name.entry(info.name.clone()).or_insert_with(|| {
+                        entries.push(Entry {
+                            name: info.name,
+                            type_str: info.type_str,
+                            metadata: String::new(),
+                            timestamps: Vec::new(),
+                            offsets: Vec::new(),
+                        });
+                        entries.len() - 1
+                    });
+                    if !info.metadata.is_empty() {
+                        entries[idx].metadata = info.metadata;
+                    }
+                    by_id.insert(entry, idx);
+                }
+                Record::Finish { entry } => {
+                    by_id.remove(&entry);
+                }
+                Record::SetMetadata { entry, metadata } => {
+                    if let Some(&idx) = by_id.get(&entry) {
+                        entries[idx].metadata = metadata;
+                    }
+                }
+                Record::Data {
+                    entry,
+                    timestamp,
+                    payload,
+                } => {
+                    let Some(&idx) = by_id.get(&entry) else {
+                        continue;
+                    };
+                    let entry = &mut entries[idx];
+                    entry.timestamps.push(timestamp);
+                    entry.offsets.push(offset);
+
+                    // Schemas are small and needed to decode any struct block;
+                    // struct fields are looked for until a schema makes them decodable
+                    let mut ops = Vec::new();
+                    if entry.name.contains("/.schema/")
+                        || (entry.type_str.starts_with("struct:") && !flattened.contains(&idx))
+                    {
+                        decode_struct_entry(
+                            &entry.name,
+                            &entry.type_str,
+                            &payload,
+                            timestamp,
+                            &mut schemas,
+                            &mut ops,
+                        );
+                        if !ops.is_empty() {
+                            flattened.insert(idx);
+                        }
+                    }
+                    // The first decodable record names the topic and its fields
+                    if !topics.contains_key(&entry.name)
+                        && let Some(sample) = decode_payload(&entry.type_str, &payload)
+                    {
+                        ops.push(StoreOp::sample(entry.name.clone(), timestamp, sample));
+                    }
+                    for op in ops {
+                        if let StoreOp::Sample { topic, value, .. } = op
+                            && !topics.contains_key(&topic)
+                        {
+                            let shape = empty_column(&value);
+                            topics.insert(topic.clone(), LazyTopic { entry: idx, shape });
+                            order.push(topic);
+                        }
+                    }
+                }
+            }
+
+            records += 1;
+            if records % PROGRESS_INTERVAL == 0 && !progress(reader.position()) {
+                return Ok(None);
+            }
+        }
+
+        // Late records (merged or backfilled logs) go where their time says
+        for entry in &mut entries {
+            if !entry.timestamps.is_sorted() {
+                let mut pairs: Vec<(u64, u64)> = entry
+                    .timestamps
+                    .iter()
+                    .copied()
+                    .zip(entry.offsets.iter().copied())
+                    .collect();
+                pairs.sort_by_key(|&(t, _)| t);
+                (entry.timestamps, entry.offsets) = pairs.into_iter().unzip();
+            }
+        }
+
+        // The clone shares the index pass's file position
+        reads.rewind()?;
+        Ok(Some(Self {
+            file: Mutex::new(FileCursor {
+                reader: BufReader::new(reads),
+                position: 0,
+                len,
+            }),
+            entries,
+            topics,
+            order,
+            schemas,
+            cache: Mutex::new(BlockCache {
+                blocks: HashMap::new(),
+                tick: 0,
+            }),
+        }))
+    }
+
+    /// Topics in the order they first appeared.
+    pub fn topics(&self) -> &[String] {
+        &self.order
+    }
+
+    pub fn has_topic(&self, topic: &str) -> bool {
+        self.topics.contains_key(topic)
+    }
+
+    /// Empty column of the topic's kind, to check it without decoding.
+    pub fn shape(&self, topic: &str) -> Option<&TopicData> {
+        self.topics.get(topic).map(|t| &t.shape)
+    }
+
+    /// `(name, type, metadata)` of every entry.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+        self.entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.type_str.as_str(), e.metadata.as_str()))
+    }
+
+    /// Record times of the topic's entry. Unlike a loaded topic these include
+    /// records that repeat the previous value.
+    pub fn timestamps(&self, topic: &str) -> Option<&[u64]> {
+        let entry = &self.entries[self.topics.get(topic)?.entry];
+        Some(&entry.timestamps)
+    }
+
+    /// Value of `topic` in effect at `time`; before the first sample, the
+    /// first sample (like the loaded store).
+    pub fn sample_at(&self, topic: &str, time: u64) -> Option<Sample> {
+        let entry = self.topics.get(topic)?.entry;
+        let timestamps = &self.entries[entry].timestamps;
+        let idx = timestamps.partition_point(|&t| t <= time).saturating_sub(1);
+
+        // A record that failed to decode leaves a block without the topic;
+        // fall back to the blocks before it
+        for block in (0..=idx / BLOCK).rev() {
+            let decoded = self.block(entry, block)?;
+            if let Some(data) = decoded.get(topic) {
+                let idx = data.timestamps().partition_point(|&t| t <= time);
+                if idx > 0 {
+                    return data.sample(idx - 1);
+                }
+            }
+        }
+        (0..timestamps.len().div_ceil(BLOCK)).find_map(|block| {
+            let decoded = self.block(entry, block)?;
+            decoded.get(topic)?.sample(0)
+        })
+    }
+
+    /// Every sample of `topic`, decoded into a column.
+    pub fn topic_data(&self, topic: &str) -> Option<TopicData> {
+        self.topic_range(topic, 0, u64::MAX)
+    }
+
+    /// Samples of `topic` from the one in effect at `start` through `end`,
+    /// rounded out to whole blocks.
+    pub fn topic_range(&self, topic: &str, start: u64, end: u64) -> Option<TopicData> {
+        let entry = self.topics.get(topic)?.entry;
+        let timestamps = &self.entries[entry].timestamps;
+        let first = timestamps
+            .partition_point(|&t| t <= start)
+            .saturating_sub(1);
+        let last = timestamps.partition_point(|&t| t <= end).max(first + 1);
+
+        let mut store = LogStore::new();
+        for block in first / BLOCK..last.div_ceil(BLOCK) {
+            let decoded = self.block(entry, block)?;
+            if let Some(data) = decoded.get(topic) {
+                for (idx, &timestamp) in data.timestamps().iter().enumerate() {
+                    if let Some(sample) = data.sample(idx) {
+                        store.apply(StoreOp::sample(topic.to_string(), timestamp, sample));
+                    }
+                }
+            }
+        }
+        store.merge_staged();
+        Some(
+            store
+                .data
+                .remove(topic)
+                .unwrap_or_else(|| self.topics[topic].shape.clone()),
+        )
+    }
+
+    /// Decoded topics of block `block` of `entry`, from the cache or the file.
+    fn block(&self, entry: usize, block: usize) -> Option<Block> {
+        {
+            let mut cache = self.cache.lock();
+            cache.tick += 1;
+            let tick = cache.tick;
+            if let Some((used, decoded)) = cache.blocks.get_mut(&(entry, block)) {
+                *used = tick;
+                return Some(decoded.clone());
+            }
+        }
+
+        let decoded = match self.decode_block(entry, block) {
+            Ok(decoded) => Arc::new(decoded),
+            Err(e) => {
+                godot_print!("wpilog: Couldn't read {}: {}", self.entries[entry].name, e);
+                return None;
+            }
+        };
+
+        let mut cache = self.cache.lock();
+        if cache.blocks.len() >= CACHE_BLOCKS
+            && let Some(&oldest) = cache
+                .blocks
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key)
+        {
+            cache.blocks.remove(&oldest);
+        }
+        let tick = cache.tick;
+        cache.blocks.insert((entry, block), (tick, decoded.clone()));
+        Some(decoded)
+    }
+
+    fn decode_block(&self, entry: usize, block: usize) -> io::Result<HashMap<String, TopicData>> {
+        let entry = &self.entries[entry];
+        let start = block * BLOCK;
+        let end = (start + BLOCK).min(entry.offsets.len());
+
+        let mut ops = Vec::new();
+        {
+            let mut file = self.file.lock();
+            for &offset in &entry.offsets[start..end] {
+                let FileCursor {
+                    reader,
+                    position,
+                    len: file_len,
+                } = &mut *file;
+                reader.seek_relative(offset as i64 - *position as i64)?;
+                *position = offset;
+                let remaining = file_len.saturating_sub(offset);
+                let Some((_, timestamp, payload, len)) = read_record(reader, remaining)? else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "log file was truncated",
+                    ));
+                };
+                *position += len;
+
+                flatten_struct_entry(
+                    &entry.name,
+                    &entry.type_str,
+                    &payload,
+                    timestamp,
+                    &self.schemas,
+                    &mut ops,
+                );
+                if let Some(sample) = decode_payload(&entry.type_str, &payload) {
+                    ops.push(StoreOp::sample(entry.name.clone(), timestamp, sample));
+                }
+            }
+        }
+
+        let mut store = LogStore::new();
+        store.apply_all(ops);
+        Ok(store.data)
+    }
+}
+
+/// Column matching a sample's kind.
+fn empty_column(sample: &Sample) -> TopicData {
+    match sample {
+        Sample::Double(_) => TopicData::new_double(),
+        Sample::Boolean(_) => TopicData::new_boolean(),
+        Sample::String(_) => TopicData::new_string(),
+        Sample::DoubleArray(_) => TopicData::new_double_array(),
+        Sample::BooleanArray(_) => TopicData::new_boolean_array(),
+        Sample::StringArray(_) => TopicData::new_string_array(),
+        Sample::Raw(_) => TopicData::new_raw(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wpilog::{WpilogWriter, encode_payload};
+
+    /// A log of `/Speed` with 10 * BLOCK records 10ms apart, every value
+    /// logged twice.
+    fn open_log(name: &str) -> LazyLog {
+        let path = std::env::temp_dir().join(format!("{}_{}.wpilog", name, std::process::id()));
+        let mut writer = WpilogWriter::new(File::create(&path).unwrap(), "").unwrap();
+        let entry = writer.start_entry("/Speed", "double", "", 0).unwrap();
+        for i in 0..10 * BLOCK as u64 {
+            let payload = encode_payload("double", &Sample::Double((i / 2) as f64));
+            writer.append(entry, 1_000 + i * 10_000, &payload).unwrap();
+        }
+        writer.finish().unwrap();
+        let log = LazyLog::open(File::open(&path).unwrap(), |_| true)
+            .unwrap()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        log
+    }
+
+    #[test]
+    fn topic_range_decodes_the_covering_blocks() {
+        let log = open_log("lazy_range");
+        let all = log.topic_data("/Speed").unwrap();
+        assert_eq!(all.timestamps().len(), 5 * BLOCK);
+
+        let (start, end) = (30_000_000, 35_000_000);
+        let range = log.topic_range("/Speed", start, end).unwrap();
+        let ts = range.timestamps();
+        assert!(ts.len() <= BLOCK, "decoded {} samples", ts.len());
+        // The sample in effect at `start`, then everything through `end`
+        let first = ts.partition_point(|&t| t <= start) - 1;
+        let last = ts.partition_point(|&t| t <= end);
+        let expected = all.timestamps();
+        let from = expected.partition_point(|&t| t <= start) - 1;
+        let to = expected.partition_point(|&t| t <= end);
+        assert_eq!(&ts[first..last], &expected[from..to]);
+        assert_eq!(range.sample(first), all.sample(from));
+    }
+
+    #[test]
+    fn topic_range_outside_the_log() {
+        let log = open_log("lazy_range_outside");
+        // Before the first sample: the first block, which holds it
+        let before = log.topic_range("/Speed", 0, 500).unwrap();
+        assert_eq!(before.timestamps().first(), Some(&1_000));
+        // After the last: the last block, ending with the held value
+        let after = log.topic_range("/Speed", u64::MAX - 1, u64::MAX).unwrap();
+        assert_eq!(
+            after.sample(after.timestamps().len() - 1),
+            Some(Sample::Double(5119.0))
+        );
+        assert!(log.topic_range("/Missing", 0, u64::MAX).is_none());
+    }
+}
@@ -1,9 +1,37 @@
 use godot::prelude::*;
 
-mod log_store;
+pub mod align;
+pub mod batch;
+pub mod column;
+pub mod computed;
+pub mod derived;
+pub mod dsp;
+pub mod export;
+pub mod expr;
+pub mod heatmap;
+pub mod lazy_log;
+mod log_loader;
+pub mod log_store;
+pub mod match_info;
+pub mod mcap;
+pub mod mcap_message;
+pub mod modes;
 mod network;
 mod nt4_node;
+pub mod odometry;
+pub mod pose;
+pub mod pose_error;
+pub mod protobuf;
+pub mod revlog;
+pub mod rlog;
+mod rlog_client;
+pub mod ros_msg;
 pub mod schema;
+pub mod search;
+pub mod session_cache;
+pub mod spectrum;
+pub mod stats;
+pub mod wpilog;
 
 struct Nt4Logging;
 
@@ -0,0 +1,560 @@
+use crate::align::{enabled_topic, estimate_offset, match_enabled_edges};
+use crate::batch::StoreOp;
+use crate::lazy_log::LazyLog;
+use crate::log_store::LogStore;
+use crate::mcap::McapReader;
+use crate::mcap_message::McapDecoder;
+use crate::revlog::RevlogDecoder;
+use crate::rlog::RlogDecoder;
+use crate::schema::{Schema, decode_struct_entry};
+use crate::session_cache;
+use crate::wpilog::{EntryInfo, Record, WpilogReader, decode_payload};
+use godot::prelude::*;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// Ops decoded before they are written to the store under one lock, so the UI
+/// sees the log fill in while readers are never blocked for long.
+const LOAD_CHUNK: usize = 16_384;
+
+/// .wpilog files at least this large are indexed and read from disk on demand
+/// instead of loaded into memory.
+const INDEX_THRESHOLD: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum LoadEvent {
+    /// Fraction of the file read so far, 0.0 to 1.0.
+    Progress(f32),
+    /// Offset (microseconds) an import was shifted by after auto-alignment.
+    Aligned(i64),
+    Loaded,
+    Failed(String),
+}
+
+/// A log file being read into the store on a background thread.
+pub struct LogLoader {
+    rx: Receiver<LoadEvent>,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Where an imported log lands in the current session.
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Prepended to every imported topic, e.g. `/Phoenix`.
+    pub prefix: String,
+    /// Microseconds added to every imported timestamp. Samples shifted to
+    /// before the start of the session are dropped.
+    pub offset: i64,
+    /// When set, the offset is estimated instead of taken from `offset`.
+    pub align: Option<Alignment>,
+}
+
+/// How [`LogLoader::spawn_import`] estimates the offset of an imported log.
+#[derive(Debug, Clone)]
+pub enum Alignment {
+    /// Cross-correlate two topics recording the same signal: one in the
+    /// session, one in the imported log (before prefixing).
+    Correlate { reference: String, imported: String },
+    /// Match the rising edges of the robot enable state in both logs.
+    EnabledEdges,
+}
+
+impl LogLoader {
+    /// Opens `path` and starts streaming it into `store`, picking the format
+    /// from the extension. Very large .wpilogs are indexed instead (see
+    /// [`LazyLog`]). With a `cache_dir`, a log opened before is read from its
+    /// session cache, and a newly parsed one is cached there. The thread stops
+    /// on its own if the store is cleared (generation bump) or the load is
+    /// cancelled.
+    pub fn spawn(
+        path: &str,
+        store: Arc<RwLock<LogStore>>,
+        generation: u32,
+        cache_dir: Option<PathBuf>,
+    ) -> std::io::Result<Self> {
+        Self::start(path, move |mut file, format, progress| {
+            let mut committer = progress.with_store(store, generation);
+            if matches!(format, LogFormat::Wpilog) && progress.total >= INDEX_THRESHOLD {
+                return load_wpilog_indexed(file, &mut committer);
+            }
+            let Some(cache_dir) = cache_dir else {
+                return load_file(file, format, &mut committer);
+            };
+
+            let key = session_cache::content_hash(&mut file).map_err(|e| e.to_string())?;
+            let cached = session_cache::cache_path(&cache_dir, key);
+            if cached.exists() {
+                match load_cache(&cached, key, &mut committer) {
+                    Ok(loaded) => return Ok(loaded),
+                    Err(e) => godot_print!("Session cache unusable, parsing the log: {}", e),
+                }
+            }
+
+            if !load_file(file, format, &mut committer)? {
+                return Ok(false);
+            }
+            if let Err(e) = session_cache::write(&committer.store, generation, &cache_dir, key) {
+                godot_print!("Couldn't write session cache: {}", e);
+            }
+            Ok(true)
+        })
+    }
+
+    /// Like [`Self::spawn`], but adds the log to what is already in `store`
+    /// (a motor controller log next to the robot log), renamed and shifted
+    /// per `options`.
+    pub fn spawn_import(
+        path: &str,
+        store: Arc<RwLock<LogStore>>,
+        generation: u32,
+        options: ImportOptions,
+    ) -> std::io::Result<Self> {
+        Self::start(path, move |file, format, committer| {
+            // Read into a private store first; the offset may depend on its data
+            let staging = Arc::new(RwLock::new(LogStore::new()));
+            if !load_file(file, format, &mut committer.with_store(staging.clone(), 0))? {
+                return Ok(false);
+            }
+            let staging = staging.read();
+
+            let offset = match &options.align {
+                Some(Alignment::Correlate {
+                    reference,
+                    imported,
+                }) => {
+                    let offset = {
+                        let store = store.read();
+                        store
+                            .topic_data(reference)
+                            .zip(staging.data.get(imported))
+                            .and_then(|(r, i)| estimate_offset(&r, i))
+                    };
+                    offset
+                        .ok_or_else(|| format!("Couldn't align {} with {}", imported, reference))?
+                }
+                Some(Alignment::EnabledEdges) => {
+                    let offset = {
+                        let store = store.read();
+                        enabled_topic(&store)
+                            .zip(enabled_topic(&staging))
+                            .and_then(|(r, i)| match_enabled_edges(&r, &i))
+                    };
+                    offset.ok_or("Couldn't find robot enable edges to align the logs on")?
+                }
+                None => options.offset,
+            };
+            if options.align.is_some() {
+                let _ = committer.tx.send(LoadEvent::Aligned(offset));
+            }
+
+            merge(
+                &staging,
+                &options.prefix,
+                offset,
+                &mut committer.with_store(store, generation),
+            )
+        })
+    }
+
+    fn start(
+        path: &str,
+        load: impl FnOnce(File, LogFormat, &Progress) -> Result<bool, String> + Send + 'static,
+    ) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        let total = file.metadata()?.len().max(1);
+        let format = LogFormat::from_path(path);
+        let (tx, rx) = channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let progress = Progress {
+            tx,
+            cancel: cancel.clone(),
+            total,
+        };
+
+        std::thread::spawn(move || {
+            let event = match load(file, format, &progress) {
+                Ok(true) => LoadEvent::Loaded,
+                Ok(false) => return, // Superseded by a newer session
+                Err(reason) => LoadEvent::Failed(reason),
+            };
+            let _ = progress.tx.send(event);
+        });
+
+        Ok(Self { rx, cancel })
+    }
+
+    /// Stops the load after the current chunk. Samples already written stay in
+    /// the store.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Events sent since the last poll.
+    pub fn poll(&self) -> Vec<LoadEvent> {
+        self.rx.try_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LogFormat {
+    Wpilog,
+    Rlog,
+    Revlog,
+    Mcap,
+}
+
+impl LogFormat {
+    /// Phoenix 6 hoot files converted by Tuner X come out as .wpilog too.
+    fn from_path(path: &str) -> Self {
+        let ext = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "rlog" => LogFormat::Rlog,
+            "revlog" => LogFormat::Revlog,
+            "mcap" => LogFormat::Mcap,
+            _ => LogFormat::Wpilog,
+        }
+    }
+}
+
+fn load_file(file: File, format: LogFormat, committer: &mut Committer) -> Result<bool, String> {
+    match format {
+        LogFormat::Wpilog => load_wpilog(file, committer),
+        LogFormat::Rlog => load_rlog(file, committer),
+        LogFormat::Revlog => load_revlog(file, committer),
+        LogFormat::Mcap => load_mcap(file, committer),
+    }
+}
+
+/// The loader thread's side of the event channel.
+struct Progress {
+    tx: Sender<LoadEvent>,
+    cancel: Arc<AtomicBool>,
+    total: u64,
+}
+
+impl Progress {
+    fn with_store(&self, store: Arc<RwLock<LogStore>>, generation: u32) -> Committer<'_> {
+        Committer {
+            store,
+            generation,
+            progress: self,
+            last_percent: 0,
+        }
+    }
+}
+
+/// Writes decoded ops into a store a chunk at a time and reports progress.
+struct Committer<'a> {
+    store: Arc<RwLock<LogStore>>,
+    generation: u32,
+    progress: &'a Progress,
+    last_percent: u64,
+}
+
+impl Committer<'_> {
+    /// Applies `ops` once a chunk has built up (or always, when `flush`).
+    /// `position` is how far into the file decoding got. Returns `Ok(false)`
+    /// if the store moved on to another generation.
+    fn commit(
+        &mut self,
+        ops: &mut Vec<StoreOp>,
+        position: u64,
+        flush: bool,
+    ) -> Result<bool, String> {
+        if ops.len() < LOAD_CHUNK && !flush {
+            return Ok(true);
+        }
+        {
+            let mut store = self.store.write();
+            if !store.check_generation(self.generation) {
+                return Ok(false);
+            }
+            // Late samples (from a log merged into the session) wait to be
+            // sorted in together
+            for op in ops.drain(..) {
+                store.apply(op);
+            }
+            if flush || self.progress.cancel.load(Ordering::Relaxed) {
+                store.merge_staged();
+            }
+        }
+        self.report(position)?;
+        Ok(true)
+    }
+
+    /// Sends progress for `position` if it moved a percent, and fails if the
+    /// load was cancelled.
+    fn report(&mut self, position: u64) -> Result<(), String> {
+        let percent = position * 100 / self.progress.total;
+        if percent > self.last_percent {
+            self.last_percent = percent;
+            let _ = self
+                .progress
+                .tx
+                .send(LoadEvent::Progress(percent as f32 / 100.0));
+        }
+        if self.progress.cancel.load(Ordering::Relaxed) {
+            return Err("Load cancelled".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// `prefix` + `topic`, with exactly one `/` between them.
+fn prefixed(prefix: &str, topic: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        return topic.to_string();
+    }
+    format!("{}/{}", prefix, topic.trim_start_matches('/'))
+}
+
+/// Copies everything in `staging` into the committer's store under `prefix`,
+/// shifted by `offset` microseconds. Samples that would land at or before
+/// time 0 are dropped rather than piled up there.
+fn merge(
+    staging: &LogStore,
+    prefix: &str,
+    offset: i64,
+    committer: &mut Committer,
+) -> Result<bool, String> {
+    let mut ops = Vec::with_capacity(LOAD_CHUNK);
+    let mut dropped = 0;
+    for topic in &staging.topic_order {
+        let Some(data) = staging.data.get(topic) else {
+            continue;
+        };
+        let name = prefixed(prefix, topic);
+        if let Some(type_str) = staging.topic_types.get(topic) {
+            ops.push(StoreOp::set_type(name.clone(), type_str.clone()));
+        }
+        if let Some(metadata) = staging.topic_metadata.get(topic) {
+            ops.push(StoreOp::set_metadata(name.clone(), metadata.clone()));
+        }
+        for (idx, &timestamp) in data.timestamps().iter().enumerate() {
+            let timestamp = timestamp as i64 + offset;
+            if timestamp < 1 {
+                dropped += 1;
+                continue;
+            }
+            if let Some(sample) = data.sample(idx) {
+                ops.push(StoreOp::sample(name.clone(), timestamp as u64, sample));
+            }
+        }
+        if !committer.commit(&mut ops, committer.progress.total, false)? {
+            return Ok(false);
+        }
+    }
+    if dropped > 0 {
+        godot_print!(
+            "Import: Dropped {} samples shifted before the start of the log",
+            dropped
+        );
+    }
+    committer.commit(&mut ops, committer.progress.total, true)
+}
+
+fn load_wpilog(file: File, committer: &mut Committer) -> Result<bool, String> {
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut reader = WpilogReader::new(BufReader::new(file), len).map_err(|e| e.to_string())?;
+    let mut ops = Vec::with_capacity(LOAD_CHUNK);
+    let mut schemas: HashMap<String, Schema> = HashMap::new();
+
+    loop {
+        let record = match reader.next_record() {
+            Ok(record) => record,
+            Err(e) => {
+                // Keep what was read before the damage
+                committer.commit(&mut ops, reader.position(), true)?;
+                return Err(e.to_string());
+            }
+        };
+        let done = record.is_none();
+
+        match record {
+            Some(Record::Start { info, .. }) => {
+                if !info.metadata.is_empty() {
+                    ops.push(StoreOp::set_metadata(info.name.clone(), info.metadata));
+                }
+                ops.push(StoreOp::set_type(info.name, info.type_str));
+            }
+            Some(Record::SetMetadata { entry, metadata }) => {
+                if let Some(info) = reader.entry(entry) {
+                    ops.push(StoreOp::set_metadata(info.name.clone(), metadata));
+                }
+            }
+            Some(Record::Data {
+                entry,
+                timestamp,
+                payload,
+            }) => {
+                if let Some(info) = reader.entry(entry) {
+                    decode_record(info, timestamp, &payload, &mut schemas, &mut ops);
+                }
+            }
+            _ => {}
+        }
+
+        if !committer.commit(&mut ops, reader.position(), done)? {
+            return Ok(false);
+        }
+        if done {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fills the store from a session cache instead of the log itself.
+fn load_cache(path: &Path, key: u64, committer: &mut Committer) -> Result<bool, String> {
+    let mut cached = session_cache::read(path, key).map_err(|e| e.to_string())?;
+
+    let mut store = committer.store.write();
+    if !store.check_generation(committer.generation) {
+        return Ok(false);
+    }
+    store.topic_types.extend(cached.topic_types.drain());
+    store.topic_metadata.extend(cached.topic_metadata.drain());
+    for topic in std::mem::take(&mut cached.topic_order) {
+        if let Some(data) = cached.data.remove(&topic) {
+            store.insert_topic(topic, data);
+        }
+    }
+    Ok(true)
+}
+
+/// Indexes a .wpilog too large to load and hands the index to the store, which
+/// decodes from the file as the UI asks for values.
+fn load_wpilog_indexed(file: File, committer: &mut Committer) -> Result<bool, String> {
+    let mut cancelled = None;
+    let log = LazyLog::open(file, |position| match committer.report(position) {
+        Ok(()) => true,
+        Err(reason) => {
+            cancelled = Some(reason);
+            false
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    let Some(log) = log else {
+        return Err(cancelled.unwrap_or_default());
+    };
+
+    let mut store = committer.store.write();
+    if !store.check_generation(committer.generation) {
+        return Ok(false);
+    }
+    store.attach_lazy(log);
+    Ok(true)
+}
+
+/// Streams an MCAP file (Foxglove, ROS bags, Phoenix exports), decoding each
+/// channel's messages by its encoding.
+fn load_mcap(file: File, committer: &mut Committer) -> Result<bool, String> {
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut reader = McapReader::new(BufReader::new(file), len).map_err(|e| e.to_string())?;
+    let mut decoder = McapDecoder::new();
+    let mut ops = Vec::with_capacity(LOAD_CHUNK);
+
+    loop {
+        let record = match reader.next_record() {
+            Ok(record) => record,
+            Err(e) => {
+                // Keep what was read before the damage
+                committer.commit(&mut ops, reader.position(), true)?;
+                return Err(e.to_string());
+            }
+        };
+        let done = record.is_none();
+        if let Some(record) = record {
+            decoder.decode(record, &mut ops);
+        }
+        if !committer.commit(&mut ops, reader.position(), done)? {
+            return Ok(false);
+        }
+        if done {
+            break;
+        }
+    }
+    if decoder.failed > 0 {
+        godot_print!(
+            "mcap: Skipped {} messages that failed to decode",
+            decoder.failed
+        );
+    }
+    Ok(true)
+}
+
+/// RLOG records can't be walked without decoding them, and the files are
+/// small next to .wpilogs, so the whole file is read up front.
+fn load_rlog(mut file: File, committer: &mut Committer) -> Result<bool, String> {
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).map_err(|e| e.to_string())?;
+
+    let mut decoder = RlogDecoder::new();
+    let mut ops = Vec::with_capacity(LOAD_CHUNK);
+    let mut pos = 0;
+    while pos < data.len() {
+        if let Err(e) = decoder.decode_record(&data, &mut pos, &mut ops) {
+            // Keep what was read before the damage
+            committer.commit(&mut ops, pos as u64, true)?;
+            return Err(e.to_string());
+        }
+        if !committer.commit(&mut ops, pos as u64, pos >= data.len())? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// REV logs are read whole like RLOGs; their records are small and only
+/// found by walking the file.
+fn load_revlog(mut file: File, committer: &mut Committer) -> Result<bool, String> {
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).map_err(|e| e.to_string())?;
+
+    let mut decoder = RevlogDecoder::new();
+    let mut ops = Vec::with_capacity(LOAD_CHUNK);
+    let mut pos = 0;
+    while pos < data.len() {
+        if let Err(e) = decoder.decode_record(&data, &mut pos, &mut ops) {
+            // Keep what was read before the damage
+            committer.commit(&mut ops, pos as u64, true)?;
+            return Err(e.to_string());
+        }
+        if !committer.commit(&mut ops, pos as u64, pos >= data.len())? {
+            return Ok(false);
+        }
+    }
+    if decoder.skipped > 0 {
+        godot_print!(
+            "revlog: Skipped {} frames from other devices or of unknown kinds",
+            decoder.skipped
+        );
+    }
+    Ok(true)
+}
+
+/// Turns one data record into store ops, registering struct schemas as they
+/// appear and flattening struct entries the same way live NT4 data is.
+fn decode_record(
+    info: &EntryInfo,
+    timestamp: u64,
+    payload: &[u8],
+    schemas: &mut HashMap<String, Schema>,
+    ops: &mut Vec<StoreOp>,
+) {
+    decode_struct_entry(&info.name, &info.type_str, payload, timestamp, schemas, ops);
+
+    if let Some(value) = decode_payload(&info.type_str, payload) {
+        ops.push(StoreOp::sample(info.name.clone(), timestamp, value));
+    }
+}
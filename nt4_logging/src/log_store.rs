@@ -1,15 +1,21 @@
+use crate::batch::{Sample, StoreOp};
+use crate::column::{ListColumn, SampleColumn, StrList, StringArrayColumn, StringColumn};
+use crate::computed::ComputedTopic;
+use crate::lazy_log::LazyLog;
 use godot::prelude::*;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ops::Range;
 
 #[derive(Debug, Clone)]
 pub enum TopicData {
     Double(Vec<u64>, Vec<f64>),
     Boolean(Vec<u64>, Vec<bool>),
-    String(Vec<u64>, Vec<String>),
-    DoubleArray(Vec<u64>, Vec<Vec<f64>>),
-    BooleanArray(Vec<u64>, Vec<Vec<bool>>),
-    StringArray(Vec<u64>, Vec<Vec<String>>),
-    Raw(Vec<u64>, Vec<Vec<u8>>),
+    String(Vec<u64>, StringColumn),
+    DoubleArray(Vec<u64>, ListColumn<f64>),
+    BooleanArray(Vec<u64>, ListColumn<bool>),
+    StringArray(Vec<u64>, StringArrayColumn),
+    Raw(Vec<u64>, ListColumn<u8>),
 }
 
 impl TopicData {
@@ -20,38 +26,186 @@ impl TopicData {
         TopicData::Boolean(Vec::new(), Vec::new())
     }
     pub fn new_string() -> Self {
-        TopicData::String(Vec::new(), Vec::new())
+        TopicData::String(Vec::new(), StringColumn::new())
     }
     pub fn new_double_array() -> Self {
-        TopicData::DoubleArray(Vec::new(), Vec::new())
+        TopicData::DoubleArray(Vec::new(), ListColumn::new())
     }
     pub fn new_boolean_array() -> Self {
-        TopicData::BooleanArray(Vec::new(), Vec::new())
+        TopicData::BooleanArray(Vec::new(), ListColumn::new())
     }
     pub fn new_string_array() -> Self {
-        TopicData::StringArray(Vec::new(), Vec::new())
+        TopicData::StringArray(Vec::new(), StringArrayColumn::new())
     }
     pub fn new_raw() -> Self {
-        TopicData::Raw(Vec::new(), Vec::new())
+        TopicData::Raw(Vec::new(), ListColumn::new())
+    }
+
+    pub fn timestamps(&self) -> &[u64] {
+        match self {
+            TopicData::Double(ts, _) => ts,
+            TopicData::Boolean(ts, _) => ts,
+            TopicData::String(ts, _) => ts,
+            TopicData::DoubleArray(ts, _) => ts,
+            TopicData::BooleanArray(ts, _) => ts,
+            TopicData::StringArray(ts, _) => ts,
+            TopicData::Raw(ts, _) => ts,
+        }
     }
 
     pub fn last_timestamp(&self) -> u64 {
+        self.timestamps().last().copied().unwrap_or(0)
+    }
+
+    /// Owned copy of sample `idx`, for writing it elsewhere.
+    pub fn sample(&self, idx: usize) -> Option<Sample> {
         match self {
-            TopicData::Double(ts, _) => ts.last().copied().unwrap_or(0),
-            TopicData::Boolean(ts, _) => ts.last().copied().unwrap_or(0),
-            TopicData::String(ts, _) => ts.last().copied().unwrap_or(0),
-            TopicData::DoubleArray(ts, _) => ts.last().copied().unwrap_or(0),
-            TopicData::BooleanArray(ts, _) => ts.last().copied().unwrap_or(0),
-            TopicData::StringArray(ts, _) => ts.last().copied().unwrap_or(0),
-            TopicData::Raw(ts, _) => ts.last().copied().unwrap_or(0),
+            TopicData::Double(_, vals) => vals.get(idx).copied().map(Sample::Double),
+            TopicData::Boolean(_, vals) => vals.get(idx).copied().map(Sample::Boolean),
+            TopicData::String(_, vals) => vals.get(idx).map(|v| Sample::String(v.to_string())),
+            TopicData::DoubleArray(_, vals) => {
+                vals.get(idx).map(|v| Sample::DoubleArray(v.to_vec()))
+            }
+            TopicData::BooleanArray(_, vals) => {
+                vals.get(idx).map(|v| Sample::BooleanArray(v.to_vec()))
+            }
+            TopicData::StringArray(_, vals) => {
+                vals.get(idx).map(|v| Sample::StringArray(v.to_vec()))
+            }
+            TopicData::Raw(_, vals) => vals.get(idx).map(|v| Sample::Raw(v.to_vec())),
+        }
+    }
+
+    /// Copy of the samples in `range`.
+    pub fn slice(&self, range: Range<usize>) -> TopicData {
+        fn copy<C: SampleColumn + Default>(
+            ts: &[u64],
+            vals: &C,
+            range: Range<usize>,
+        ) -> (Vec<u64>, C) {
+            let mut out = C::default();
+            for idx in range.clone() {
+                out.push_from(vals, idx);
+            }
+            (ts[range].to_vec(), out)
+        }
+
+        let end = range.end.min(self.timestamps().len());
+        let range = range.start.min(end)..end;
+        match self {
+            TopicData::Double(ts, vals) => {
+                TopicData::Double(ts[range.clone()].to_vec(), vals[range].to_vec())
+            }
+            TopicData::Boolean(ts, vals) => {
+                TopicData::Boolean(ts[range.clone()].to_vec(), vals[range].to_vec())
+            }
+            TopicData::String(ts, vals) => {
+                let (ts, vals) = copy(ts, vals, range);
+                TopicData::String(ts, vals)
+            }
+            TopicData::DoubleArray(ts, vals) => {
+                let (ts, vals) = copy(ts, vals, range);
+                TopicData::DoubleArray(ts, vals)
+            }
+            TopicData::BooleanArray(ts, vals) => {
+                let (ts, vals) = copy(ts, vals, range);
+                TopicData::BooleanArray(ts, vals)
+            }
+            TopicData::StringArray(ts, vals) => {
+                let (ts, vals) = copy(ts, vals, range);
+                TopicData::StringArray(ts, vals)
+            }
+            TopicData::Raw(ts, vals) => {
+                let (ts, vals) = copy(ts, vals, range);
+                TopicData::Raw(ts, vals)
+            }
         }
     }
 }
 
+/// Picks the timestamps and values out of a topic's data, if it is of the
+/// kind `C` stores.
+type ColumnOf<C> = fn(&mut TopicData) -> Option<(&mut Vec<u64>, &mut C)>;
+
+/// Appends a sample unless it repeats the last value.
+fn push_sample<C: SampleColumn>(
+    timestamps: &mut Vec<u64>,
+    values: &mut C,
+    timestamp: u64,
+    value: &C::Value,
+) -> bool {
+    if values.len() > 0 && values.same(values.len() - 1, value) {
+        return false;
+    }
+    timestamps.push(timestamp);
+    values.push_value(value);
+    true
+}
+
+/// Merges late samples (`staged`, in arrival order) into a sorted column in
+/// one pass. Each goes after any existing samples with the same timestamp;
+/// values that repeat their predecessor are dropped, so a successor made
+/// redundant by a late sample moves back to its time.
+fn merge_sorted<C: SampleColumn>(
+    timestamps: &mut Vec<u64>,
+    values: &mut C,
+    staged_ts: &[u64],
+    staged: &C,
+) {
+    let mut order: Vec<usize> = (0..staged_ts.len()).collect();
+    order.sort_by_key(|&i| staged_ts[i]);
+    let Some(&first) = order.first() else {
+        return;
+    };
+
+    // Samples before the earliest late one stay where they are
+    let keep = timestamps.partition_point(|&t| t <= staged_ts[first]);
+    let old_ts = timestamps.split_off(keep);
+    let old = values.split_tail(keep);
+    let (mut i, mut j) = (0, 0);
+    while i < old_ts.len() || j < order.len() {
+        let from_old = j == order.len() || (i < old_ts.len() && old_ts[i] <= staged_ts[order[j]]);
+        let (source, idx, time) = if from_old {
+            i += 1;
+            (&old, i - 1, old_ts[i - 1])
+        } else {
+            j += 1;
+            (staged, order[j - 1], staged_ts[order[j - 1]])
+        };
+        if values.len() > 0 && values.same_as(values.len() - 1, source, idx) {
+            continue;
+        }
+        timestamps.push(time);
+        values.push_from(source, idx);
+    }
+}
+
 pub struct LogStore {
     pub data: HashMap<String, TopicData>,
     pub topic_types: HashMap<String, String>,
+    /// Entry metadata from logs (usually JSON, e.g. `{"units":"rps"}`).
+    pub topic_metadata: HashMap<String, String>,
     pub generation: u32,
+    /// Bumped every time a sample is stored; never reset, so readers can
+    /// compare against the last revision they saw.
+    pub revision: u64,
+    /// Revision at which each topic last received a sample.
+    pub topic_revisions: HashMap<String, u64>,
+    /// Revision at which each topic last changed anywhere but at its end (a
+    /// late sample, replaced or truncated data), for readers that only fold
+    /// in new samples.
+    pub topic_rewrites: HashMap<String, u64>,
+    /// Topics in the order they first appeared (cleared with the store); a
+    /// removed computed topic drops out.
+    pub topic_order: Vec<String>,
+    /// A log too large to load, read from disk as it is queried. Topics in
+    /// `data` take precedence over its topics.
+    pub lazy: Option<LazyLog>,
+    /// Topics computed from expressions; kept when the store is cleared.
+    pub computed: Vec<ComputedTopic>,
+    /// Samples older than their topic's last one, in arrival order, until
+    /// [`Self::merge_staged`] sorts them in.
+    staged: HashMap<String, TopicData>,
 }
 
 impl LogStore {
@@ -59,7 +213,15 @@ impl LogStore {
         Self {
             data: HashMap::new(),
             topic_types: HashMap::new(),
+            topic_metadata: HashMap::new(),
             generation: 0,
+            revision: 0,
+            topic_revisions: HashMap::new(),
+            topic_rewrites: HashMap::new(),
+            topic_order: Vec::new(),
+            lazy: None,
+            computed: Vec::new(),
+            staged: HashMap::new(),
         }
     }
 
@@ -67,121 +229,355 @@ impl LogStore {
         self.topic_types.insert(topic, type_str);
     }
 
+    pub fn set_metadata(&mut self, topic: String, metadata: String) {
+        self.topic_metadata.insert(topic, metadata);
+    }
+
+    /// Units named in the topic's metadata (`"units"` or `"unit"` key).
+    pub fn get_topic_units(&self, topic: &str) -> Option<String> {
+        let metadata = self.topic_metadata.get(topic)?;
+        ["\"units\"", "\"unit\""].iter().find_map(|key| {
+            let rest = metadata[metadata.find(key)? + key.len()..].trim_start();
+            let rest = rest.strip_prefix(':')?.trim_start().strip_prefix('"')?;
+            Some(rest[..rest.find('"')?].to_string())
+        })
+    }
+
     pub fn clear(&mut self) {
         self.data.clear();
         self.topic_types.clear();
+        self.topic_metadata.clear();
+        self.topic_revisions.clear();
+        self.topic_rewrites.clear();
+        self.topic_order.clear();
+        self.staged.clear();
+        self.lazy = None;
+        for topic in &mut self.computed {
+            topic.reset();
+        }
         self.generation += 1;
     }
 
+    /// Adds a whole topic at once, replacing any data under that name.
+    pub fn insert_topic(&mut self, topic: String, data: TopicData) {
+        self.staged.remove(&topic);
+        if self.data.insert(topic.clone(), data).is_none() {
+            self.topic_order.push(topic.clone());
+        }
+        self.revision += 1;
+        self.topic_revisions.insert(topic.clone(), self.revision);
+        self.topic_rewrites.insert(topic, self.revision);
+    }
+
+    /// Makes the topics of an indexed log visible through the getters.
+    pub fn attach_lazy(&mut self, log: LazyLog) {
+        for (name, type_str, metadata) in log.entries() {
+            self.topic_types
+                .insert(name.to_string(), type_str.to_string());
+            if !metadata.is_empty() {
+                self.topic_metadata
+                    .insert(name.to_string(), metadata.to_string());
+            }
+        }
+        for topic in log.topics() {
+            if !self.data.contains_key(topic) {
+                self.topic_order.push(topic.clone());
+            }
+            self.revision += 1;
+            self.topic_revisions.insert(topic.clone(), self.revision);
+        }
+        self.lazy = Some(log);
+    }
+
+    /// The indexed log, if it holds `topic` and `data` doesn't.
+    fn lazy_for(&self, topic: &str) -> Option<&LazyLog> {
+        self.lazy
+            .as_ref()
+            .filter(|log| log.has_topic(topic) && !self.data.contains_key(topic))
+    }
+
+    /// Topic names, loaded and indexed.
+    pub fn topic_names(&self) -> impl Iterator<Item = &str> {
+        let lazy = self.lazy.iter().flat_map(|log| log.topics());
+        self.data
+            .keys()
+            .chain(lazy.filter(|t| !self.data.contains_key(*t)))
+            .map(String::as_str)
+    }
+
+    /// Every sample of `topic`. Topics of an indexed log are decoded in full,
+    /// so prefer the point getters where they do.
+    pub fn topic_data(&self, topic: &str) -> Option<Cow<'_, TopicData>> {
+        match self.data.get(topic) {
+            Some(data) => Some(Cow::Borrowed(data)),
+            None => self.lazy_for(topic)?.topic_data(topic).map(Cow::Owned),
+        }
+    }
+
+    /// Samples of `topic` covering `start..=end` (microseconds): every sample
+    /// in the window and the one in effect at `start`, maybe more. Loaded
+    /// topics come back whole; indexed ones only decode the blocks the window
+    /// touches. Callers pick the window out with the timestamps as usual.
+    pub fn topic_range(&self, topic: &str, start: u64, end: u64) -> Option<Cow<'_, TopicData>> {
+        match self.data.get(topic) {
+            Some(data) => Some(Cow::Borrowed(data)),
+            None => self
+                .lazy_for(topic)?
+                .topic_range(topic, start, end)
+                .map(Cow::Owned),
+        }
+    }
+
+    /// The topic's column, or an empty one of the same kind for an indexed
+    /// topic; enough to check its kind without decoding it.
+    pub fn topic_shape(&self, topic: &str) -> Option<&TopicData> {
+        match self.data.get(topic) {
+            Some(data) => Some(data),
+            None => self.lazy_for(topic)?.shape(topic),
+        }
+    }
+
+    /// Sample times of `topic`. For an indexed topic these are its record
+    /// times, which may repeat a value.
+    pub fn topic_timestamps(&self, topic: &str) -> Option<&[u64]> {
+        match self.data.get(topic) {
+            Some(data) => Some(data.timestamps()),
+            None => self.lazy_for(topic)?.timestamps(topic),
+        }
+    }
+
     pub fn check_generation(&self, generation: u32) -> bool {
         self.generation == generation
     }
 
     pub fn has_topic(&self, topic: &str) -> bool {
-        self.data.contains_key(topic)
+        self.data.contains_key(topic) || self.lazy_for(topic).is_some()
     }
 
-    pub fn update_double(&mut self, topic: String, timestamp: u64, value: f64) {
-        let entry = self.data.entry(topic).or_insert_with(TopicData::new_double);
-        if let TopicData::Double(ts, original_vals) = entry {
-            if let Some(last_val) = original_vals.last() {
-                if (last_val - value).abs() < f64::EPSILON {
-                    return;
-                }
-            }
-            ts.push(timestamp);
-            original_vals.push(value);
-        }
+    /// Topics that received samples after `revision`.
+    pub fn topics_changed_since(&self, revision: u64) -> Vec<&str> {
+        self.topic_revisions
+            .iter()
+            .filter(|&(_, &rev)| rev > revision)
+            .map(|(topic, _)| topic.as_str())
+            .collect()
     }
 
-    pub fn update_boolean(&mut self, topic: String, timestamp: u64, value: bool) {
-        let entry = self
-            .data
-            .entry(topic)
-            .or_insert_with(TopicData::new_boolean);
-        if let TopicData::Boolean(ts, original_vals) = entry {
-            if let Some(last_val) = original_vals.last() {
-                if *last_val == value {
-                    return;
-                }
-            }
-            ts.push(timestamp);
-            original_vals.push(value);
-        }
+    /// Topics whose value at the cursor differs between times `a` and `b`,
+    /// i.e. that have a sample in `(min(a, b), max(a, b)]`.
+    pub fn topics_stepped_between(&self, a: u64, b: u64) -> Vec<&str> {
+        let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+        self.topic_names()
+            .filter(|topic| {
+                let ts = self.topic_timestamps(topic).unwrap_or_default();
+                let idx = ts.partition_point(|&t| t <= lo);
+                idx < ts.len() && ts[idx] <= hi
+            })
+            .collect()
     }
 
-    pub fn update_string(&mut self, topic: String, timestamp: u64, value: String) {
-        let entry = self.data.entry(topic).or_insert_with(TopicData::new_string);
-        if let TopicData::String(ts, original_vals) = entry {
-            if let Some(last_val) = original_vals.last() {
-                if *last_val == value {
-                    return;
+    /// Runs `push` against the topic's data (creating it with `new` if
+    /// needed) and records a new revision if a sample was actually stored.
+    /// A sample older than the topic's last one is staged instead, and
+    /// merged in once the topic has staged as many samples as it holds or
+    /// on [`Self::merge_staged`], so merging a log costs one pass.
+    fn update_with<C: SampleColumn>(
+        &mut self,
+        topic: String,
+        timestamp: u64,
+        value: &C::Value,
+        new: fn() -> TopicData,
+        column: ColumnOf<C>,
+    ) {
+        match self.data.get(&topic) {
+            Some(entry) if entry.last_timestamp() > timestamp => {
+                let held = entry.timestamps().len();
+                let staged = self.staged.entry(topic.clone()).or_insert_with(new);
+                if let Some((ts, values)) = column(staged) {
+                    ts.push(timestamp);
+                    values.push_value(value);
+                }
+                if staged.timestamps().len() >= held {
+                    self.merge_topic(&topic);
                 }
+                return;
             }
-            ts.push(timestamp);
-            original_vals.push(value);
+            Some(_) => {}
+            None => {
+                self.data.insert(topic.clone(), new());
+                self.topic_order.push(topic.clone());
+            }
+        }
+
+        let entry = self.data.get_mut(&topic).and_then(column);
+        if entry.is_some_and(|(ts, values)| push_sample(ts, values, timestamp, value)) {
+            self.revision += 1;
+            self.topic_revisions.insert(topic, self.revision);
         }
     }
 
-    pub fn update_double_array(&mut self, topic: String, timestamp: u64, value: Vec<f64>) {
-        let entry = self
-            .data
-            .entry(topic)
-            .or_insert_with(TopicData::new_double_array);
-        if let TopicData::DoubleArray(ts, original_vals) = entry {
-            if let Some(last_val) = original_vals.last() {
-                if *last_val == value {
-                    return;
-                }
+    /// Sorts the staged late samples of `topic` into its data.
+    fn merge_topic(&mut self, topic: &str) {
+        let (Some(staged), Some(entry)) = (self.staged.remove(topic), self.data.get_mut(topic))
+        else {
+            return;
+        };
+        match (entry, &staged) {
+            (TopicData::Double(ts, vals), TopicData::Double(sts, svals)) => {
+                merge_sorted(ts, vals, sts, svals)
+            }
+            (TopicData::Boolean(ts, vals), TopicData::Boolean(sts, svals)) => {
+                merge_sorted(ts, vals, sts, svals)
+            }
+            (TopicData::String(ts, vals), TopicData::String(sts, svals)) => {
+                merge_sorted(ts, vals, sts, svals)
+            }
+            (TopicData::DoubleArray(ts, vals), TopicData::DoubleArray(sts, svals)) => {
+                merge_sorted(ts, vals, sts, svals)
+            }
+            (TopicData::BooleanArray(ts, vals), TopicData::BooleanArray(sts, svals)) => {
+                merge_sorted(ts, vals, sts, svals)
             }
-            ts.push(timestamp);
-            original_vals.push(value);
+            (TopicData::StringArray(ts, vals), TopicData::StringArray(sts, svals)) => {
+                merge_sorted(ts, vals, sts, svals)
+            }
+            (TopicData::Raw(ts, vals), TopicData::Raw(sts, svals)) => {
+                merge_sorted(ts, vals, sts, svals)
+            }
+            _ => return,
         }
+        self.revision += 1;
+        self.topic_revisions
+            .insert(topic.to_string(), self.revision);
+        self.topic_rewrites.insert(topic.to_string(), self.revision);
     }
 
-    pub fn update_boolean_array(&mut self, topic: String, timestamp: u64, value: Vec<bool>) {
-        let entry = self
-            .data
-            .entry(topic)
-            .or_insert_with(TopicData::new_boolean_array);
-        if let TopicData::BooleanArray(ts, original_vals) = entry {
-            if let Some(last_val) = original_vals.last() {
-                if *last_val == value {
-                    return;
-                }
-            }
-            ts.push(timestamp);
-            original_vals.push(value);
+    /// Sorts every staged late sample into its topic; until then readers
+    /// don't see them.
+    pub fn merge_staged(&mut self) {
+        let topics: Vec<String> = self.staged.keys().cloned().collect();
+        for topic in topics {
+            self.merge_topic(&topic);
         }
     }
 
-    pub fn update_string_array(&mut self, topic: String, timestamp: u64, value: Vec<String>) {
-        let entry = self
-            .data
-            .entry(topic)
-            .or_insert_with(TopicData::new_string_array);
-        if let TopicData::StringArray(ts, original_vals) = entry {
-            if let Some(last_val) = original_vals.last() {
-                if *last_val == value {
-                    return;
-                }
-            }
-            ts.push(timestamp);
-            original_vals.push(value);
+    pub fn update_double(&mut self, topic: String, timestamp: u64, value: f64) {
+        self.update_with(
+            topic,
+            timestamp,
+            &value,
+            TopicData::new_double,
+            |entry| match entry {
+                TopicData::Double(ts, vals) => Some((ts, vals)),
+                _ => None,
+            },
+        );
+    }
+
+    pub fn update_boolean(&mut self, topic: String, timestamp: u64, value: bool) {
+        self.update_with(
+            topic,
+            timestamp,
+            &value,
+            TopicData::new_boolean,
+            |entry| match entry {
+                TopicData::Boolean(ts, vals) => Some((ts, vals)),
+                _ => None,
+            },
+        );
+    }
+
+    pub fn update_string(&mut self, topic: String, timestamp: u64, value: &str) {
+        self.update_with(
+            topic,
+            timestamp,
+            value,
+            TopicData::new_string,
+            |entry| match entry {
+                TopicData::String(ts, vals) => Some((ts, vals)),
+                _ => None,
+            },
+        );
+    }
+
+    pub fn update_double_array(&mut self, topic: String, timestamp: u64, value: &[f64]) {
+        self.update_with(
+            topic,
+            timestamp,
+            value,
+            TopicData::new_double_array,
+            |entry| match entry {
+                TopicData::DoubleArray(ts, vals) => Some((ts, vals)),
+                _ => None,
+            },
+        );
+    }
+
+    pub fn update_boolean_array(&mut self, topic: String, timestamp: u64, value: &[bool]) {
+        self.update_with(
+            topic,
+            timestamp,
+            value,
+            TopicData::new_boolean_array,
+            |entry| match entry {
+                TopicData::BooleanArray(ts, vals) => Some((ts, vals)),
+                _ => None,
+            },
+        );
+    }
+
+    pub fn update_string_array(&mut self, topic: String, timestamp: u64, value: &[String]) {
+        self.update_with(
+            topic,
+            timestamp,
+            value,
+            TopicData::new_string_array,
+            |entry| match entry {
+                TopicData::StringArray(ts, vals) => Some((ts, vals)),
+                _ => None,
+            },
+        );
+    }
+
+    pub fn update_raw(&mut self, topic: String, timestamp: u64, value: &[u8]) {
+        self.update_with(
+            topic,
+            timestamp,
+            value,
+            TopicData::new_raw,
+            |entry| match entry {
+                TopicData::Raw(ts, vals) => Some((ts, vals)),
+                _ => None,
+            },
+        );
+    }
+
+    pub fn apply(&mut self, op: StoreOp) {
+        match op {
+            StoreOp::Sample {
+                topic,
+                timestamp,
+                value,
+            } => match value {
+                Sample::Double(v) => self.update_double(topic, timestamp, v),
+                Sample::Boolean(v) => self.update_boolean(topic, timestamp, v),
+                Sample::String(v) => self.update_string(topic, timestamp, &v),
+                Sample::DoubleArray(v) => self.update_double_array(topic, timestamp, &v),
+                Sample::BooleanArray(v) => self.update_boolean_array(topic, timestamp, &v),
+                Sample::StringArray(v) => self.update_string_array(topic, timestamp, &v),
+                Sample::Raw(v) => self.update_raw(topic, timestamp, &v),
+            },
+            StoreOp::SetType { topic, type_str } => self.set_type(topic, type_str),
+            StoreOp::SetMetadata { topic, metadata } => self.set_metadata(topic, metadata),
         }
     }
 
-    pub fn update_raw(&mut self, topic: String, timestamp: u64, value: Vec<u8>) {
-        let entry = self.data.entry(topic).or_insert_with(TopicData::new_raw);
-        if let TopicData::Raw(ts, original_vals) = entry {
-            if let Some(last_val) = original_vals.last() {
-                if *last_val == value {
-                    return;
-                }
-            }
-            ts.push(timestamp);
-            original_vals.push(value);
+    /// Applies `ops`, then merges any late samples among them.
+    pub fn apply_all(&mut self, ops: impl IntoIterator<Item = StoreOp>) {
+        for op in ops {
+            self.apply(op);
         }
+        self.merge_staged();
     }
 
     fn get_index(timestamps: &[u64], query_time: u64) -> usize {
@@ -189,6 +585,19 @@ impl LogStore {
         if idx == 0 { 0 } else { idx - 1 }
     }
 
+    /// Value of `topic` at `query_time`, for topics only in the indexed log.
+    fn lazy_sample(&self, topic: &str, query_time: u64) -> Option<Sample> {
+        self.lazy_for(topic)?.sample_at(topic, query_time)
+    }
+
+    /// Owned value of `topic` at `query_time`, whatever its kind.
+    pub fn get_sample(&self, topic: &str, query_time: u64) -> Option<Sample> {
+        match self.data.get(topic) {
+            Some(data) => data.sample(Self::get_index(data.timestamps(), query_time)),
+            None => self.lazy_sample(topic, query_time),
+        }
+    }
+
     pub fn get_double(&self, topic: &str, query_time: u64, default: f64) -> f64 {
         if let Some(TopicData::Double(ts, vals)) = self.data.get(topic) {
             if ts.is_empty() {
@@ -196,6 +605,8 @@ impl LogStore {
             }
             let idx = Self::get_index(ts, query_time);
             vals.get(idx).copied().unwrap_or(default)
+        } else if let Some(Sample::Double(v)) = self.lazy_sample(topic, query_time) {
+            v
         } else {
             default
         }
@@ -208,119 +619,117 @@ impl LogStore {
             }
             let idx = Self::get_index(ts, query_time);
             vals.get(idx).copied().unwrap_or(default)
+        } else if let Some(Sample::Boolean(v)) = self.lazy_sample(topic, query_time) {
+            v
         } else {
             default
         }
     }
 
     pub fn get_boolean_series(&self, topic: &str) -> Option<(Vec<u64>, Vec<bool>)> {
-        if let Some(TopicData::Boolean(ts, vals)) = self.data.get(topic) {
+        if let TopicData::Boolean(ts, vals) = self.topic_data(topic)?.as_ref() {
             Some((ts.clone(), vals.clone()))
         } else {
             None
         }
     }
 
-    pub fn get_string(&self, topic: &str, query_time: u64, default: String) -> String {
+    pub fn get_string(&self, topic: &str, query_time: u64) -> Option<Cow<'_, str>> {
         if let Some(TopicData::String(ts, vals)) = self.data.get(topic) {
             if ts.is_empty() {
-                return default;
+                return None;
             }
-            let idx = Self::get_index(ts, query_time);
-            vals.get(idx).cloned().unwrap_or(default)
+            vals.get(Self::get_index(ts, query_time)).map(Cow::Borrowed)
+        } else if let Some(Sample::String(v)) = self.lazy_sample(topic, query_time) {
+            Some(Cow::Owned(v))
         } else {
-            default
+            None
         }
     }
 
-    pub fn get_double_array(&self, topic: &str, query_time: u64, default: Vec<f64>) -> Vec<f64> {
+    pub fn get_double_array(&self, topic: &str, query_time: u64) -> Option<Cow<'_, [f64]>> {
         if let Some(TopicData::DoubleArray(ts, vals)) = self.data.get(topic) {
             if ts.is_empty() {
-                return default;
+                return None;
             }
-            let idx = Self::get_index(ts, query_time);
-            vals.get(idx).cloned().unwrap_or(default)
+            vals.get(Self::get_index(ts, query_time)).map(Cow::Borrowed)
+        } else if let Some(Sample::DoubleArray(v)) = self.lazy_sample(topic, query_time) {
+            Some(Cow::Owned(v))
         } else {
-            default
+            None
         }
     }
 
-    pub fn get_boolean_array(&self, topic: &str, query_time: u64, default: Vec<bool>) -> Vec<bool> {
+    pub fn get_boolean_array(&self, topic: &str, query_time: u64) -> Option<Cow<'_, [bool]>> {
         if let Some(TopicData::BooleanArray(ts, vals)) = self.data.get(topic) {
             if ts.is_empty() {
-                return default;
+                return None;
             }
-            let idx = Self::get_index(ts, query_time);
-            vals.get(idx).cloned().unwrap_or(default)
+            vals.get(Self::get_index(ts, query_time)).map(Cow::Borrowed)
+        } else if let Some(Sample::BooleanArray(v)) = self.lazy_sample(topic, query_time) {
+            Some(Cow::Owned(v))
         } else {
-            default
+            None
         }
     }
 
-    pub fn get_string_array(
-        &self,
-        topic: &str,
-        query_time: u64,
-        default: Vec<String>,
-    ) -> Vec<String> {
+    /// Borrowed, so loaded topics only; indexed ones come from
+    /// [`Self::get_sample`].
+    pub fn get_string_array(&self, topic: &str, query_time: u64) -> Option<StrList<'_>> {
         if let Some(TopicData::StringArray(ts, vals)) = self.data.get(topic) {
             if ts.is_empty() {
-                return default;
+                return None;
             }
-            let idx = Self::get_index(ts, query_time);
-            vals.get(idx).cloned().unwrap_or(default)
+            vals.get(Self::get_index(ts, query_time))
         } else {
-            default
+            None
         }
     }
 
-    pub fn get_raw(&self, topic: &str, query_time: u64) -> Option<Vec<u8>> {
+    pub fn get_raw(&self, topic: &str, query_time: u64) -> Option<Cow<'_, [u8]>> {
         if let Some(TopicData::Raw(ts, vals)) = self.data.get(topic) {
             if ts.is_empty() {
                 return None;
             }
-            let idx = Self::get_index(ts, query_time);
-            vals.get(idx).cloned()
+            vals.get(Self::get_index(ts, query_time)).map(Cow::Borrowed)
+        } else if let Some(Sample::Raw(v)) = self.lazy_sample(topic, query_time) {
+            Some(Cow::Owned(v))
         } else {
             None
         }
     }
 
+    fn type_str(&self, topic: &str, data: &TopicData) -> String {
+        if let Some(t) = self.topic_types.get(topic) {
+            return t.clone();
+        }
+        match data {
+            TopicData::Double(_, _) => "double",
+            TopicData::Boolean(_, _) => "boolean",
+            TopicData::String(_, _) => "string",
+            TopicData::DoubleArray(_, _) => "double[]",
+            TopicData::BooleanArray(_, _) => "boolean[]",
+            TopicData::StringArray(_, _) => "string[]",
+            TopicData::Raw(_, _) => "raw",
+        }
+        .to_string()
+    }
+
+    pub fn get_topic_type(&self, topic: &str) -> Option<String> {
+        self.topic_shape(topic)
+            .map(|data| self.type_str(topic, data))
+    }
+
     pub fn get_topics_info(&self) -> Vec<(String, String)> {
-        self.data
-            .iter()
-            .map(|(k, v)| {
-                let type_str = if let Some(t) = self.topic_types.get(k) {
-                    t.as_str()
-                } else {
-                    match v {
-                        TopicData::Double(_, _) => "double",
-                        TopicData::Boolean(_, _) => "boolean",
-                        TopicData::String(_, _) => "string",
-                        TopicData::DoubleArray(_, _) => "double[]",
-                        TopicData::BooleanArray(_, _) => "boolean[]",
-                        TopicData::StringArray(_, _) => "string[]",
-                        TopicData::Raw(_, _) => "raw",
-                    }
-                };
-                (k.clone(), type_str.to_string())
-            })
+        self.topic_names()
+            .filter_map(|topic| Some((topic.to_string(), self.get_topic_type(topic)?)))
             .collect()
     }
 
     pub fn get_start_timestamp(&self) -> u64 {
         let mut min_ts = u64::MAX;
-        for data in self.data.values() {
-            let first = match data {
-                TopicData::Double(ts, _) => ts.first(),
-                TopicData::Boolean(ts, _) => ts.first(),
-                TopicData::String(ts, _) => ts.first(),
-                TopicData::DoubleArray(ts, _) => ts.first(),
-                TopicData::BooleanArray(ts, _) => ts.first(),
-                TopicData::StringArray(ts, _) => ts.first(),
-                TopicData::Raw(ts, _) => ts.first(),
-            };
-            if let Some(&t) = first {
+        for topic in self.topic_names() {
+            if let Some(&t) = self.topic_timestamps(topic).and_then(|ts| ts.first()) {
                 if t < min_ts {
                     min_ts = t;
                 }
@@ -331,8 +740,12 @@ impl LogStore {
 
     pub fn get_last_timestamp(&self) -> u64 {
         let mut max_ts = 0;
-        for data in self.data.values() {
-            let last = data.last_timestamp();
+        for topic in self.topic_names() {
+            let last = self
+                .topic_timestamps(topic)
+                .and_then(|ts| ts.last())
+                .copied()
+                .unwrap_or(0);
             if last > max_ts {
                 max_ts = last;
             }
@@ -340,3 +753,163 @@ impl LogStore {
         max_ts
     }
 }
+
+/// `start..=end` (microseconds) cut into consecutive inclusive windows of
+/// `width`, for walking a long log through [`LogStore::topic_range`].
+/// Nothing when `end < start`.
+pub fn windows(start: u64, end: u64, width: u64) -> impl Iterator<Item = (u64, u64)> {
+    let width = width.max(1);
+    std::iter::successors((start <= end).then_some(start), move |&from: &u64| {
+        from.checked_add(width).filter(|&next| next <= end)
+    })
+    .map(move |from| (from, from.saturating_add(width - 1).min(end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doubles(samples: &[(u64, f64)]) -> Vec<StoreOp> {
+        samples
+            .iter()
+            .map(|&(t, v)| StoreOp::sample("/x".into(), t, Sample::Double(v)))
+            .collect()
+    }
+
+    fn series(store: &LogStore) -> (Vec<u64>, Vec<f64>) {
+        match store.data.get("/x") {
+            Some(TopicData::Double(ts, vals)) => (ts.clone(), vals.clone()),
+            _ => panic!("no double topic"),
+        }
+    }
+
+    #[test]
+    fn interleaves_two_sources() {
+        let mut store = LogStore::new();
+        store.apply_all(doubles(&[(100, 1.0), (300, 3.0), (500, 5.0)]));
+        store.apply_all(doubles(&[(200, 2.0), (400, 4.0), (600, 6.0)]));
+
+        let (ts, vals) = series(&store);
+        assert_eq!(ts, [100, 200, 300, 400, 500, 600]);
+        assert_eq!(vals, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+        // At, between and before the sample times
+        for (i, &t) in ts.iter().enumerate() {
+            assert_eq!(LogStore::get_index(&ts, t), i);
+            assert_eq!(LogStore::get_index(&ts, t + 50), i);
+            assert_eq!(store.get_double("/x", t, f64::NAN), vals[i]);
+            assert_eq!(store.get_double("/x", t + 99, f64::NAN), vals[i]);
+        }
+        assert_eq!(LogStore::get_index(&ts, 99), 0);
+        assert_eq!(LogStore::get_index(&ts, 0), 0);
+        assert_eq!(store.get_double("/x", 0, f64::NAN), 1.0);
+    }
+
+    #[test]
+    fn interleaves_sources_sample_by_sample() {
+        let mut store = LogStore::new();
+        let a = (0..1000u64).map(|i| (i * 20, i as f64));
+        let b = (0..1000u64).map(|i| (i * 20 + 10, -(i as f64) - 1.0));
+        // The second source lags the first by 50 samples
+        let mut ops = Vec::new();
+        let (a, b): (Vec<_>, Vec<_>) = (a.collect(), b.collect());
+        for i in 0..1050 {
+            if i < 1000 {
+                ops.extend(doubles(&a[i..i + 1]));
+            }
+            if i >= 50 {
+                ops.extend(doubles(&b[i - 50..i - 49]));
+            }
+        }
+        store.apply_all(ops);
+
+        let (ts, vals) = series(&store);
+        assert_eq!(ts.len(), 2000);
+        assert!(ts.is_sorted());
+        for (i, &t) in ts.iter().enumerate() {
+            let expected = if t % 20 == 0 {
+                (t / 20) as f64
+            } else {
+                -((t / 20) as f64) - 1.0
+            };
+            assert_eq!(vals[i], expected);
+            assert_eq!(store.get_double("/x", t + 5, f64::NAN), expected);
+        }
+    }
+
+    #[test]
+    fn late_samples_keep_values_deduplicated() {
+        let mut store = LogStore::new();
+        store.apply_all(doubles(&[(100, 1.0), (300, 2.0), (500, 3.0)]));
+        // Repeats the value before it: dropped
+        store.apply_all(doubles(&[(200, 1.0)]));
+        assert_eq!(series(&store).0, [100, 300, 500]);
+        // Holds the value after it: that sample moves back in time
+        store.apply_all(doubles(&[(250, 2.0)]));
+        assert_eq!(series(&store), (vec![100, 250, 500], vec![1.0, 2.0, 3.0]));
+        // Same time as an existing sample: goes after it
+        store.apply_all(doubles(&[(250, 4.0)]));
+        assert_eq!(store.get_double("/x", 250, f64::NAN), 4.0);
+        assert_eq!(series(&store).0, [100, 250, 250, 500]);
+    }
+
+    #[test]
+    fn late_samples_wait_for_merge() {
+        let mut store = LogStore::new();
+        store.apply_all(doubles(&[(100, 1.0), (200, 2.0), (300, 3.0), (400, 4.0)]));
+        let revision = store.revision;
+        store.apply(StoreOp::sample("/x".into(), 150, Sample::Double(9.0)));
+        assert_eq!(store.get_double("/x", 150, f64::NAN), 1.0);
+        assert_eq!(store.revision, revision);
+
+        store.merge_staged();
+        assert_eq!(store.get_double("/x", 150, f64::NAN), 9.0);
+        assert!(store.revision > revision);
+        assert_eq!(store.topic_rewrites.get("/x"), Some(&store.revision));
+    }
+
+    #[test]
+    fn interleaves_variable_length_samples() {
+        let mut store = LogStore::new();
+        let strings = |t: u64, v: &[&str]| {
+            let v = v.iter().map(|s| s.to_string()).collect();
+            StoreOp::sample("/s".into(), t, Sample::StringArray(v))
+        };
+        let arrays =
+            |t: u64, v: &[f64]| StoreOp::sample("/a".into(), t, Sample::DoubleArray(v.to_vec()));
+        store.apply_all([
+            strings(100, &["a"]),
+            strings(300, &["c", "cc"]),
+            arrays(100, &[1.0]),
+            arrays(300, &[3.0, 3.0, 3.0]),
+        ]);
+        store.apply_all([
+            strings(200, &[]),
+            strings(400, &["d"]),
+            arrays(50, &[0.5, 0.5]),
+            arrays(200, &[2.0]),
+        ]);
+
+        let names = |t| store.get_string_array("/s", t).unwrap().to_vec();
+        assert_eq!(names(150), ["a"]);
+        assert!(names(250).is_empty());
+        assert_eq!(names(350), ["c", "cc"]);
+        assert_eq!(names(450), ["d"]);
+        assert_eq!(
+            store.get_double_array("/a", 60).as_deref(),
+            Some(&[0.5, 0.5][..])
+        );
+        assert_eq!(
+            store.get_double_array("/a", 150).as_deref(),
+            Some(&[1.0][..])
+        );
+        assert_eq!(
+            store.get_double_array("/a", 250).as_deref(),
+            Some(&[2.0][..])
+        );
+        assert_eq!(
+            store.get_double_array("/a", 350).as_deref(),
+            Some(&[3.0; 3][..])
+        );
+    }
+}
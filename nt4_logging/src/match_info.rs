@@ -0,0 +1,333 @@
+//! FMS match metadata (event, match, alliance) and match phase boundaries,
+//! read from the DriverStation topics AdvantageKit logs or the `/FMSInfo`
+//! table published over NetworkTables.
+
+use crate::batch::Sample;
+use crate::log_store::{LogStore, TopicData};
+use crate::modes::{ModeInterval, RobotMode};
+use std::borrow::Cow;
+
+const EVENT_NAME_TOPICS: &[&str] = &[
+    "/AdvantageKit/DriverStation/EventName",
+    "/DriverStation/EventName",
+    "/FMSInfo/EventName",
+];
+const MATCH_NUMBER_TOPICS: &[&str] = &[
+    "/AdvantageKit/DriverStation/MatchNumber",
+    "/DriverStation/MatchNumber",
+    "/FMSInfo/MatchNumber",
+];
+const REPLAY_NUMBER_TOPICS: &[&str] = &[
+    "/AdvantageKit/DriverStation/ReplayNumber",
+    "/DriverStation/ReplayNumber",
+    "/FMSInfo/ReplayNumber",
+];
+const MATCH_TYPE_TOPICS: &[&str] = &[
+    "/AdvantageKit/DriverStation/MatchType",
+    "/DriverStation/MatchType",
+    "/FMSInfo/MatchType",
+];
+/// 1-3 red, 4-6 blue (WPILib's `AllianceStationID`).
+const ALLIANCE_STATION_TOPICS: &[&str] = &[
+    "/AdvantageKit/DriverStation/AllianceStation",
+    "/DriverStation/AllianceStation",
+];
+const FMS_RED_TOPIC: &str = "/FMSInfo/IsRedAlliance";
+const FMS_STATION_TOPIC: &str = "/FMSInfo/StationNumber";
+/// Seconds left in the current phase, as the driver station reports it.
+const MATCH_TIME_TOPICS: &[&str] = &[
+    "/AdvantageKit/DriverStation/MatchTime",
+    "/DriverStation/MatchTime",
+];
+
+/// Phase lengths (seconds) for logs without a match time topic.
+const AUTO_SECONDS: f64 = 15.0;
+const TELEOP_SECONDS: f64 = 135.0;
+/// Endgame is the end of teleop.
+const ENDGAME_SECONDS: f64 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchType {
+    #[default]
+    None,
+    Practice,
+    Qualification,
+    Elimination,
+}
+
+impl MatchType {
+    fn from_index(index: f64) -> Self {
+        match index as i64 {
+            1 => MatchType::Practice,
+            2 => MatchType::Qualification,
+            3 => MatchType::Elimination,
+            _ => MatchType::None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MatchType::None => "None",
+            MatchType::Practice => "Practice",
+            MatchType::Qualification => "Qualification",
+            MatchType::Elimination => "Elimination",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alliance {
+    Red,
+    Blue,
+}
+
+impl Alliance {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Alliance::Red => "red",
+            Alliance::Blue => "blue",
+        }
+    }
+}
+
+/// One enabled phase of the match (microseconds).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Phase {
+    pub start: u64,
+    pub end: u64,
+    /// Match time at the start of the phase, in seconds.
+    pub length: f64,
+}
+
+impl Phase {
+    fn contains(&self, time: u64) -> bool {
+        (self.start..=self.end).contains(&time)
+    }
+
+    /// Seconds left in the phase at `time`.
+    fn remaining(&self, time: u64) -> f64 {
+        (self.length - (time - self.start) as f64 / 1e6).max(0.0)
+    }
+
+    /// Log time at which `remaining` seconds were left.
+    fn time_at(&self, remaining: f64) -> u64 {
+        let elapsed = ((self.length - remaining).max(0.0) * 1e6) as u64;
+        self.start + elapsed
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchInfo {
+    pub event_name: String,
+    pub match_type: MatchType,
+    pub match_number: u32,
+    pub replay_number: u32,
+    pub alliance: Option<Alliance>,
+    /// Driver station 1-3, 0 when unknown.
+    pub station: u8,
+    pub auto: Option<Phase>,
+    pub teleop: Option<Phase>,
+}
+
+impl MatchInfo {
+    /// Reads the match metadata in `store`; phases come from its mode
+    /// `intervals` (see [`crate::modes::ModeTracker`]). A log holding several
+    /// matches describes the last one that reached teleop.
+    pub fn read(store: &LogStore, intervals: &[ModeInterval]) -> Self {
+        // FMS fields are blank until the FMS connects, so keep the last set value
+        let event_name = last_value(store, EVENT_NAME_TOPICS, |s| match s {
+            Sample::String(name) if !name.is_empty() => Some(name.clone()),
+            _ => None,
+        });
+        let number = |topics| {
+            last_value(store, topics, |s| match s {
+                Sample::Double(v) if *v > 0.0 => Some(*v),
+                _ => None,
+            })
+        };
+
+        let station = number(ALLIANCE_STATION_TOPICS)
+            .map(|id| match id as u8 {
+                id @ 1..=3 => (Some(Alliance::Red), id),
+                id @ 4..=6 => (Some(Alliance::Blue), id - 3),
+                _ => (None, 0),
+            })
+            .or_else(|| {
+                let station = number(&[FMS_STATION_TOPIC])? as u8;
+                let red = last_value(store, &[FMS_RED_TOPIC], |s| match s {
+                    Sample::Boolean(v) => Some(*v),
+                    _ => None,
+                })?;
+                let alliance = if red { Alliance::Red } else { Alliance::Blue };
+                Some((Some(alliance), station))
+            });
+        let (alliance, station) = station.unwrap_or((None, 0));
+
+        let (auto, teleop) = phases(store, intervals);
+        Self {
+            event_name: event_name.unwrap_or_default(),
+            match_type: number(MATCH_TYPE_TOPICS)
+                .map(MatchType::from_index)
+                .unwrap_or_default(),
+            match_number: number(MATCH_NUMBER_TOPICS).unwrap_or(0.0) as u32,
+            replay_number: number(REPLAY_NUMBER_TOPICS).unwrap_or(0.0) as u32,
+            alliance,
+            station,
+            auto,
+            teleop,
+        }
+    }
+
+    /// When endgame began, a fixed time before the end of teleop.
+    pub fn endgame_start(&self) -> Option<u64> {
+        let teleop = self.teleop?;
+        Some(teleop.time_at(ENDGAME_SECONDS.min(teleop.length)))
+    }
+
+    /// Seconds left in the auto or teleop phase at log time `time`; `None`
+    /// outside them.
+    pub fn match_time(&self, time: u64) -> Option<f64> {
+        [self.auto, self.teleop]
+            .into_iter()
+            .flatten()
+            .find(|phase| phase.contains(time))
+            .map(|phase| phase.remaining(time))
+    }
+
+    /// Log time at which `match_time` seconds were left in auto or teleop.
+    pub fn timestamp_at(&self, match_time: f64, teleop: bool) -> Option<u64> {
+        let phase = if teleop { self.teleop } else { self.auto }?;
+        Some(phase.time_at(match_time))
+    }
+}
+
+/// Part of the log to analyze, as offered by the heatmap and odometry views.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeRange {
+    Enabled,
+    Auto,
+    Teleop,
+    /// Teleop of the match up to the start of endgame.
+    TeleopNoEndgame,
+    FullLog,
+    /// Explicit bounds (microseconds), e.g. the visible part of the timeline.
+    Between(u64, u64),
+}
+
+impl TimeRange {
+    /// The range named by a "Time Range" option; `start` and `end` bound
+    /// "Visible Range".
+    pub fn from_name(name: &str, start: u64, end: u64) -> Option<Self> {
+        let range = match name {
+            "Enabled" => TimeRange::Enabled,
+            "Auto" => TimeRange::Auto,
+            "Teleop" => TimeRange::Teleop,
+            "Teleop (No Endgame)" => TimeRange::TeleopNoEndgame,
+            "Full Log" => TimeRange::FullLog,
+            "Visible Range" => TimeRange::Between(start, end),
+            _ => return None,
+        };
+        Some(range)
+    }
+
+    /// The `(start, end)` spans of the range, in time order. Mode ranges
+    /// cover every interval of those modes in `intervals`.
+    pub fn spans(
+        &self,
+        store: &LogStore,
+        intervals: &[ModeInterval],
+        info: &MatchInfo,
+    ) -> Vec<(u64, u64)> {
+        let modes = |pick: fn(RobotMode) -> bool| {
+            let mut spans: Vec<(u64, u64)> = Vec::new();
+            for interval in intervals.iter().filter(|i| pick(i.mode)) {
+                match spans.last_mut() {
+                    Some(last) if last.1 == interval.start => last.1 = interval.end,
+                    _ => spans.push((interval.start, interval.end)),
+                }
+            }
+            spans
+        };
+        match *self {
+            TimeRange::Enabled => {
+                modes(|m| matches!(m, RobotMode::Auto | RobotMode::Teleop | RobotMode::Test))
+            }
+            TimeRange::Auto => modes(|m| m == RobotMode::Auto),
+            TimeRange::Teleop => modes(|m| m == RobotMode::Teleop),
+            TimeRange::TeleopNoEndgame => info
+                .teleop
+                .zip(info.endgame_start())
+                .map(|(teleop, endgame)| (teleop.start, endgame))
+                .into_iter()
+                .collect(),
+            TimeRange::FullLog => vec![(store.get_start_timestamp(), store.get_last_timestamp())],
+            TimeRange::Between(start, end) => vec![(start, end.max(start))],
+        }
+    }
+}
+
+/// Auto and teleop of the last match in `intervals`: its teleop is the last
+/// teleop interval, and auto the last auto interval before it.
+fn phases(store: &LogStore, intervals: &[ModeInterval]) -> (Option<Phase>, Option<Phase>) {
+    let teleop = intervals.iter().rposition(|i| i.mode == RobotMode::Teleop);
+    let auto = intervals[..teleop.unwrap_or(intervals.len())]
+        .iter()
+        .rposition(|i| i.mode == RobotMode::Auto);
+
+    let match_time = MATCH_TIME_TOPICS
+        .iter()
+        .find(|topic| store.has_topic(topic));
+    let phase = |idx: Option<usize>, default: f64| {
+        let interval = intervals[idx?];
+        let data = match_time.and_then(|t| store.topic_range(t, interval.start, interval.end));
+        let length = match data.as_deref() {
+            Some(TopicData::Double(ts, vals)) => measured_length(ts, vals, &interval),
+            _ => None,
+        };
+        Some(Phase {
+            start: interval.start,
+            end: interval.end,
+            length: length.unwrap_or(default),
+        })
+    };
+    (phase(auto, AUTO_SECONDS), phase(teleop, TELEOP_SECONDS))
+}
+
+/// Phase length implied by the match time samples inside `interval`. When the
+/// driver station reports whole seconds, a stored sample (repeats are dropped)
+/// is the first of its second, so the phase started just under a second
+/// before the value plus the time elapsed; otherwise every sample gives the
+/// length directly. Phases last whole seconds.
+fn measured_length(ts: &[u64], vals: &[f64], interval: &ModeInterval) -> Option<f64> {
+    let first = ts.partition_point(|&t| t < interval.start);
+    let last = ts.partition_point(|&t| t <= interval.end);
+    let vals = &vals[first..last];
+    let whole_seconds = vals.iter().all(|v| v.fract() == 0.0);
+    let lengths = (first..last)
+        .zip(vals)
+        .filter(|&(_, &v)| v > 0.0)
+        .map(|(idx, &v)| v + (ts[idx] - interval.start) as f64 / 1e6);
+    let length = if whole_seconds {
+        lengths.map(|len| len + 1.0).min_by(f64::total_cmp)
+    } else {
+        lengths.max_by(f64::total_cmp)
+    };
+    length.map(f64::round)
+}
+
+/// Latest sample of the first present topic in `topics` that `pick` accepts.
+/// Only the end of the topic is decoded unless the last sample is rejected.
+fn last_value<T>(
+    store: &LogStore,
+    topics: &[&str],
+    pick: impl Fn(&Sample) -> Option<T>,
+) -> Option<T> {
+    let topic = topics.iter().find(|topic| store.has_topic(topic))?;
+    let last = *store.topic_timestamps(topic)?.last()?;
+    let latest = |data: Cow<TopicData>| {
+        (0..data.timestamps().len())
+            .rev()
+            .find_map(|idx| pick(&data.sample(idx)?))
+    };
+    latest(store.topic_range(topic, last, u64::MAX)?).or_else(|| latest(store.topic_data(topic)?))
+}
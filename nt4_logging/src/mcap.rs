@@ -0,0 +1,441 @@
+//! MCAP container reading and writing (https://mcap.dev/spec). Only the
+//! records needed to get messages in and out are handled; indexes and the
+//! summary section are skipped on read and not written.
+
+use byteorder::{ByteOrder, LittleEndian};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+
+pub const MAGIC: &[u8; 8] = b"\x89MCAP0\r\n";
+
+const OP_HEADER: u8 = 0x01;
+const OP_FOOTER: u8 = 0x02;
+const OP_SCHEMA: u8 = 0x03;
+const OP_CHANNEL: u8 = 0x04;
+const OP_MESSAGE: u8 = 0x05;
+const OP_CHUNK: u8 = 0x06;
+const OP_DATA_END: u8 = 0x0F;
+
+/// Largest decompressed chunk accepted; writers default to a few MB.
+const MAX_CHUNK: u64 = 256 << 20;
+
+#[derive(Debug, Clone)]
+pub struct McapSchema {
+    pub id: u16,
+    pub name: String,
+    pub encoding: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct McapChannel {
+    pub id: u16,
+    pub schema_id: u16,
+    pub topic: String,
+    pub message_encoding: String,
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct McapMessage {
+    pub channel_id: u16,
+    pub sequence: u32,
+    /// Nanoseconds.
+    pub log_time: u64,
+    pub publish_time: u64,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub enum McapRecord {
+    Schema(McapSchema),
+    Channel(McapChannel),
+    Message(McapMessage),
+}
+
+/// Reads an MCAP file front to back, opening chunks as it meets them.
+pub struct McapReader<R> {
+    reader: R,
+    /// Bytes of the file consumed so far.
+    position: u64,
+    /// Length of the file, to check record lengths against.
+    len: u64,
+    /// Records from the chunk being read.
+    pending: VecDeque<McapRecord>,
+    done: bool,
+}
+
+impl<R: Read> McapReader<R> {
+    /// Reads the magic from `reader`, a file of `len` bytes.
+    pub fn new(mut reader: R, len: u64) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not an MCAP file"));
+        }
+        Ok(Self {
+            reader,
+            position: 8,
+            len,
+            pending: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Next schema, channel or message, or `None` once the data section ends.
+    /// A file cut off mid-record (still being written) also just ends.
+    pub fn next_record(&mut self) -> io::Result<Option<McapRecord>> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Ok(Some(record));
+            }
+            if self.done {
+                return Ok(None);
+            }
+
+            let mut header = [0u8; 9];
+            if !fill(&mut self.reader, &mut header)? {
+                self.done = true;
+                continue;
+            }
+            let opcode = header[0];
+            let len = LittleEndian::read_u64(&header[1..]);
+            if matches!(opcode, OP_FOOTER | OP_DATA_END) || &header[..8] == MAGIC {
+                self.done = true;
+                continue;
+            }
+
+            // Also guards the allocation against a garbage length
+            if len > self.len.saturating_sub(self.position + 9) {
+                self.done = true;
+                continue;
+            }
+            let mut body = vec![0u8; len as usize];
+            if !fill(&mut self.reader, &mut body)? {
+                self.done = true;
+                continue;
+            }
+            self.position += 9 + len;
+
+            if opcode == OP_CHUNK {
+                let records = read_chunk(&body)?;
+                parse_records(&records, &mut self.pending)?;
+            } else if let Some(record) = parse_record(opcode, &body)? {
+                return Ok(Some(record));
+            }
+        }
+    }
+}
+
+/// Parses back-to-back records (a chunk's contents).
+fn parse_records(data: &[u8], out: &mut VecDeque<McapRecord>) -> io::Result<()> {
+    let mut cursor = Cursor(data);
+    while !cursor.0.is_empty() {
+        let opcode = cursor.u8()?;
+        let len = cursor.u64()? as usize;
+        if let Some(record) = parse_record(opcode, cursor.take(len)?)? {
+            out.push_back(record);
+        }
+    }
+    Ok(())
+}
+
+fn parse_record(opcode: u8, body: &[u8]) -> io::Result<Option<McapRecord>> {
+    let mut cursor = Cursor(body);
+    let record = match opcode {
+        OP_SCHEMA => McapRecord::Schema(McapSchema {
+            id: cursor.u16()?,
+            name: cursor.string()?,
+            encoding: cursor.string()?,
+            data: cursor.bytes()?.to_vec(),
+        }),
+        OP_CHANNEL => McapRecord::Channel(McapChannel {
+            id: cursor.u16()?,
+            schema_id: cursor.u16()?,
+            topic: cursor.string()?,
+            message_encoding: cursor.string()?,
+            metadata: cursor.string_map()?,
+        }),
+        OP_MESSAGE => McapRecord::Message(McapMessage {
+            channel_id: cursor.u16()?,
+            sequence: cursor.u32()?,
+            log_time: cursor.u64()?,
+            publish_time: cursor.u64()?,
+            data: cursor.0.to_vec(),
+        }),
+        _ => return Ok(None),
+    };
+    Ok(Some(record))
+}
+
+/// Decompresses a chunk record's contents.
+fn read_chunk(body: &[u8]) -> io::Result<Vec<u8>> {
+    let mut cursor = Cursor(body);
+    let _start_time = cursor.u64()?;
+    let _end_time = cursor.u64()?;
+    let uncompressed_size = cursor.u64()?;
+    let _crc = cursor.u32()?;
+    let compression = cursor.string()?;
+    let len = cursor.u64()?;
+    let records = cursor.take(usize::try_from(len).unwrap_or(usize::MAX))?;
+    if uncompressed_size > MAX_CHUNK {
+        return Err(invalid("MCAP chunk is too large"));
+    }
+
+    match compression.as_str() {
+        "" => Ok(records.to_vec()),
+        "zstd" => decompress(
+            zstd::stream::read::Decoder::with_buffer(records)?,
+            uncompressed_size,
+        ),
+        "lz4" => decompress(
+            lz4_flex::frame::FrameDecoder::new(records),
+            uncompressed_size,
+        ),
+        other => Err(invalid(&format!("unsupported chunk compression {}", other))),
+    }
+}
+
+/// Reads a decompressor to the end, growing the buffer with the data rather
+/// than trusting the declared `size` up front.
+fn decompress(decoder: impl Read, size: u64) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    decoder.take(size + 1).read_to_end(&mut out)?;
+    if out.len() as u64 != size {
+        return Err(invalid("MCAP chunk doesn't match its uncompressed size"));
+    }
+    Ok(out)
+}
+
+/// Writes an unchunked, unindexed MCAP file. Readers fall back to scanning
+/// when there is no summary, which is fine at our sizes.
+pub struct McapWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> McapWriter<W> {
+    pub fn new(mut writer: W, library: &str) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        let mut body = Vec::new();
+        put_string(&mut body, "");
+        put_string(&mut body, library);
+        write_record(&mut writer, OP_HEADER, &body)?;
+        Ok(Self { writer })
+    }
+
+    pub fn write_schema(&mut self, schema: &McapSchema) -> io::Result<()> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&schema.id.to_le_bytes());
+        put_string(&mut body, &schema.name);
+        put_string(&mut body, &schema.encoding);
+        put_bytes(&mut body, &schema.data);
+        write_record(&mut self.writer, OP_SCHEMA, &body)
+    }
+
+    pub fn write_channel(&mut self, channel: &McapChannel) -> io::Result<()> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&channel.id.to_le_bytes());
+        body.extend_from_slice(&channel.schema_id.to_le_bytes());
+        put_string(&mut body, &channel.topic);
+        put_string(&mut body, &channel.message_encoding);
+        let mut map = Vec::new();
+        for (key, value) in &channel.metadata {
+            put_string(&mut map, key);
+            put_string(&mut map, value);
+        }
+        put_bytes(&mut body, &map);
+        write_record(&mut self.writer, OP_CHANNEL, &body)
+    }
+
+    pub fn write_message(&mut self, message: &McapMessage) -> io::Result<()> {
+        let mut body = Vec::with_capacity(22 + message.data.len());
+        body.extend_from_slice(&message.channel_id.to_le_bytes());
+        body.extend_from_slice(&message.sequence.to_le_bytes());
+        body.extend_from_slice(&message.log_time.to_le_bytes());
+        body.extend_from_slice(&message.publish_time.to_le_bytes());
+        body.extend_from_slice(&message.data);
+        write_record(&mut self.writer, OP_MESSAGE, &body)
+    }
+
+    /// Ends the data section, writes an empty footer and the closing magic.
+    pub fn finish(mut self) -> io::Result<W> {
+        write_record(&mut self.writer, OP_DATA_END, &0u32.to_le_bytes())?;
+        write_record(&mut self.writer, OP_FOOTER, &[0u8; 20])?;
+        self.writer.write_all(MAGIC)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn write_record(writer: &mut impl Write, opcode: u8, body: &[u8]) -> io::Result<()> {
+    writer.write_all(&[opcode])?;
+    writer.write_all(&(body.len() as u64).to_le_bytes())?;
+    writer.write_all(body)
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    put_bytes(out, value.as_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, value: &[u8]) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+/// `read_exact` that reports a clean end of file as `false`.
+fn fill(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Little-endian reader over a record body.
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid("truncated MCAP record"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(LittleEndian::read_u16(self.take(2)?))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(LittleEndian::read_u64(self.take(8)?))
+    }
+
+    fn bytes(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        Ok(String::from_utf8_lossy(self.bytes()?).into_owned())
+    }
+
+    fn string_map(&mut self) -> io::Result<BTreeMap<String, String>> {
+        let mut entries = Cursor(self.bytes()?);
+        let mut map = BTreeMap::new();
+        while !entries.0.is_empty() {
+            let key = entries.string()?;
+            let value = entries.string()?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(time: u64) -> McapMessage {
+        McapMessage {
+            channel_id: 1,
+            sequence: 0,
+            log_time: time,
+            publish_time: time,
+            data: b"{\"value\": 1}".to_vec(),
+        }
+    }
+
+    /// A file holding one chunk of `records`, declared as `size` bytes
+    /// uncompressed.
+    fn chunked(compression: &str, records: &[u8], size: u64) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&0u64.to_le_bytes());
+        body.extend_from_slice(&0u64.to_le_bytes());
+        body.extend_from_slice(&size.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes());
+        put_string(&mut body, compression);
+        body.extend_from_slice(&(records.len() as u64).to_le_bytes());
+        body.extend_from_slice(records);
+        let mut file = MAGIC.to_vec();
+        write_record(&mut file, OP_CHUNK, &body).unwrap();
+        file
+    }
+
+    fn records() -> Vec<u8> {
+        let mut writer = McapWriter { writer: Vec::new() };
+        for time in [1, 2, 3] {
+            writer.write_message(&message(time)).unwrap();
+        }
+        writer.writer
+    }
+
+    fn read_all(file: &[u8]) -> io::Result<Vec<McapRecord>> {
+        let mut reader = McapReader::new(file, file.len() as u64)?;
+        let mut out = Vec::new();
+        while let Some(record) = reader.next_record()? {
+            out.push(record);
+        }
+        Ok(out)
+    }
+
+    #[test]
+    fn reads_compressed_chunks() {
+        let records = records();
+        let size = records.len() as u64;
+        let zstd = zstd::bulk::compress(&records, 3).unwrap();
+        let mut lz4 = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        lz4.write_all(&records).unwrap();
+        let lz4 = lz4.finish().unwrap();
+
+        for file in [
+            chunked("", &records, size),
+            chunked("zstd", &zstd, size),
+            chunked("lz4", &lz4, size),
+        ] {
+            let read = read_all(&file).unwrap();
+            assert_eq!(read.len(), 3);
+            assert!(matches!(read[2], McapRecord::Message(ref m) if m.log_time == 3));
+        }
+    }
+
+    #[test]
+    fn rejects_lying_chunk_sizes() {
+        let records = records();
+        let zstd = zstd::bulk::compress(&records, 3).unwrap();
+        let size = records.len() as u64;
+        assert!(read_all(&chunked("zstd", &zstd, size - 1)).is_err());
+        assert!(read_all(&chunked("zstd", &zstd, size + 1)).is_err());
+        // Would allocate the declared size up front
+        assert!(read_all(&chunked("zstd", &zstd, u64::MAX)).is_err());
+        assert!(read_all(&chunked("lz4", &zstd, MAX_CHUNK)).is_err());
+    }
+
+    #[test]
+    fn stops_at_records_longer_than_the_file() {
+        let mut file = MAGIC.to_vec();
+        write_record(&mut file, OP_MESSAGE, &[0; 22]).unwrap();
+        file.push(OP_MESSAGE);
+        file.extend_from_slice(&u64::MAX.to_le_bytes());
+        file.extend_from_slice(&[0; 64]);
+        let read = read_all(&file).unwrap();
+        assert_eq!(read.len(), 1);
+    }
+}
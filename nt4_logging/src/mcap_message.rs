@@ -0,0 +1,453 @@
+//! Decoding MCAP channels into store ops, and the JSON message layout
+//! `export_mcap` writes. JSON, CBOR, protobuf and ROS messages all decode to
+//! an `rmpv::Value` tree first, then flatten into one topic per field.
+
+use crate::batch::{Sample, StoreOp};
+use crate::mcap::{McapChannel, McapRecord, McapSchema};
+use crate::protobuf::Descriptors;
+use crate::ros_msg::RosSchema;
+use crate::schema::{Schema, array_sample, decode_struct_entry, scalar_sample};
+use godot::prelude::*;
+use rmpv::Value;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+/// Channel metadata written by `export_mcap`: the WPILib type of the topic
+/// and its entry metadata, so an exported session loads back unchanged.
+pub const TYPE_KEY: &str = "wpilib_type";
+pub const METADATA_KEY: &str = "wpilib_metadata";
+
+enum Decoding {
+    /// Written by `export_mcap`: `{"value": ...}` holding one sample.
+    Wpilib(String),
+    Json,
+    Cbor,
+    Protobuf {
+        schema_id: u16,
+        message: String,
+    },
+    Ros {
+        schema_id: u16,
+        cdr: bool,
+    },
+    /// Unknown encodings are kept as raw bytes.
+    Raw,
+}
+
+struct Channel {
+    topic: String,
+    decoding: Decoding,
+}
+
+/// Keeps the schemas and channels an MCAP file declares before using them.
+#[derive(Default)]
+pub struct McapDecoder {
+    schemas: HashMap<u16, McapSchema>,
+    channels: HashMap<u16, Channel>,
+    protobuf: HashMap<u16, Descriptors>,
+    ros: HashMap<u16, RosSchema>,
+    /// WPILib struct schemas from round-tripped `/.schema/` topics.
+    struct_schemas: HashMap<String, Schema>,
+    /// Messages that couldn't be decoded and were skipped.
+    pub failed: usize,
+}
+
+impl McapDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decode(&mut self, record: McapRecord, ops: &mut Vec<StoreOp>) {
+        match record {
+            McapRecord::Schema(schema) => {
+                self.schemas.insert(schema.id, schema);
+            }
+            McapRecord::Channel(channel) => {
+                let topic = format!("/{}", channel.topic.trim_start_matches('/'));
+                let decoding = self.decoding(&channel);
+                match &decoding {
+                    Decoding::Wpilib(type_str) => {
+                        ops.push(StoreOp::set_type(topic.clone(), type_str.clone()));
+                        if let Some(metadata) = channel.metadata.get(METADATA_KEY) {
+                            ops.push(StoreOp::set_metadata(topic.clone(), metadata.clone()));
+                        }
+                    }
+                    Decoding::Raw => {
+                        let type_str = self
+                            .schemas
+                            .get(&channel.schema_id)
+                            .map_or(channel.message_encoding.clone(), |s| s.name.clone());
+                        ops.push(StoreOp::set_type(topic.clone(), type_str));
+                    }
+                    _ => {}
+                }
+                self.channels
+                    .insert(channel.id, Channel { topic, decoding });
+            }
+            McapRecord::Message(message) => {
+                let Some(channel) = self.channels.get(&message.channel_id) else {
+                    return;
+                };
+                let timestamp = message.log_time / 1000;
+                let data = message.data.as_slice();
+                let value = match &channel.decoding {
+                    Decoding::Wpilib(type_str) => {
+                        let sample = serde_json::from_slice::<serde_json::Value>(data)
+                            .ok()
+                            .and_then(|json| wpilib_sample(type_str, json.get("value")?));
+                        match sample {
+                            Some(sample) => {
+                                push_wpilib(
+                                    &channel.topic,
+                                    type_str,
+                                    sample,
+                                    timestamp,
+                                    &mut self.struct_schemas,
+                                    ops,
+                                );
+                            }
+                            None => self.failed += 1,
+                        }
+                        return;
+                    }
+                    Decoding::Json => serde_json::from_slice(data)
+                        .map(json_value)
+                        .map_err(|e| e.to_string()),
+                    Decoding::Cbor => ciborium::from_reader(data)
+                        .map(cbor_value)
+                        .map_err(|e| e.to_string()),
+                    Decoding::Protobuf { schema_id, message } => self.protobuf[schema_id]
+                        .decode(message, data)
+                        .map_err(|e| e.to_string()),
+                    Decoding::Ros { schema_id, cdr } => self.ros[schema_id]
+                        .decode(data, *cdr)
+                        .map_err(|e| e.to_string()),
+                    Decoding::Raw => {
+                        let sample = Sample::Raw(message.data);
+                        ops.push(StoreOp::sample(channel.topic.clone(), timestamp, sample));
+                        return;
+                    }
+                };
+                match value {
+                    Ok(value) => flatten_value(&channel.topic, value, timestamp, ops),
+                    Err(_) => self.failed += 1,
+                }
+            }
+        }
+    }
+
+    /// Picks how a channel's messages decode, parsing its schema the first
+    /// time a channel uses it.
+    fn decoding(&mut self, channel: &McapChannel) -> Decoding {
+        // Cloned so the parsed forms can be cached while it's in use
+        let schema = self.schemas.get(&channel.schema_id).cloned();
+        match (channel.message_encoding.as_str(), schema) {
+            ("json", _) => match channel.metadata.get(TYPE_KEY) {
+                Some(type_str) => Decoding::Wpilib(type_str.clone()),
+                None => Decoding::Json,
+            },
+            ("cbor", _) => Decoding::Cbor,
+            ("protobuf", Some(schema)) if schema.encoding == "protobuf" => {
+                if let Entry::Vacant(entry) = self.protobuf.entry(schema.id) {
+                    match Descriptors::parse(&schema.data) {
+                        Ok(descriptors) => {
+                            entry.insert(descriptors);
+                        }
+                        Err(e) => {
+                            godot_print!("mcap: Bad protobuf schema {}: {}", schema.name, e);
+                            return Decoding::Raw;
+                        }
+                    }
+                }
+                if !self.protobuf[&schema.id].contains(&schema.name) {
+                    godot_print!("mcap: Schema doesn't describe {}", schema.name);
+                    return Decoding::Raw;
+                }
+                Decoding::Protobuf {
+                    schema_id: schema.id,
+                    message: schema.name,
+                }
+            }
+            ("ros1", Some(schema)) | ("cdr", Some(schema))
+                if matches!(schema.encoding.as_str(), "ros1msg" | "ros2msg") =>
+            {
+                self.ros.entry(schema.id).or_insert_with(|| {
+                    RosSchema::parse(&schema.name, &String::from_utf8_lossy(&schema.data))
+                });
+                Decoding::Ros {
+                    schema_id: schema.id,
+                    cdr: channel.message_encoding == "cdr",
+                }
+            }
+            (encoding, _) => {
+                godot_print!(
+                    "mcap: Keeping {} ({}) as raw bytes",
+                    channel.topic,
+                    encoding
+                );
+                Decoding::Raw
+            }
+        }
+    }
+}
+
+/// Writes a decoded message as samples under `topic`: maps become child
+/// topics, arrays of maps become one array per field (like struct arrays),
+/// and scalars and scalar arrays become samples.
+pub fn flatten_value(topic: &str, value: Value, timestamp: u64, ops: &mut Vec<StoreOp>) {
+    let sample = match value {
+        Value::Map(entries) => {
+            for (key, value) in entries {
+                let key = match key {
+                    Value::String(s) => s.into_str().unwrap_or_default(),
+                    other => other.to_string(),
+                };
+                flatten_value(&format!("{}/{}", topic, key), value, timestamp, ops);
+            }
+            return;
+        }
+        Value::Array(items) if items.first().is_some_and(Value::is_map) => {
+            let mut fields: Vec<(Value, Vec<Value>)> = Vec::new();
+            for item in items {
+                let Value::Map(entries) = item else {
+                    continue;
+                };
+                for (key, value) in entries {
+                    match fields.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, values)) => values.push(value),
+                        None => fields.push((key, vec![value])),
+                    }
+                }
+            }
+            let entries = fields
+                .into_iter()
+                .map(|(key, values)| (key, Value::Array(values)))
+                .collect();
+            flatten_value(topic, Value::Map(entries), timestamp, ops);
+            return;
+        }
+        // An empty list still has to replace the previous one
+        Value::Array(items) if items.is_empty() => Some(Sample::DoubleArray(Vec::new())),
+        Value::Array(items) => array_sample(&items),
+        Value::String(s) => Some(Sample::String(s.into_str().unwrap_or_default())),
+        Value::Binary(bytes) => Some(Sample::Raw(bytes)),
+        other => scalar_sample(other),
+    };
+    if let Some(sample) = sample {
+        ops.push(StoreOp::sample(topic.to_string(), timestamp, sample));
+    }
+}
+
+fn json_value(json: serde_json::Value) -> Value {
+    match json {
+        serde_json::Value::Null => Value::Nil,
+        serde_json::Value::Bool(b) => Value::Boolean(b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => Value::from(i),
+            (_, Some(u)) => Value::from(u),
+            _ => Value::F64(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => Value::from(s),
+        serde_json::Value::Array(items) => {
+            Value::Array(items.into_iter().map(json_value).collect())
+        }
+        serde_json::Value::Object(entries) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (Value::from(k), json_value(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn cbor_value(cbor: ciborium::Value) -> Value {
+    match cbor {
+        ciborium::Value::Integer(i) => {
+            let i = i128::from(i);
+            i64::try_from(i).map_or(Value::F64(i as f64), Value::from)
+        }
+        ciborium::Value::Bytes(bytes) => Value::Binary(bytes),
+        ciborium::Value::Float(f) => Value::F64(f),
+        ciborium::Value::Text(s) => Value::from(s),
+        ciborium::Value::Bool(b) => Value::Boolean(b),
+        ciborium::Value::Tag(_, value) => cbor_value(*value),
+        ciborium::Value::Array(items) => Value::Array(items.into_iter().map(cbor_value).collect()),
+        ciborium::Value::Map(entries) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (cbor_value(k), cbor_value(v)))
+                .collect(),
+        ),
+        _ => Value::Nil,
+    }
+}
+
+/// Rebuilds a sample from the `value` of an exported message. Byte arrays come
+/// back as raw payloads, so structs and schemas survive the trip.
+fn wpilib_sample(type_str: &str, value: &serde_json::Value) -> Option<Sample> {
+    let sample = match value {
+        serde_json::Value::Bool(b) => Sample::Boolean(*b),
+        serde_json::Value::Number(n) => Sample::Double(n.as_f64()?),
+        serde_json::Value::String(s) => Sample::String(s.clone()),
+        serde_json::Value::Array(items) => match type_str {
+            "boolean[]" => Sample::BooleanArray(items.iter().filter_map(|v| v.as_bool()).collect()),
+            "string[]" => Sample::StringArray(
+                items
+                    .iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect(),
+            ),
+            "double[]" | "float[]" | "int64[]" => {
+                Sample::DoubleArray(items.iter().filter_map(|v| v.as_f64()).collect())
+            }
+            _ => Sample::Raw(
+                items
+                    .iter()
+                    .filter_map(|v| v.as_u64().map(|b| b as u8))
+                    .collect(),
+            ),
+        },
+        _ => return None,
+    };
+    Some(sample)
+}
+
+/// Stores a round-tripped sample, registering schemas and flattening structs
+/// exactly like a .wpilog entry of the same type.
+fn push_wpilib(
+    topic: &str,
+    type_str: &str,
+    sample: Sample,
+    timestamp: u64,
+    schemas: &mut HashMap<String, Schema>,
+    ops: &mut Vec<StoreOp>,
+) {
+    match &sample {
+        Sample::Raw(bytes) => decode_struct_entry(topic, type_str, bytes, timestamp, schemas, ops),
+        Sample::String(s) => {
+            decode_struct_entry(topic, type_str, s.as_bytes(), timestamp, schemas, ops)
+        }
+        _ => {}
+    }
+    ops.push(StoreOp::sample(topic.to_string(), timestamp, sample));
+}
+
+/// The `value` of an exported message for one sample. Non-finite doubles have
+/// no JSON form and become `null`.
+pub fn sample_json(sample: &Sample) -> serde_json::Value {
+    use serde_json::Value as Json;
+    let number = |v: f64| serde_json::Number::from_f64(v).map_or(Json::Null, Json::Number);
+    match sample {
+        Sample::Double(v) => number(*v),
+        Sample::Boolean(v) => Json::Bool(*v),
+        Sample::String(v) => Json::String(v.clone()),
+        Sample::DoubleArray(vals) => Json::Array(vals.iter().map(|&v| number(v)).collect()),
+        Sample::BooleanArray(vals) => Json::Array(vals.iter().map(|&v| Json::Bool(v)).collect()),
+        Sample::StringArray(vals) => {
+            Json::Array(vals.iter().map(|v| Json::String(v.clone())).collect())
+        }
+        Sample::Raw(bytes) => Json::Array(bytes.iter().map(|&b| Json::from(b)).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_store::LogStore;
+    use crate::mcap::McapReader;
+
+    /// Decodes a fixture from `tests/fixtures` (see `make_mcap.py` there).
+    fn load(data: &[u8]) -> LogStore {
+        let mut reader = McapReader::new(data, data.len() as u64).unwrap();
+        let mut decoder = McapDecoder::new();
+        let mut ops = Vec::new();
+        while let Some(record) = reader.next_record().unwrap() {
+            decoder.decode(record, &mut ops);
+        }
+        assert_eq!(decoder.failed, 0);
+        let mut store = LogStore::new();
+        store.apply_all(ops);
+        store
+    }
+
+    const T0: u64 = 1_000_000;
+    const T1: u64 = 1_020_000;
+    const T2: u64 = 1_040_000;
+
+    #[test]
+    fn decodes_protobuf() {
+        let store = load(include_bytes!("../tests/fixtures/protobuf.mcap"));
+        assert_eq!(store.get_double("/robot/pose/position/x", T0, -1.0), 1.0);
+        assert_eq!(store.get_double("/robot/pose/position/x", T2, -1.0), 3.0);
+        assert_eq!(store.get_double("/robot/pose/position/y", T1, -1.0), 2.0);
+        assert_eq!(store.get_double("/robot/pose/orientation/w", T1, -1.0), 1.0);
+        assert_eq!(
+            store.get_string("/robot/pose/frame_id", T0).unwrap(),
+            "field"
+        );
+        assert_eq!(
+            store
+                .get_double_array("/robot/pose/covariance", T2)
+                .unwrap()[..],
+            [0.2, 0.2, 0.3]
+        );
+    }
+
+    #[test]
+    fn decodes_ros1() {
+        let store = load(include_bytes!("../tests/fixtures/ros1.mcap"));
+        check_module_state(&store);
+    }
+
+    #[test]
+    fn decodes_ros2_cdr() {
+        let store = load(include_bytes!("../tests/fixtures/ros2.mcap"));
+        check_module_state(&store);
+    }
+
+    fn check_module_state(store: &LogStore) {
+        let topic = "/swerve/front_left";
+        assert_eq!(
+            store.get_string(&format!("{}/name", topic), T0).unwrap(),
+            "front_left"
+        );
+        assert_eq!(store.get_double(&format!("{}/speed", topic), T1, -1.0), 2.0);
+        assert_eq!(
+            store
+                .get_double_array(&format!("{}/currents", topic), T2)
+                .unwrap()[..],
+            [12.0, 13.0]
+        );
+        assert_eq!(
+            store.get_double(&format!("{}/offset/x", topic), T2, -1.0),
+            0.3
+        );
+        assert!(!store.get_boolean(&format!("{}/enabled", topic), T0, true));
+        assert!(store.get_boolean(&format!("{}/enabled", topic), T1, false));
+    }
+
+    #[test]
+    fn decodes_json() {
+        let store = load(include_bytes!("../tests/fixtures/json.mcap"));
+        assert_eq!(store.get_double("/drive/speed", T2, -1.0), 3.0);
+        assert!(store.get_boolean("/drive/enabled", T1, false));
+        assert_eq!(store.get_string("/drive/mode", T0).unwrap(), "auto");
+        assert_eq!(
+            store.get_double_array("/drive/modules/angle", T1).unwrap()[..],
+            [0.1, -0.1]
+        );
+    }
+
+    #[test]
+    fn decodes_cbor() {
+        let store = load(include_bytes!("../tests/fixtures/cbor.mcap"));
+        assert_eq!(store.get_double("/arm/angle", T1, -1.0), 0.25);
+        assert_eq!(store.get_double("/arm/count", T0, 0.0), -3.0);
+        assert!(store.get_boolean("/arm/at_goal", T2, false));
+        assert_eq!(
+            store.get_double_array("/arm/joints", T2).unwrap()[..],
+            [1.0, 4.0]
+        );
+        assert_eq!(store.get_raw("/arm/tag", T0).unwrap()[..], [1, 2]);
+    }
+}
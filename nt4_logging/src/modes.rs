@@ -0,0 +1,231 @@
+//! Robot mode intervals (disabled, auto, teleop, test, e-stop) swept from the
+//! DriverStation boolean topics. The sweep is kept between calls and only new
+//! samples are folded in, so live sessions don't redo the whole log.
+
+use crate::log_store::{LogStore, TopicData};
+
+/// Candidate topics per input, AdvantageKit's names first.
+const ENABLED_TOPICS: &[&str] = &[
+    "/AdvantageKit/DriverStation/Enabled",
+    "/DriverStation/Enabled",
+];
+const AUTO_TOPICS: &[&str] = &[
+    "/AdvantageKit/DriverStation/Autonomous",
+    "/DriverStation/Autonomous",
+];
+const TEST_TOPICS: &[&str] = &["/AdvantageKit/DriverStation/Test", "/DriverStation/Test"];
+const ESTOP_TOPICS: &[&str] = &[
+    "/AdvantageKit/DriverStation/EmergencyStop",
+    "/DriverStation/EStop",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobotMode {
+    Disabled,
+    Auto,
+    Teleop,
+    Test,
+    EStop,
+}
+
+impl RobotMode {
+    /// E-stop wins over everything, then disabled, auto, test and teleop.
+    fn from_flags(enabled: bool, auto: bool, test: bool, estop: bool) -> Self {
+        if estop {
+            RobotMode::EStop
+        } else if !enabled {
+            RobotMode::Disabled
+        } else if auto {
+            RobotMode::Auto
+        } else if test {
+            RobotMode::Test
+        } else {
+            RobotMode::Teleop
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RobotMode::Disabled => "disabled",
+            RobotMode::Auto => "auto",
+            RobotMode::Teleop => "teleop",
+            RobotMode::Test => "test",
+            RobotMode::EStop => "estop",
+        }
+    }
+}
+
+/// `mode` held from `start` to `end` (microseconds).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeInterval {
+    pub start: u64,
+    pub end: u64,
+    pub mode: RobotMode,
+}
+
+/// Progress through one input topic.
+#[derive(Debug, Clone, Default)]
+struct Series {
+    topic: Option<&'static str>,
+    /// Time of the last sample swept and how many swept samples share it, to
+    /// find the new ones in a window read from there.
+    last: Option<u64>,
+    at_last: usize,
+    /// Value in effect at the sweep position; false before the first sample.
+    current: bool,
+}
+
+/// Incrementally maintained mode intervals for one store.
+#[derive(Debug, Clone, Default)]
+pub struct ModeTracker {
+    generation: u32,
+    revision: u64,
+    /// enabled, auto, test, e-stop
+    series: [Series; 4],
+    /// Time up to which `intervals` are final.
+    swept: Option<u64>,
+    intervals: Vec<ModeInterval>,
+}
+
+impl ModeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mode intervals from the first mode sample to the end of the log,
+    /// adjacent intervals of the same mode merged. Empty without an enabled
+    /// topic.
+    pub fn intervals(&mut self, store: &LogStore) -> Vec<ModeInterval> {
+        self.update(store);
+
+        let mut intervals = self.intervals.clone();
+        if let Some(swept) = self.swept {
+            push_interval(
+                &mut intervals,
+                swept,
+                store.get_last_timestamp(),
+                self.mode(),
+            );
+        }
+        intervals
+    }
+
+    fn mode(&self) -> RobotMode {
+        let [enabled, auto, test, estop] = &self.series;
+        RobotMode::from_flags(enabled.current, auto.current, test.current, estop.current)
+    }
+
+    /// Folds in samples added since the last call. Anything other than new
+    /// samples at the end (another log, late samples, a topic that appeared
+    /// under a preferred name) starts the sweep over.
+    fn update(&mut self, store: &LogStore) {
+        if store.generation == self.generation && store.revision == self.revision {
+            return;
+        }
+        if store.generation != self.generation {
+            *self = Self::new();
+            self.generation = store.generation;
+        }
+        let seen = self.revision;
+        self.revision = store.revision;
+
+        let candidates = [ENABLED_TOPICS, AUTO_TOPICS, TEST_TOPICS, ESTOP_TOPICS];
+        let topics = candidates.map(|names| {
+            names.iter().copied().find(|name| {
+                store
+                    .topic_shape(name)
+                    .is_some_and(|data| matches!(data, TopicData::Boolean(..)))
+            })
+        });
+        let rewritten = topics.iter().flatten().any(|topic| {
+            store
+                .topic_rewrites
+                .get(*topic)
+                .is_some_and(|&rev| rev > seen)
+        });
+        if rewritten || topics.iter().zip(&self.series).any(|(t, s)| *t != s.topic) {
+            self.reset(topics);
+        }
+        if topics[0].is_none() {
+            return;
+        }
+
+        let mut samples = self.new_samples(store, seen);
+        if let Some(swept) = self.swept
+            && samples.first().is_some_and(|&(t, ..)| t < swept)
+        {
+            self.reset(topics);
+            samples = self.new_samples(store, seen);
+        }
+
+        // Values take effect at their timestamp
+        for (t, i, value) in samples {
+            if let Some(swept) = self.swept
+                && t > swept
+            {
+                let mode = self.mode();
+                push_interval(&mut self.intervals, swept, t, mode);
+            }
+            self.swept = Some(t);
+            let series = &mut self.series[i];
+            series.current = value;
+            if series.last == Some(t) {
+                series.at_last += 1;
+            } else {
+                series.last = Some(t);
+                series.at_last = 1;
+            }
+        }
+    }
+
+    /// Samples of every input not swept yet, in time order. Only inputs that
+    /// changed after revision `seen` are read, from their last swept sample on.
+    fn new_samples(&self, store: &LogStore, seen: u64) -> Vec<(u64, usize, bool)> {
+        let mut samples = Vec::new();
+        for (i, series) in self.series.iter().enumerate() {
+            let Some(topic) = series.topic else {
+                continue;
+            };
+            let changed = store
+                .topic_revisions
+                .get(topic)
+                .is_some_and(|&rev| rev > seen);
+            if series.last.is_some() && !changed {
+                continue;
+            }
+            let Some(data) = store.topic_range(topic, series.last.unwrap_or(0), u64::MAX) else {
+                continue;
+            };
+            let TopicData::Boolean(ts, vals) = &*data else {
+                continue;
+            };
+            let start = series
+                .last
+                .map_or(0, |last| ts.partition_point(|&t| t < last) + series.at_last);
+            samples.extend((start..ts.len()).map(|idx| (ts[idx], i, vals[idx])));
+        }
+        samples.sort_by_key(|&(t, i, _)| (t, i));
+        samples
+    }
+
+    fn reset(&mut self, topics: [Option<&'static str>; 4]) {
+        self.swept = None;
+        self.intervals.clear();
+        for (series, topic) in self.series.iter_mut().zip(topics) {
+            *series = Series {
+                topic,
+                ..Series::default()
+            };
+        }
+    }
+}
+
+fn push_interval(intervals: &mut Vec<ModeInterval>, start: u64, end: u64, mode: RobotMode) {
+    if start >= end {
+        return;
+    }
+    match intervals.last_mut() {
+        Some(last) if last.mode == mode && last.end == start => last.end = end,
+        _ => intervals.push(ModeInterval { start, end, mode }),
+    }
+}
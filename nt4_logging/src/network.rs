@@ -1,6 +1,8 @@
+use crate::batch::{BatchWriter, Sample, StoreOp};
 use crate::log_store::LogStore;
-use crate::schema::{Schema, decode_struct};
+use crate::schema::{Schema, array_sample, flatten_struct, scalar_sample};
 use godot::prelude::*;
+use nt_client::data::r#type::DataType;
 use nt_client::{Client, NTAddr, NewClientOptions, subscribe::ReceivedMessage};
 use parking_lot::RwLock;
 use std::collections::HashMap;
@@ -80,6 +82,7 @@ impl NetworkManager {
                             log_to_file("nm: Subscribed! Waiting for messages...");
 
                             let mut schemas: HashMap<String, Schema> = HashMap::new();
+                            let writer = BatchWriter::spawn(store_clone, generation);
 
                             loop {
                                 match sub.recv().await {
@@ -93,12 +96,6 @@ impl NetworkManager {
                                             timestamp = 1;
                                         }
 
-                                        let mut store = store_clone.write();
-                                        if !store.check_generation(generation) {
-                                            godot_print!("nm: Generation mismatch ({} vs {}). Stopping thread.", store.generation, generation);
-                                            break;
-                                        }
-
                                         // 1. Handle Schema Definitions
                                         if topic_name.starts_with("/.schema/") {
                                             if let rmpv::Value::String(s) = &value {
@@ -118,235 +115,23 @@ impl NetworkManager {
                                             }
                                         }
 
-                                        // 2. Handle Data
-                                        match topic_ref.r#type() {
-                                            nt_client::data::r#type::DataType::Struct(
-                                                struct_name,
-                                            ) => {
-                                                if let rmpv::Value::Binary(data) = &value {
-                                                    store.update_raw(
-                                                        topic_name.to_string(),
-                                                        timestamp,
-                                                        data.clone(),
-                                                    );
-                                                    store.set_type(
-                                                        topic_name.to_string(),
-                                                        format!("struct:{}", struct_name),
-                                                    );
-
-                                                    let schema_lookup =
-                                                        format!("struct:{}", struct_name);
-                                                    if let Some(schema) =
-                                                        schemas.get(&schema_lookup)
-                                                    {
-                                                        let decoded =
-                                                            decode_struct(schema, data, &schemas);
-                                                        for (field_path, val) in decoded {
-                                                            let full_path = format!(
-                                                                "{}/{}",
-                                                                topic_name, field_path
-                                                            );
-                                                            match val {
-                                                                rmpv::Value::F64(f) => store
-                                                                    .update_double(
-                                                                        full_path, timestamp, f,
-                                                                    ),
-                                                                rmpv::Value::F32(f) => store
-                                                                    .update_double(
-                                                                        full_path, timestamp,
-                                                                        f as f64,
-                                                                    ),
-                                                                rmpv::Value::Boolean(b) => store
-                                                                    .update_boolean(
-                                                                        full_path, timestamp, b,
-                                                                    ),
-                                                                rmpv::Value::Integer(i) => {
-                                                                    if let Some(f) = i.as_f64() {
-                                                                        store.update_double(
-                                                                            full_path, timestamp, f,
-                                                                        );
-                                                                    }
-                                                                }
-                                                                _ => {}
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                            nt_client::data::r#type::DataType::StructArray(
-                                                struct_name,
-                                            ) => {
-                                                if let rmpv::Value::Binary(data) = &value {
-                                                    store.update_raw(
-                                                        topic_name.to_string(),
-                                                        timestamp,
-                                                        data.clone(),
-                                                    );
-                                                    store.set_type(
-                                                        topic_name.to_string(),
-                                                        format!("struct:{}[]", struct_name),
-                                                    );
-
-                                                    let schema_lookup =
-                                                        format!("struct:{}", struct_name);
-                                                    if let Some(schema) =
-                                                        schemas.get(&schema_lookup)
-                                                    {
-                                                        let mut cursor = 0;
-                                                        let mut soa: HashMap<
-                                                            String,
-                                                            Vec<rmpv::Value>,
-                                                        > = HashMap::new();
-                                                        while cursor < data.len() {
-                                                            let size =
-                                                                crate::schema::calculate_size(
-                                                                    schema, &schemas,
-                                                                );
-                                                            if size == 0
-                                                                || cursor + size > data.len()
-                                                            {
-                                                                break;
-                                                            }
-                                                            let decoded = decode_struct(
-                                                                schema,
-                                                                &data[cursor..cursor + size],
-                                                                &schemas,
-                                                            );
-                                                            for (field, val) in decoded {
-                                                                soa.entry(field)
-                                                                    .or_default()
-                                                                    .push(val);
-                                                            }
-                                                            cursor += size;
-                                                        }
-                                                        for (field_path, values) in soa {
-                                                            let full_path = format!(
-                                                                "{}/{}",
-                                                                topic_name, field_path
-                                                            );
-                                                            match values.first() {
-                                                                Some(rmpv::Value::F64(_))
-                                                                | Some(rmpv::Value::F32(_)) => {
-                                                                    let floats: Vec<f64> = values
-                                                                        .iter()
-                                                                        .filter_map(|v| v.as_f64())
-                                                                        .collect();
-                                                                    store.update_double_array(
-                                                                        full_path, timestamp,
-                                                                        floats,
-                                                                    );
-                                                                }
-                                                                Some(rmpv::Value::Boolean(_)) => {
-                                                                    let bools: Vec<bool> = values
-                                                                        .iter()
-                                                                        .filter_map(|v| v.as_bool())
-                                                                        .collect();
-                                                                    store.update_boolean_array(
-                                                                        full_path, timestamp, bools,
-                                                                    );
-                                                                }
-                                                                _ => {}
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                            _ => {
-                                                match value {
-                                                    rmpv::Value::Boolean(b) => store
-                                                        .update_boolean(
-                                                            topic_name.to_string(),
-                                                            timestamp,
-                                                            b,
-                                                        ),
-                                                    rmpv::Value::F32(f) => store.update_double(
-                                                        topic_name.to_string(),
-                                                        timestamp,
-                                                        f as f64,
-                                                    ),
-                                                    rmpv::Value::F64(f) => store.update_double(
-                                                        topic_name.to_string(),
-                                                        timestamp,
-                                                        f,
-                                                    ),
-                                                    rmpv::Value::Integer(i) => {
-                                                        if let Some(f) = i.as_f64() {
-                                                            store.update_double(
-                                                                topic_name.to_string(),
-                                                                timestamp,
-                                                                f,
-                                                            );
-                                                        }
-                                                    }
-                                                    rmpv::Value::String(s) => {
-                                                        if let Some(str_val) = s.as_str() {
-                                                            store.update_string(
-                                                                topic_name.to_string(),
-                                                                timestamp,
-                                                                str_val.to_string(),
-                                                            );
-                                                        }
-                                                    }
-                                                    rmpv::Value::Binary(vec) => store.update_raw(
-                                                        topic_name.to_string(),
-                                                        timestamp,
-                                                        vec,
-                                                    ),
-                                                    rmpv::Value::Array(vec) => {
-                                                        if vec.is_empty() {
-                                                            // Can't infer type
-                                                        } else {
-                                                            match &vec[0] {
-                                                                rmpv::Value::Boolean(_) => {
-                                                                    let bools: Vec<bool> = vec
-                                                                        .iter()
-                                                                        .filter_map(|v| v.as_bool())
-                                                                        .collect();
-                                                                    store.update_boolean_array(
-                                                                        topic_name.to_string(),
-                                                                        timestamp,
-                                                                        bools,
-                                                                    );
-                                                                }
-                                                                rmpv::Value::F64(_)
-                                                                | rmpv::Value::F32(_) => {
-                                                                    let floats: Vec<f64> = vec
-                                                                        .iter()
-                                                                        .filter_map(|v| v.as_f64())
-                                                                        .collect();
-                                                                    store.update_double_array(
-                                                                        topic_name.to_string(),
-                                                                        timestamp,
-                                                                        floats,
-                                                                    );
-                                                                }
-                                                                rmpv::Value::String(_) => {
-                                                                    let strings: Vec<String> = vec
-                                                                        .iter()
-                                                                        .filter_map(|v| {
-                                                                            v.as_str().map(|s| {
-                                                                                s.to_string()
-                                                                            })
-                                                                        })
-                                                                        .collect();
-                                                                    store.update_string_array(
-                                                                        topic_name.to_string(),
-                                                                        timestamp,
-                                                                        strings,
-                                                                    );
-                                                                }
-                                                                _ => {}
-                                                            }
-                                                        }
-                                                    }
-                                                    _ => {
-                                                        log_to_file(&format!(
-                                                            "nm: Unhandled Value for {}: {:?}",
-                                                            topic_name, value
-                                                        ));
-                                                    } // Ignore Maps/Ext/Nil
-                                                }
-                                            }
+                                        // 2. Decode without holding the store lock; the
+                                        // committer applies it with the rest of the batch
+                                        let mut ops = Vec::new();
+                                        decode_update(
+                                            topic_name,
+                                            &topic_ref.r#type(),
+                                            value,
+                                            timestamp,
+                                            &schemas,
+                                            &mut ops,
+                                        );
+                                        if !writer.send(ops) {
+                                            godot_print!(
+                                                "nm: Generation {} is stale. Stopping thread.",
+                                                generation
+                                            );
+                                            break;
                                         }
                                     }
                                     Ok(ReceivedMessage::Announced(announce)) => {
@@ -393,6 +178,101 @@ impl NetworkManager {
     }
 }
 
+/// Turns one NT4 value update into store ops. Struct payloads are kept as raw
+/// bytes and also flattened into one child topic per field.
+fn decode_update(
+    topic_name: &str,
+    data_type: &DataType,
+    value: rmpv::Value,
+    timestamp: u64,
+    schemas: &HashMap<String, Schema>,
+    ops: &mut Vec<StoreOp>,
+) {
+    match data_type {
+        DataType::Struct(struct_name) => {
+            if let rmpv::Value::Binary(data) = value {
+                ops.push(StoreOp::set_type(
+                    topic_name.to_string(),
+                    format!("struct:{}", struct_name),
+                ));
+
+                flatten_struct(
+                    topic_name,
+                    struct_name,
+                    false,
+                    &data,
+                    timestamp,
+                    schemas,
+                    ops,
+                );
+
+                ops.push(StoreOp::sample(
+                    topic_name.to_string(),
+                    timestamp,
+                    Sample::Raw(data),
+                ));
+            }
+        }
+        DataType::StructArray(struct_name) => {
+            if let rmpv::Value::Binary(data) = value {
+                ops.push(StoreOp::set_type(
+                    topic_name.to_string(),
+                    format!("struct:{}[]", struct_name),
+                ));
+
+                flatten_struct(
+                    topic_name,
+                    struct_name,
+                    true,
+                    &data,
+                    timestamp,
+                    schemas,
+                    ops,
+                );
+
+                ops.push(StoreOp::sample(
+                    topic_name.to_string(),
+                    timestamp,
+                    Sample::Raw(data),
+                ));
+            }
+        }
+        _ => match value {
+            rmpv::Value::String(s) => {
+                if let Some(str_val) = s.into_str() {
+                    ops.push(StoreOp::sample(
+                        topic_name.to_string(),
+                        timestamp,
+                        Sample::String(str_val),
+                    ));
+                }
+            }
+            rmpv::Value::Binary(vec) => {
+                ops.push(StoreOp::sample(
+                    topic_name.to_string(),
+                    timestamp,
+                    Sample::Raw(vec),
+                ));
+            }
+            rmpv::Value::Array(vec) => {
+                // Can't infer the type of an empty array
+                if let Some(sample) = array_sample(&vec) {
+                    ops.push(StoreOp::sample(topic_name.to_string(), timestamp, sample));
+                }
+            }
+            other => match scalar_sample(other) {
+                Some(sample) => {
+                    ops.push(StoreOp::sample(topic_name.to_string(), timestamp, sample));
+                }
+                None => {
+                    // Ignore Maps/Ext/Nil
+                    log_to_file(&format!("nm: Unhandled Value for {}", topic_name));
+                }
+            },
+        },
+    }
+}
+
 fn log_to_file(msg: &str) {
     use std::io::Write;
     if let Ok(mut file) = std::fs::OpenOptions::new()
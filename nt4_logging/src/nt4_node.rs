@@ -1,10 +1,29 @@
-use crate::log_store::LogStore;
+use crate::batch::Sample;
+use crate::dsp::Window;
+use crate::export::{self, CsvLayout, ExportJob};
+use crate::heatmap::HeatmapGrid;
+use crate::log_loader::{Alignment, ImportOptions, LoadEvent, LogLoader};
+use crate::log_store::{LogStore, TopicData};
+use crate::match_info::{MatchInfo, TimeRange};
+use crate::modes::ModeTracker;
 use crate::network::NetworkManager;
+use crate::odometry::Region;
+use crate::pose::{PoseFormat, PoseSample, SpatialPose};
+use crate::pose_error::ERROR_TOPICS;
+use crate::rlog_client::RlogClient;
+use crate::search::{Edge, Match, Query};
+use crate::stats::{PERCENTILES, Statistics};
 use byteorder::{ByteOrder, LittleEndian};
+use godot::classes::ProjectSettings;
 use godot::prelude::*;
 use parking_lot::RwLock;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+/// Where decoded logs are cached for instant reopening.
+const SESSION_CACHE_DIR: &str = "user://session_cache";
+
 #[derive(GodotClass)]
 #[class(base=Node)]
 pub struct NT4 {
@@ -13,11 +32,27 @@ pub struct NT4 {
 
     store: Arc<RwLock<LogStore>>,
     network: Option<NetworkManager>, // Option to allow late initialization
+    loader: Option<LogLoader>,
+    rlog: Option<RlogClient>,
+    /// Exports still writing.
+    exports: Vec<ExportJob>,
 
     server_ip: String,
 
     // Debug/Replay controls
     cursor_time: u64, // 0 means "live"
+
+    // Change tracking for the per-frame signals
+    seen_generation: u32,
+    seen_revision: u64,
+    seen_topic_count: usize,
+    seen_cursor: u64,
+
+    modes: ModeTracker,
+
+    /// Result of the last search, for the store generation it ran on.
+    search_results: Vec<Match>,
+    search_generation: u32,
 }
 
 #[godot_api]
@@ -27,14 +62,65 @@ impl INode for NT4 {
             base,
             store: Arc::new(RwLock::new(LogStore::new())),
             network: None,
+            loader: None,
+            rlog: None,
+            exports: Vec::new(),
             server_ip: "127.0.0.1".to_string(),
             cursor_time: 0,
+            seen_generation: 0,
+            seen_revision: 0,
+            seen_topic_count: 0,
+            seen_cursor: 0,
+            modes: ModeTracker::new(),
+            search_results: Vec::new(),
+            search_generation: 0,
         }
     }
+
+    fn process(&mut self, _delta: f64) {
+        self.poll_loader();
+        self.poll_exports();
+        self.update_computed();
+        self.emit_changes();
+    }
 }
 
 #[godot_api]
 impl NT4 {
+    /// New topics since the previous frame, in arrival order.
+    #[signal]
+    fn topics_added(topics: PackedStringArray);
+
+    /// Topics whose value at the replay cursor may have changed since the
+    /// previous frame (new samples, or the cursor moved past one of theirs).
+    #[signal]
+    fn values_changed(topics: PackedStringArray);
+
+    /// Fraction of the log file read so far, 0.0 to 1.0.
+    #[signal]
+    fn log_load_progress(fraction: f32);
+
+    #[signal]
+    fn log_loaded();
+
+    #[signal]
+    fn log_load_failed(reason: GString);
+
+    /// An import was auto-aligned by this many microseconds.
+    #[signal]
+    fn log_import_aligned(offset_micros: i64);
+
+    /// A search ran or was cleared; see `get_search_results`.
+    #[signal]
+    fn search_results_changed();
+
+    /// An export finished writing `count` records (rows, for CSV) to `path`.
+    #[signal]
+    fn export_finished(path: GString, count: i64);
+
+    #[signal]
+    fn export_failed(path: GString, reason: GString);
+
     #[func]
     pub fn start_client(&mut self, server_ip: String) {
         godot_print!("NT4: start_client called with ip: {}", server_ip);
@@ -45,6 +131,8 @@ impl NT4 {
             store.clear();
         }
 
+        self.loader = None;
+        self.rlog = None;
         self.server_ip = server_ip.clone();
 
         // Get generation
@@ -58,14 +146,38 @@ impl NT4 {
         godot_print!("NT4: NetworkManager initialized (Gen: {}).", generation);
     }
 
+    /// Connects to an AdvantageKit robot's RLOG server (port 5800) instead of
+    /// NT4, e.g. for logs from robots that don't publish over NetworkTables.
+    #[func]
+    pub fn start_rlog_client(&mut self, server_ip: String) {
+        godot_print!("NT4: start_rlog_client called with ip: {}", server_ip);
+
+        // Stop old threads and clear data
+        {
+            let mut store = self.store.write();
+            store.clear();
+        }
+        self.network = None;
+        self.loader = None;
+        self.server_ip = server_ip.clone();
+
+        let generation = self.store.read().generation;
+        self.rlog = Some(RlogClient::new(self.store.clone(), server_ip, generation));
+    }
+
     #[func]
     pub fn disconnect(&mut self) {
         godot_print!("NT4: Disconnecting...");
         let mut store = self.store.write();
         store.clear(); // Bumps generation, stopping the network thread
         self.network = None; // Drop the manager (though thread lives until it checks gen)
+        self.loader = None;
+        self.rlog = None;
     }
 
+    /// Starts streaming a .wpilog into the store on a background thread.
+    /// Returns false if the file can't be opened; later failures arrive through
+    /// `log_load_failed`.
     #[func]
     pub fn load_log_file(&mut self, path: String) -> bool {
         godot_print!("NT4: Loading log file: {}", path);
@@ -75,77 +187,180 @@ impl NT4 {
             let mut store = self.store.write();
             store.clear();
         }
+        let generation = self.store.read().generation;
+        self.rlog = None;
 
-        use std::io::Read;
-        let mut content = Vec::new();
-        if let Err(e) = std::fs::File::open(&path).and_then(|mut f| f.read_to_end(&mut content)) {
-            godot_print!("Error reading file: {}", e);
-            return false;
-        }
-
-        // Parse Log using frclib-datalog
-        use frclib_core::value::FrcValue;
-        use frclib_datalog::DataLogReader;
-        use std::collections::HashMap;
-
-        let cursor = std::io::Cursor::new(content);
-        let reader = match DataLogReader::try_new(cursor, Default::default()) {
-            Ok(r) => r,
+        let cache_dir = ProjectSettings::singleton().globalize_path(SESSION_CACHE_DIR);
+        let cache_dir = Some(PathBuf::from(cache_dir.to_string()));
+        match LogLoader::spawn(&path, self.store.clone(), generation, cache_dir) {
+            Ok(loader) => {
+                self.loader = Some(loader);
+                true
+            }
             Err(e) => {
-                godot_print!("Error creating DataLogReader: {:?}", e);
-                return false;
+                godot_print!("Error reading file: {}", e);
+                self.loader = None;
+                false
             }
+        }
+    }
+
+    /// Adds a log (e.g. a Phoenix 6 log converted to .wpilog) to the current
+    /// session under `prefix` without clearing what is loaded. Timestamps are
+    /// shifted by `time_offset` microseconds, or, when both reference topics
+    /// are given, by the offset that best lines up `import_reference_topic`
+    /// (in the new log, before prefixing) with `reference_topic`.
+    #[func]
+    pub fn import_log_file(
+        &mut self,
+        path: String,
+        prefix: String,
+        time_offset: i64,
+        reference_topic: String,
+        import_reference_topic: String,
+    ) -> bool {
+        let align = if reference_topic.is_empty() || import_reference_topic.is_empty() {
+            None
+        } else {
+            Some(Alignment::Correlate {
+                reference: reference_topic,
+                imported: import_reference_topic,
+            })
         };
+        self.start_import(
+            path,
+            ImportOptions {
+                prefix,
+                offset: time_offset,
+                align,
+            },
+        )
+    }
 
-        let mut store = self.store.write();
+    /// Merges another log (a replayed simulation, the other robot in the
+    /// match, ...) into the session under `prefix`, shifted by `time_offset`
+    /// microseconds.
+    #[func]
+    pub fn add_log_file(&mut self, path: String, prefix: String, time_offset: i64) -> bool {
+        self.start_import(
+            path,
+            ImportOptions {
+                prefix,
+                offset: time_offset,
+                align: None,
+            },
+        )
+    }
 
-        let keys = reader.get_all_entry_keys();
-        for key in keys {
-            // Set type (use last known type)
-            let type_strs = reader.read_entry_type_str(key);
-            if let Some(ts) = type_strs.last() {
-                store.set_type(key.clone(), ts.value.clone());
-            }
+    /// Like [`Self::add_log_file`], but lines the log up with the session by
+    /// the robot enable edges both logs recorded. `log_import_aligned`
+    /// reports the offset that was used.
+    #[func]
+    pub fn add_log_file_auto_aligned(&mut self, path: String, prefix: String) -> bool {
+        self.start_import(
+            path,
+            ImportOptions {
+                prefix,
+                offset: 0,
+                align: Some(Alignment::EnabledEdges),
+            },
+        )
+    }
 
-            // Values
-            let values = reader.read_entry(key);
-            for v in values {
-                let timestamp = v.timestamp as u64;
-                match &v.value {
-                    FrcValue::Double(val) => store.update_double(key.clone(), timestamp, *val),
-                    FrcValue::Boolean(val) => store.update_boolean(key.clone(), timestamp, *val),
-                    FrcValue::String(val) => {
-                        store.update_string(key.clone(), timestamp, val.to_string())
-                    }
-                    FrcValue::DoubleArray(val) => {
-                        store.update_double_array(key.clone(), timestamp, val.to_vec())
-                    }
-                    FrcValue::BooleanArray(val) => {
-                        store.update_boolean_array(key.clone(), timestamp, val.to_vec())
-                    }
-                    FrcValue::StringArray(val) => {
-                        let vec: Vec<String> = val.iter().map(|s| s.to_string()).collect();
-                        store.update_string_array(key.clone(), timestamp, vec)
-                    }
-                    FrcValue::Raw(val) => store.update_raw(key.clone(), timestamp, val.to_vec()),
-                    _ => {
-                        // Attempt to cast other types if needed, or ignore
-                        // Int/Float/etc might exist in FrcValue
-                        if let FrcValue::Int(i) = v.value {
-                            // Assuming generic number as double
-                            store.update_double(key.clone(), timestamp, i as f64);
-                        } else if let FrcValue::Float(f) = v.value {
-                            store.update_double(key.clone(), timestamp, f as f64);
-                        }
-                    }
-                }
-            }
+    /// Stops the log load in progress. Whatever was read so far stays loaded.
+    #[func]
+    pub fn cancel_log_load(&mut self) {
+        if let Some(loader) = &self.loader {
+            loader.cancel();
         }
+    }
 
-        godot_print!("Log file loaded. Topics: {}", store.data.len());
+    #[func]
+    pub fn is_loading_log(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    /// Writes `topics` (every topic when empty) between `start` and `end`
+    /// (microseconds; `end` <= 0 means the end of the log) to a .wpilog on a
+    /// background thread. Returns whether the export started; it reports
+    /// through `export_finished` or `export_failed`.
+    #[func]
+    pub fn export_wpilog(
+        &mut self,
+        path: String,
+        topics: PackedStringArray,
+        start: i64,
+        end: i64,
+    ) -> bool {
+        let topics: Vec<String> = topics.as_slice().iter().map(|t| t.to_string()).collect();
+        let (start, end) = self.export_range(start, end);
+        let snapshot = export::snapshot(&self.store.read(), &topics, start, end);
+        self.exports
+            .push(ExportJob::spawn(path, snapshot, move |store, path| {
+                export::export_wpilog(store, path, &topics, start, end)
+            }));
         true
     }
 
+    /// Same as [`Self::export_wpilog`] but writes an MCAP file with one JSON
+    /// channel per topic, readable by Foxglove and loadable here again.
+    #[func]
+    pub fn export_mcap(
+        &mut self,
+        path: String,
+        topics: PackedStringArray,
+        start: i64,
+        end: i64,
+    ) -> bool {
+        let topics: Vec<String> = topics.as_slice().iter().map(|t| t.to_string()).collect();
+        let (start, end) = self.export_range(start, end);
+        let snapshot = export::snapshot(&self.store.read(), &topics, start, end);
+        self.exports
+            .push(ExportJob::spawn(path, snapshot, move |store, path| {
+                export::export_mcap(store, path, &topics, start, end)
+            }));
+        true
+    }
+
+    /// Writes one CSV row per sample (`timestamp,topic,value`) for `topics`
+    /// and everything under them, between `start` and `end` (microseconds),
+    /// on a background thread like [`Self::export_wpilog`].
+    #[func]
+    pub fn export_csv_long(
+        &mut self,
+        path: String,
+        topics: PackedStringArray,
+        start: i64,
+        end: i64,
+    ) -> bool {
+        self.export_csv(path, topics, start, end, CsvLayout::Long)
+    }
+
+    /// Writes a CSV with one column per topic (struct fields flattened). Rows
+    /// follow `reference_topic`'s samples when given, otherwise every
+    /// `period_micros`.
+    #[func]
+    pub fn export_csv_wide(
+        &mut self,
+        path: String,
+        topics: PackedStringArray,
+        start: i64,
+        end: i64,
+        period_micros: i64,
+        reference_topic: String,
+    ) -> bool {
+        let layout = if reference_topic.is_empty() {
+            CsvLayout::Wide {
+                period: period_micros.max(1) as u64,
+            }
+        } else {
+            CsvLayout::Aligned {
+                reference: reference_topic,
+            }
+        };
+        self.export_csv(path, topics, start, end, layout)
+    }
+
     #[func]
     pub fn subscribe_to_all(&self) {
         // In a real implementation using nt-network or equivalent,
@@ -167,6 +382,250 @@ impl NT4 {
         }
     }
 
+    /// Every pose of `topic` at the cursor, for the geometry getters. Number
+    /// arrays are read as legacy `[x, y, rotation]` poses, as the field
+    /// visualizers accept them.
+    fn poses_at(&self, topic: &str) -> Vec<SpatialPose> {
+        let store = self.store.read();
+        let mut poses = Vec::new();
+        let format = store
+            .get_topic_type(topic)
+            .and_then(|t| PoseFormat::from_type(&t, true));
+        let Some(format) = format else {
+            return poses;
+        };
+        let time = self.current_time();
+        if let Some(bytes) = store.get_raw(topic, time) {
+            format.decode_spatial(PoseSample::Struct(&bytes), &mut poses);
+        } else if let Some(values) = store.get_double_array(topic, time) {
+            format.decode_spatial(PoseSample::Numbers(&values), &mut poses);
+        }
+        poses
+    }
+
+    /// Extends computed topics over new samples, taking the write lock only
+    /// when an input changed.
+    fn update_computed(&self) {
+        if self.store.read().computed_stale() {
+            self.store.write().update_computed();
+        }
+    }
+
+    fn current_search_results(&self) -> &[Match] {
+        if self.store.read().generation == self.search_generation {
+            &self.search_results
+        } else {
+            &[]
+        }
+    }
+
+    /// Spans of a "Time Range" option; `start` and `end` bound "Visible Range".
+    fn time_range_spans(&mut self, name: &str, start: i64, end: i64) -> Option<Vec<(u64, u64)>> {
+        let range = TimeRange::from_name(name, start.max(0) as u64, end.max(0) as u64)?;
+        let store = self.store.read();
+        let intervals = self.modes.intervals(&store);
+        let info = MatchInfo::read(&store, &intervals);
+        Some(range.spans(&store, &intervals, &info))
+    }
+
+    fn match_info(&mut self) -> MatchInfo {
+        let store = self.store.read();
+        let intervals = self.modes.intervals(&store);
+        MatchInfo::read(&store, &intervals)
+    }
+
+    fn export_csv(
+        &mut self,
+        path: String,
+        topics: PackedStringArray,
+        start: i64,
+        end: i64,
+        layout: CsvLayout,
+    ) -> bool {
+        let topics: Vec<String> = topics.as_slice().iter().map(|t| t.to_string()).collect();
+        let (start, end) = self.export_range(start, end);
+        let start = start.max(self.store.read().get_start_timestamp());
+        let snapshot = {
+            let store = self.store.read();
+            let mut wanted = topics.clone();
+            if let CsvLayout::Aligned { reference } = &layout
+                && !wanted.is_empty()
+            {
+                wanted.push(reference.clone());
+            }
+            export::snapshot(&store, &wanted, start, end)
+        };
+        self.exports
+            .push(ExportJob::spawn(path, snapshot, move |store, path| {
+                export::export_csv(store, path, &topics, start, end, &layout)
+            }));
+        true
+    }
+
+    /// `start` and `end` of an export in microseconds; `end` <= 0 means the
+    /// end of the log.
+    fn export_range(&self, start: i64, end: i64) -> (u64, u64) {
+        let end = if end > 0 {
+            end as u64
+        } else {
+            self.store.read().get_last_timestamp()
+        };
+        (start.max(0) as u64, end)
+    }
+
+    /// Reports exports that finished since the last frame.
+    fn poll_exports(&mut self) {
+        let mut finished = Vec::new();
+        self.exports.retain(|job| match job.poll() {
+            Some(result) => {
+                finished.push((job.path.clone(), result));
+                false
+            }
+            None => true,
+        });
+        for (path, result) in finished {
+            let path = GString::from(path.as_str());
+            match result {
+                Ok(count) => {
+                    godot_print!("NT4: Exported {} records to {}", count, path);
+                    self.base_mut().emit_signal(
+                        "export_finished",
+                        &[path.to_variant(), (count as i64).to_variant()],
+                    );
+                }
+                Err(e) => {
+                    godot_print!("Error exporting to {}: {}", path, e);
+                    let reason = GString::from(e.to_string().as_str());
+                    self.base_mut()
+                        .emit_signal("export_failed", &[path.to_variant(), reason.to_variant()]);
+                }
+            }
+        }
+    }
+
+    /// Starts merging a log into the session. Only one load runs at a time.
+    fn start_import(&mut self, path: String, options: ImportOptions) -> bool {
+        if self.loader.is_some() {
+            godot_print!("NT4: A log is already loading");
+            return false;
+        }
+        godot_print!("NT4: Importing log file: {} under {}", path, options.prefix);
+
+        let generation = self.store.read().generation;
+        match LogLoader::spawn_import(&path, self.store.clone(), generation, options) {
+            Ok(loader) => {
+                self.loader = Some(loader);
+                true
+            }
+            Err(e) => {
+                godot_print!("Error reading file: {}", e);
+                false
+            }
+        }
+    }
+
+    fn poll_loader(&mut self) {
+        let events = match &self.loader {
+            Some(loader) => loader.poll(),
+            None => return,
+        };
+        for event in events {
+            match event {
+                LoadEvent::Progress(fraction) => {
+                    self.base_mut()
+                        .emit_signal("log_load_progress", &[fraction.to_variant()]);
+                }
+                LoadEvent::Aligned(offset) => {
+                    godot_print!("NT4: Import aligned by {} us", offset);
+                    self.base_mut()
+                        .emit_signal("log_import_aligned", &[offset.to_variant()]);
+                }
+                LoadEvent::Loaded => {
+                    let topics = self.store.read().topic_order.len();
+                    godot_print!("Log file loaded. Topics: {}", topics);
+                    self.loader = None;
+                    self.base_mut().emit_signal("log_loaded", &[]);
+                }
+                LoadEvent::Failed(reason) => {
+                    godot_print!("Log file load failed: {}", reason);
+                    self.loader = None;
+                    self.base_mut()
+                        .emit_signal("log_load_failed", &[reason.to_variant()]);
+                }
+            }
+        }
+    }
+
+    fn emit_changes(&mut self) {
+        let cursor = self.cursor_time;
+        let (added, changed) = {
+            let store = self.store.read();
+            if store.generation != self.seen_generation {
+                self.seen_generation = store.generation;
+                self.seen_topic_count = 0;
+            }
+            if store.revision == self.seen_revision
+                && cursor == self.seen_cursor
+                && self.seen_topic_count == store.topic_order.len()
+            {
+                return;
+            }
+
+            let added: PackedStringArray = store.topic_order[self.seen_topic_count..]
+                .iter()
+                .map(GString::from)
+                .collect();
+
+            let mut changed: HashSet<&str> = store
+                .topics_changed_since(self.seen_revision)
+                .into_iter()
+                .collect();
+            if cursor != self.seen_cursor {
+                // 0 means "live", i.e. the latest sample
+                let from = if self.seen_cursor > 0 {
+                    self.seen_cursor
+                } else {
+                    u64::MAX
+                };
+                let to = if cursor > 0 { cursor } else { u64::MAX };
+                changed.extend(store.topics_stepped_between(from, to));
+            }
+
+            self.seen_topic_count = store.topic_order.len();
+            self.seen_revision = store.revision;
+            self.seen_cursor = cursor;
+            (
+                added,
+                changed
+                    .into_iter()
+                    .map(GString::from)
+                    .collect::<PackedStringArray>(),
+            )
+        };
+
+        if !added.is_empty() {
+            self.base_mut()
+                .emit_signal("topics_added", &[added.to_variant()]);
+        }
+        if !changed.is_empty() {
+            self.base_mut()
+                .emit_signal("values_changed", &[changed.to_variant()]);
+        }
+    }
+
+    #[func]
+    pub fn get_topic_type(&self, topic: String) -> String {
+        let store = self.store.read();
+        store.get_topic_type(&topic).unwrap_or_default()
+    }
+
+    /// Units from the topic's log metadata, or "" if none were recorded.
+    #[func]
+    pub fn get_topic_units(&self, topic: String) -> String {
+        let store = self.store.read();
+        store.get_topic_units(&topic).unwrap_or_default()
+    }
+
     #[func]
     pub fn get_log_start_time(&self) -> i64 {
         let store = self.store.read();
@@ -196,50 +655,36 @@ impl NT4 {
     #[func]
     pub fn get_string(&self, topic: String, default: String) -> String {
         let store = self.store.read();
-        store.get_string(&topic, self.current_time(), default)
+        match store.get_string(&topic, self.current_time()) {
+            Some(s) => s.into_owned(),
+            None => default,
+        }
     }
 
     #[func]
     pub fn get_value(&self, topic: String, default: Variant) -> Variant {
         let store = self.store.read();
-        let time = self.current_time();
+        topic_value(&store, &topic, self.current_time()).unwrap_or(default)
+    }
 
-        if let Some(crate::log_store::TopicData::Double(..)) = store.data.get(&topic) {
-            return store.get_double(&topic, time, 0.0).to_variant();
-        }
-        if let Some(crate::log_store::TopicData::Boolean(..)) = store.data.get(&topic) {
-            return store.get_boolean(&topic, time, false).to_variant();
-        }
-        if let Some(crate::log_store::TopicData::String(..)) = store.data.get(&topic) {
-            return store.get_string(&topic, time, "".to_string()).to_variant();
-        }
-        if let Some(crate::log_store::TopicData::DoubleArray(..)) = store.data.get(&topic) {
-            let vec = store.get_double_array(&topic, time, Vec::new());
-            return vec.into_iter().collect::<PackedFloat64Array>().to_variant();
-        }
-        if let Some(crate::log_store::TopicData::BooleanArray(..)) = store.data.get(&topic) {
-            let vec = store.get_boolean_array(&topic, time, Vec::new());
-            let mut arr = Array::new();
-            for v in vec {
-                arr.push(v);
-            }
-            return arr.to_variant();
-        }
-        if let Some(crate::log_store::TopicData::StringArray(..)) = store.data.get(&topic) {
-            let vec = store.get_string_array(&topic, time, Vec::new());
-            return vec
-                .iter()
-                .map(GString::from)
-                .collect::<PackedStringArray>()
-                .to_variant();
-        }
-        if let Some(crate::log_store::TopicData::Raw(..)) = store.data.get(&topic) {
-            if let Some(vec) = store.get_raw(&topic, time) {
-                return PackedByteArray::from(&vec[..]).to_variant();
+    /// Values of all `topics` at one instant, read under a single lock so every
+    /// visualizer sees the same timestamp. `time` is in microseconds; 0 uses
+    /// the replay cursor. Topics with no data are left out.
+    #[func]
+    pub fn get_snapshot(&self, topics: PackedStringArray, time: i64) -> VarDictionary {
+        let time = if time > 0 {
+            time as u64
+        } else {
+            self.current_time()
+        };
+        let store = self.store.read();
+        let mut dict = VarDictionary::new();
+        for topic in topics.as_slice() {
+            if let Some(value) = topic_value(&store, &topic.to_string(), time) {
+                dict.set(topic.clone(), value);
             }
         }
-
-        default
+        dict
     }
 
     #[func]
@@ -249,35 +694,50 @@ impl NT4 {
         default: PackedFloat64Array,
     ) -> PackedFloat64Array {
         let store = self.store.read();
-        let vec = store.get_double_array(&topic, self.current_time(), Vec::new());
-        if vec.is_empty() {
-            return default;
+        match store.get_double_array(&topic, self.current_time()) {
+            Some(vals) if !vals.is_empty() => PackedFloat64Array::from(&*vals),
+            _ => default,
         }
-        vec.into_iter().collect()
     }
 
     #[func]
     pub fn get_boolean_array(&self, topic: String, default: Array<bool>) -> Array<bool> {
         let store = self.store.read();
-        let vec = store.get_boolean_array(&topic, self.current_time(), Vec::new());
-        if vec.is_empty() {
-            return default;
-        }
-        let mut arr = Array::new();
-        for v in vec {
-            arr.push(v);
+        match store.get_boolean_array(&topic, self.current_time()) {
+            Some(vals) if !vals.is_empty() => {
+                let mut arr = Array::new();
+                for &v in vals.iter() {
+                    arr.push(v);
+                }
+                arr
+            }
+            _ => default,
         }
-        arr
     }
 
     #[func]
     pub fn get_string_array(&self, topic: String, default: PackedStringArray) -> PackedStringArray {
         let store = self.store.read();
-        let vec = store.get_string_array(&topic, self.current_time(), Vec::new());
-        if vec.is_empty() {
-            return default;
+        let time = self.current_time();
+        match store.get_string_array(&topic, time) {
+            Some(list) if !list.is_empty() => list.iter().map(GString::from).collect(),
+            Some(_) => default,
+            None => match store.get_sample(&topic, time) {
+                Some(Sample::StringArray(list)) if !list.is_empty() => {
+                    list.iter().map(|s| GString::from(s.as_str())).collect()
+                }
+                _ => default,
+            },
+        }
+    }
+
+    #[func]
+    pub fn get_raw(&self, topic: String, default: PackedByteArray) -> PackedByteArray {
+        let store = self.store.read();
+        match store.get_raw(&topic, self.current_time()) {
+            Some(bytes) => PackedByteArray::from(&*bytes),
+            None => default,
         }
-        vec.iter().map(GString::from).collect()
     }
 
     #[func]
@@ -303,29 +763,432 @@ impl NT4 {
         dict
     }
 
-    // --- Geometry Helpers (Parsing Raw Bytes) ---
+    /// Robot mode over the log as `{start, end, mode}` (microseconds; mode is
+    /// "disabled", "auto", "teleop", "test" or "estop"), from the
+    /// DriverStation topics. Updated incrementally as live data arrives.
+    #[func]
+    pub fn get_mode_intervals(&mut self) -> Array<VarDictionary> {
+        let store = self.store.read();
+        let mut arr = Array::new();
+        for interval in self.modes.intervals(&store) {
+            let mut dict = VarDictionary::new();
+            dict.set("start", interval.start as i64);
+            dict.set("end", interval.end as i64);
+            dict.set("mode", interval.mode.as_str());
+            arr.push(&dict);
+        }
+        arr
+    }
 
-    // Helper to get raw bytes
-    fn get_raw_bytes(&self, topic: &str) -> Option<Vec<u8>> {
+    /// Adds (or redefines) `name` as a topic computed from `expression` over
+    /// other topics, e.g. `hypot(/Drive/VelocityX, /Drive/VelocityY)`; see
+    /// `expr.rs` for the syntax. Returns an error message, empty on success.
+    #[func]
+    pub fn add_computed_topic(&mut self, name: String, expression: String) -> GString {
+        match self.store.write().define_computed(&name, &expression) {
+            Ok(()) => GString::new(),
+            Err(e) => {
+                godot_print!("NT4: Can't compute {}: {}", name, e);
+                GString::from(e.as_str())
+            }
+        }
+    }
+
+    #[func]
+    pub fn remove_computed_topic(&mut self, name: String) -> bool {
+        let mut store = self.store.write();
+        let index = store.topic_order.iter().position(|topic| *topic == name);
+        if !store.remove_computed(&name) {
+            return false;
+        }
+        // Topics after it moved up one in `topic_order`
+        if index.is_some_and(|index| index < self.seen_topic_count) {
+            self.seen_topic_count -= 1;
+        }
+        true
+    }
+
+    /// Computed topics as `{name, expression}`, in definition order.
+    #[func]
+    pub fn get_computed_topics(&self) -> Array<VarDictionary> {
         let store = self.store.read();
-        store.get_raw(topic, self.current_time())
+        let mut arr = Array::new();
+        for topic in store.computed_topics() {
+            let mut dict = VarDictionary::new();
+            dict.set("name", topic.name());
+            dict.set("expression", topic.source());
+            arr.push(&dict);
+        }
+        arr
+    }
+
+    /// Stores the rate of change of the numeric topic `source` as the double
+    /// topic `target`, smoothed over `smoothing` samples on each side (0 for
+    /// a plain difference). Takes the samples logged so far.
+    #[func]
+    pub fn differentiate_topic(&mut self, source: String, target: String, smoothing: i64) -> bool {
+        let half_window = smoothing.max(0) as usize;
+        match self
+            .store
+            .write()
+            .differentiate(&source, &target, half_window)
+        {
+            Ok(()) => true,
+            Err(e) => {
+                godot_print!("NT4: Can't differentiate {}: {}", source, e);
+                false
+            }
+        }
     }
 
+    /// Stores the running integral of the numeric topic `source` (value ×
+    /// seconds) as the double topic `target`.
+    #[func]
+    pub fn integrate_topic(&mut self, source: String, target: String) -> bool {
+        match self.store.write().integrate(&source, &target) {
+            Ok(()) => true,
+            Err(e) => {
+                godot_print!("NT4: Can't integrate {}: {}", source, e);
+                false
+            }
+        }
+    }
+
+    /// Finds where `condition` holds, e.g. `/Shooter/RPM < 3000 && enabled`
+    /// (the syntax of computed topics, plus the words `enabled`, `disabled`,
+    /// `auto`, `teleop`, `test` and `estop` for the robot mode), for at least
+    /// `min_duration` microseconds. `edge` is "" for the spans, or "rising" or
+    /// "falling" for when they begin or end. Returns an error message, empty
+    /// on success; the matches replace those of the previous search.
+    #[func]
+    pub fn search_log(&mut self, condition: String, min_duration: i64, edge: String) -> GString {
+        let Some(edge) = Edge::from_name(&edge) else {
+            return GString::from(format!("unknown edge '{}'", edge).as_str());
+        };
+        let query = Query {
+            condition,
+            min_duration: min_duration.max(0) as u64,
+            edge,
+        };
+        let result = {
+            let store = self.store.read();
+            let intervals = self.modes.intervals(&store);
+            self.search_generation = store.generation;
+            store.search(&query, &intervals)
+        };
+        let error = match result {
+            Ok(matches) => {
+                self.search_results = matches;
+                GString::new()
+            }
+            Err(e) => {
+                godot_print!("NT4: Can't search for {}: {}", query.condition, e);
+                self.search_results.clear();
+                GString::from(e.as_str())
+            }
+        };
+        self.base_mut().emit_signal("search_results_changed", &[]);
+        error
+    }
+
+    #[func]
+    pub fn clear_search(&mut self) {
+        self.search_results.clear();
+        self.base_mut().emit_signal("search_results_changed", &[]);
+    }
+
+    /// Matches of the last search as `{start, end}` (microseconds), in time
+    /// order. Empty once another log is loaded.
+    #[func]
+    pub fn get_search_results(&self) -> Array<VarDictionary> {
+        let mut arr = Array::new();
+        for m in self.current_search_results() {
+            let mut dict = VarDictionary::new();
+            dict.set("start", m.start as i64);
+            dict.set("end", m.end as i64);
+            arr.push(&dict);
+        }
+        arr
+    }
+
+    /// Start of the first match after `timestamp`, or -1.
+    #[func]
+    pub fn get_next_search_result(&self, timestamp: i64) -> i64 {
+        self.current_search_results()
+            .iter()
+            .find(|m| m.start as i64 > timestamp)
+            .map_or(-1, |m| m.start as i64)
+    }
+
+    /// Start of the last match before `timestamp`, or -1.
+    #[func]
+    pub fn get_previous_search_result(&self, timestamp: i64) -> i64 {
+        self.current_search_results()
+            .iter()
+            .rfind(|m| (m.start as i64) < timestamp)
+            .map_or(-1, |m| m.start as i64)
+    }
+
+    /// Statistics of a numeric topic between two timestamps (microseconds):
+    /// `count`, `min`, `max`, `mean`, `rms`, `std_dev`, `time_weighted_mean`
+    /// and the percentiles `p1`, `p5`, `p25`, `p50`, `p75`, `p95` and `p99`.
+    /// A double array element is `topic/index`. Empty when there's no data.
+    #[func]
+    pub fn get_statistics(&self, topic: String, start: i64, end: i64) -> VarDictionary {
+        let store = self.store.read();
+        store
+            .statistics(&topic, start.max(0) as u64, end.max(0) as u64)
+            .map_or_else(VarDictionary::new, |stats| statistics_dict(&stats))
+    }
+
+    /// Frequency content of a double or boolean topic between two timestamps
+    /// (microseconds, `end <= 0` for the end of the log), resampled evenly
+    /// and averaged over overlapping FFT segments. `window` is "hann" (or
+    /// ""), "hamming", "blackman" or "rectangular". Returns `sample_rate`
+    /// (Hz), `frequencies`, `magnitudes` (amplitude) and `psd` (units²/Hz)
+    /// per bin, and the strongest peaks as `dominant_frequencies` and
+    /// `dominant_magnitudes`. Empty on failure.
+    #[func]
+    pub fn compute_spectrum(
+        &self,
+        topic: String,
+        start: i64,
+        end: i64,
+        window: String,
+    ) -> VarDictionary {
+        let mut dict = VarDictionary::new();
+        let Some(window) = Window::from_name(&window) else {
+            godot_print!("NT4: Unknown window {}", window);
+            return dict;
+        };
+        let store = self.store.read();
+        let end = if end > 0 {
+            end as u64
+        } else {
+            store.get_last_timestamp()
+        };
+        let result = match store.spectrum(&topic, start.max(0) as u64, end, window) {
+            Ok(result) => result,
+            Err(e) => {
+                godot_print!("NT4: Can't compute the spectrum of {}: {}", topic, e);
+                return dict;
+            }
+        };
+        let spectrum = &result.spectrum;
+        let (frequencies, magnitudes): (Vec<f64>, Vec<f64>) = result.peaks.into_iter().unzip();
+        dict.set("sample_rate", result.sample_rate);
+        dict.set(
+            "frequencies",
+            PackedFloat64Array::from(spectrum.frequencies.as_slice()),
+        );
+        dict.set(
+            "magnitudes",
+            PackedFloat64Array::from(spectrum.magnitudes.as_slice()),
+        );
+        dict.set("psd", PackedFloat64Array::from(spectrum.psd.as_slice()));
+        dict.set(
+            "dominant_frequencies",
+            PackedFloat64Array::from(frequencies.as_slice()),
+        );
+        dict.set(
+            "dominant_magnitudes",
+            PackedFloat64Array::from(magnitudes.as_slice()),
+        );
+        dict
+    }
+
+    /// Compares the pose topic `estimate` with the poses in `measured`
+    /// (vision results, or the simulated true pose), storing the errors at
+    /// each measurement as `prefix/XError`, `YError`, `TranslationError`
+    /// (meters) and `RotationError` (radians). With `legacy`, number array
+    /// topics are read as `[x, y, rotation]` poses. Returns the statistics
+    /// as in `get_statistics`, keyed by those names; empty on failure.
+    #[func]
+    pub fn analyze_pose_error(
+        &mut self,
+        estimate: String,
+        measured: String,
+        legacy: bool,
+        prefix: String,
+    ) -> VarDictionary {
+        let mut dict = VarDictionary::new();
+        let result = self
+            .store
+            .write()
+            .pose_error(&estimate, &measured, legacy, &prefix);
+        let stats = match result {
+            Ok(stats) => stats,
+            Err(e) => {
+                godot_print!("NT4: Can't compare {} with {}: {}", estimate, measured, e);
+                return dict;
+            }
+        };
+        for ((name, _), stats) in ERROR_TOPICS.iter().zip(stats) {
+            if let Some(stats) = stats {
+                dict.set(*name, statistics_dict(&stats));
+            }
+        }
+        dict
+    }
+
+    /// Driving metrics of a pose topic over a "Time Range" option ("Enabled",
+    /// "Auto", "Teleop", "Teleop (No Endgame)", "Full Log", or "Visible
+    /// Range" from `start` to `end` in microseconds): `distance` (m),
+    /// `duration` (s), `top_speed`, `max_acceleration`, `max_angular_velocity`,
+    /// the per-sample profiles `times`, `speeds`, `accelerations` and
+    /// `angular_velocities`, and `region_times`, the seconds spent in each of
+    /// `regions` (field meters). Empty if the topic isn't a pose; number
+    /// arrays count as `[x, y, rotation]` poses with `legacy`.
+    #[func]
+    pub fn get_odometry_metrics(
+        &mut self,
+        topic: String,
+        legacy: bool,
+        time_range: String,
+        start: i64,
+        end: i64,
+        regions: Array<Rect2>,
+    ) -> VarDictionary {
+        let mut dict = VarDictionary::new();
+        let Some(spans) = self.time_range_spans(&time_range, start, end) else {
+            godot_print!("NT4: Unknown time range {}", time_range);
+            return dict;
+        };
+        let regions: Vec<Region> = regions
+            .iter_shared()
+            .map(|rect| {
+                let (a, b) = (rect.position, rect.position + rect.size);
+                Region {
+                    min_x: a.x.min(b.x) as f64,
+                    min_y: a.y.min(b.y) as f64,
+                    max_x: a.x.max(b.x) as f64,
+                    max_y: a.y.max(b.y) as f64,
+                }
+            })
+            .collect();
+        let store = self.store.read();
+        let Some(metrics) = store.odometry_metrics(&topic, legacy, &spans, &regions) else {
+            return dict;
+        };
+
+        let times: Vec<i64> = metrics.times.iter().map(|&t| t as i64).collect();
+        dict.set("distance", metrics.distance);
+        dict.set("duration", metrics.duration);
+        dict.set("top_speed", metrics.top_speed);
+        dict.set("max_acceleration", metrics.max_acceleration);
+        dict.set("max_angular_velocity", metrics.max_angular_velocity);
+        dict.set("times", PackedInt64Array::from(times.as_slice()));
+        dict.set(
+            "speeds",
+            PackedFloat64Array::from(metrics.speeds.as_slice()),
+        );
+        dict.set(
+            "accelerations",
+            PackedFloat64Array::from(metrics.accelerations.as_slice()),
+        );
+        dict.set(
+            "angular_velocities",
+            PackedFloat64Array::from(metrics.angular_velocities.as_slice()),
+        );
+        dict.set(
+            "region_times",
+            PackedFloat64Array::from(metrics.region_times.as_slice()),
+        );
+        dict
+    }
+
+    /// Time spent in each cell of a `grid_resolution`-meter grid over a field
+    /// of `field_size` meters by the poses of `topic` (Pose2d, Pose3d,
+    /// Translation2d/3d, Transform2d/3d or arrays of them) during a "Time
+    /// Range" option; `start` and `end` (microseconds) bound "Visible Range"
+    /// only. Row-major from the field's `y = 0` edge, `ceil(field_size.x /
+    /// grid_resolution)` cells per row, scaled so the busiest cell is 1.
+    /// Empty if the topic isn't a pose or the grid is invalid. A number array
+    /// topic counts as `[x, y, rotation]` poses, since it was asked for.
+    #[func]
+    pub fn compute_heatmap(
+        &mut self,
+        topic: String,
+        range_kind: String,
+        grid_resolution: f64,
+        field_size: Vector2,
+        #[opt(default = 0)] start: i64,
+        #[opt(default = 0)] end: i64,
+    ) -> PackedFloat32Array {
+        let grid = HeatmapGrid::new(field_size.x as f64, field_size.y as f64, grid_resolution);
+        let Some(grid) = grid else {
+            godot_print!("NT4: Invalid heatmap grid");
+            return PackedFloat32Array::new();
+        };
+        let Some(spans) = self.time_range_spans(&range_kind, start, end) else {
+            godot_print!("NT4: Unknown time range {}", range_kind);
+            return PackedFloat32Array::new();
+        };
+        let store = self.store.read();
+        match store.heatmap(&topic, true, &spans, &grid) {
+            Some(cells) => PackedFloat32Array::from(cells.as_slice()),
+            None => PackedFloat32Array::new(),
+        }
+    }
+
+    /// FMS match metadata and phase boundaries: `event_name`, `match_type`
+    /// ("None", "Practice", "Qualification" or "Elimination"), `match_number`,
+    /// `replay_number`, `alliance` ("red", "blue" or ""), `station` (1-3, 0
+    /// when unknown) and the `auto_start`, `teleop_start`, `endgame_start` and
+    /// `match_end` timestamps (microseconds, -1 when unknown).
+    #[func]
+    pub fn get_match_info(&mut self) -> VarDictionary {
+        let info = self.match_info();
+        let micros = |t: Option<u64>| t.map_or(-1, |t| t as i64);
+
+        let mut dict = VarDictionary::new();
+        dict.set("event_name", info.event_name.as_str());
+        dict.set("match_type", info.match_type.as_str());
+        dict.set("match_number", info.match_number as i64);
+        dict.set("replay_number", info.replay_number as i64);
+        dict.set("alliance", info.alliance.map_or("", |a| a.as_str()));
+        dict.set("station", info.station as i64);
+        dict.set("auto_start", micros(info.auto.map(|p| p.start)));
+        dict.set("teleop_start", micros(info.teleop.map(|p| p.start)));
+        dict.set("endgame_start", micros(info.endgame_start()));
+        dict.set("match_end", micros(info.teleop.map(|p| p.end)));
+        dict
+    }
+
+    /// Seconds left in auto or teleop at `timestamp` (microseconds), or -1.0
+    /// outside the match.
+    #[func]
+    pub fn get_match_time(&mut self, timestamp: i64) -> f64 {
+        let info = self.match_info();
+        info.match_time(timestamp.max(0) as u64).unwrap_or(-1.0)
+    }
+
+    /// Log timestamp (microseconds) at which `match_time` seconds were left in
+    /// teleop (or auto), or -1 if the log has no such phase.
+    #[func]
+    pub fn get_match_timestamp(&mut self, match_time: f64, teleop: bool) -> i64 {
+        let info = self.match_info();
+        info.timestamp_at(match_time, teleop)
+            .map_or(-1, |t| t as i64)
+    }
+
+    // --- Geometry Helpers (Parsing Raw Bytes) ---
+
     #[func]
     pub fn get_translation2d(&self, topic: String, default: Vector2) -> Vector2 {
-        if let Some(bytes) = self.get_raw_bytes(&topic) {
-            if bytes.len() >= 16 {
-                let x = LittleEndian::read_f64(&bytes[0..8]);
-                let y = LittleEndian::read_f64(&bytes[8..16]);
-                return Vector2::new(x as f32, -y as f32); // Godot Y-Up
+        match self.poses_at(&topic).first() {
+            Some(pose) => {
+                let [x, y, _] = pose.translation;
+                Vector2::new(x as f32, -y as f32) // Godot Y-Up
             }
+            None => default,
         }
-        default
     }
 
     #[func]
     pub fn get_rotation2d(&self, topic: String, default: f64) -> f64 {
-        if let Some(bytes) = self.get_raw_bytes(&topic) {
+        let store = self.store.read();
+        if let Some(bytes) = store.get_raw(&topic, self.current_time()) {
             if bytes.len() >= 8 {
                 let val = LittleEndian::read_f64(&bytes[0..8]);
                 return -val; // Negated
@@ -336,45 +1199,19 @@ impl NT4 {
 
     #[func]
     pub fn get_pose2d(&self, topic: String, default: Transform2D) -> Transform2D {
-        if let Some(bytes) = self.get_raw_bytes(&topic) {
-            if bytes.len() >= 24 {
-                let x = LittleEndian::read_f64(&bytes[0..8]);
-                let y = LittleEndian::read_f64(&bytes[8..16]);
-                let rot = LittleEndian::read_f64(&bytes[16..24]);
-
-                let params = Vector2::new(x as f32, -y as f32);
-                let rotation = -rot as f32;
-                return Transform2D::from_angle_origin(rotation, params);
-            }
-        }
-        default
+        let Some(pose) = self.poses_at(&topic).first().map(SpatialPose::planar) else {
+            return default;
+        };
+        let origin = Vector2::new(pose.x as f32, -pose.y as f32);
+        let rotation = -pose.heading.unwrap_or_default() as f32; // Negated
+        Transform2D::from_angle_origin(rotation, origin)
     }
 
     #[func]
     pub fn get_pose3d(&self, topic: String, default: Transform3D) -> Transform3D {
-        if let Some(bytes) = self.get_raw_bytes(&topic) {
-            if bytes.len() >= 56 {
-                // Translation (24 bytes)
-                let tx = LittleEndian::read_f64(&bytes[0..8]);
-                let ty = LittleEndian::read_f64(&bytes[8..16]);
-                let tz = LittleEndian::read_f64(&bytes[16..24]);
-
-                // Rotation (32 bytes)
-                let qw = LittleEndian::read_f64(&bytes[24..32]);
-                let qx = LittleEndian::read_f64(&bytes[32..40]);
-                let qy = LittleEndian::read_f64(&bytes[40..48]);
-                let qz = LittleEndian::read_f64(&bytes[48..56]);
-
-                // Mapping: Origin: Vector3(-y, z, -x)
-                let origin = Vector3::new(-ty as f32, tz as f32, -tx as f32);
-
-                // Mapping: Basis: Quaternion(-qy, qz, -qx, qw)
-                let quat = Quaternion::new(-qy as f32, qz as f32, -qx as f32, qw as f32);
-
-                return Transform3D::new(Basis::from_quaternion(quat), origin);
-            }
-        }
-        default
+        self.poses_at(&topic)
+            .first()
+            .map_or(default, godot_transform)
     }
 
     #[func]
@@ -383,33 +1220,15 @@ impl NT4 {
         topic: String,
         default: Array<Transform3D>,
     ) -> Array<Transform3D> {
-        // Assuming raw bytes contain concatenated Pose3Ds [56 bytes * N]
-        if let Some(bytes) = self.get_raw_bytes(&topic) {
-            if bytes.len() % 56 == 0 {
-                let count = bytes.len() / 56;
-                let mut array = Array::new();
-                for i in 0..count {
-                    let offset = i * 56;
-                    // Translation
-                    let tx = LittleEndian::read_f64(&bytes[offset + 0..offset + 8]);
-                    let ty = LittleEndian::read_f64(&bytes[offset + 8..offset + 16]);
-                    let tz = LittleEndian::read_f64(&bytes[offset + 16..offset + 24]);
-
-                    // Rotation
-                    let qw = LittleEndian::read_f64(&bytes[offset + 24..offset + 32]);
-                    let qx = LittleEndian::read_f64(&bytes[offset + 32..offset + 40]);
-                    let qy = LittleEndian::read_f64(&bytes[offset + 40..offset + 48]);
-                    let qz = LittleEndian::read_f64(&bytes[offset + 48..offset + 56]);
-
-                    let origin = Vector3::new(-ty as f32, tz as f32, -tx as f32);
-                    let quat = Quaternion::new(-qy as f32, qz as f32, -qx as f32, qw as f32);
-
-                    array.push(Transform3D::new(Basis::from_quaternion(quat), origin));
-                }
-                return array;
-            }
+        let poses = self.poses_at(&topic);
+        if poses.is_empty() {
+            return default;
         }
-        default
+        let mut array = Array::new();
+        for pose in &poses {
+            array.push(godot_transform(pose));
+        }
+        array
     }
 
     #[func]
@@ -426,3 +1245,82 @@ impl NT4 {
         arr
     }
 }
+
+/// `Statistics` as the dictionary `get_statistics` returns.
+fn statistics_dict(stats: &Statistics) -> VarDictionary {
+    let mut dict = VarDictionary::new();
+    dict.set("count", stats.count as i64);
+    dict.set("min", stats.min);
+    dict.set("max", stats.max);
+    dict.set("mean", stats.mean);
+    dict.set("rms", stats.rms);
+    dict.set("std_dev", stats.std_dev);
+    dict.set("time_weighted_mean", stats.time_weighted_mean);
+    for (p, value) in PERCENTILES.iter().zip(stats.percentiles) {
+        dict.set(format!("p{}", p).as_str(), value);
+    }
+    dict
+}
+
+/// A pose in Godot's axes: FRC (x, y, z) is Godot (-y, z, -x).
+fn godot_transform(pose: &SpatialPose) -> Transform3D {
+    let [x, y, z] = pose.translation;
+    let [qw, qx, qy, qz] = pose.rotation;
+    let origin = Vector3::new(-y as f32, z as f32, -x as f32);
+    let quat = Quaternion::new(-qy as f32, qz as f32, -qx as f32, qw as f32);
+    Transform3D::new(Basis::from_quaternion(quat), origin)
+}
+
+/// Value of `topic` at `time` as the matching Godot type.
+fn topic_value(store: &LogStore, topic: &str, time: u64) -> Option<Variant> {
+    let Some(data) = store.data.get(topic) else {
+        // Indexed topics decode the one sample
+        return store.get_sample(topic, time).map(sample_value);
+    };
+    let value = match data {
+        TopicData::Double(..) => store.get_double(topic, time, 0.0).to_variant(),
+        TopicData::Boolean(..) => store.get_boolean(topic, time, false).to_variant(),
+        TopicData::String(..) => GString::from(&*store.get_string(topic, time)?).to_variant(),
+        TopicData::DoubleArray(..) => {
+            PackedFloat64Array::from(&*store.get_double_array(topic, time)?).to_variant()
+        }
+        TopicData::BooleanArray(..) => {
+            let mut arr = Array::new();
+            for &v in store.get_boolean_array(topic, time)?.iter() {
+                arr.push(v);
+            }
+            arr.to_variant()
+        }
+        TopicData::StringArray(..) => store
+            .get_string_array(topic, time)?
+            .iter()
+            .map(GString::from)
+            .collect::<PackedStringArray>()
+            .to_variant(),
+        TopicData::Raw(..) => PackedByteArray::from(&*store.get_raw(topic, time)?).to_variant(),
+    };
+    Some(value)
+}
+
+/// An owned sample as the matching Godot type.
+fn sample_value(sample: Sample) -> Variant {
+    match sample {
+        Sample::Double(v) => v.to_variant(),
+        Sample::Boolean(v) => v.to_variant(),
+        Sample::String(v) => GString::from(v.as_str()).to_variant(),
+        Sample::DoubleArray(v) => PackedFloat64Array::from(v.as_slice()).to_variant(),
+        Sample::BooleanArray(v) => {
+            let mut arr = Array::new();
+            for b in v {
+                arr.push(b);
+            }
+            arr.to_variant()
+        }
+        Sample::StringArray(v) => v
+            .iter()
+            .map(|s| GString::from(s.as_str()))
+            .collect::<PackedStringArray>()
+            .to_variant(),
+        Sample::Raw(v) => PackedByteArray::from(v.as_slice()).to_variant(),
+    }
+}
@@ -0,0 +1,164 @@
+//! Driving metrics from a pose topic over parts of the log: distance, speed,
+//! acceleration and turn rate profiles, and time spent in field regions.
+
+use crate::dsp;
+use crate::log_store::LogStore;
+use crate::pose::{PlanarPose, PoseFormat, angle_difference};
+
+/// Faster than any robot drives; a step this fast is a pose reset.
+const MAX_SPEED: f64 = 10.0;
+/// Longer gaps between samples mean the robot held still (repeated poses
+/// aren't stored), so motion isn't interpolated across them.
+const MAX_GAP: u64 = 250_000;
+/// Samples on each side smoothed over when differentiating.
+const SMOOTHING: usize = 2;
+
+/// An axis-aligned area of the field (meters).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Region {
+    fn contains(&self, pose: &PlanarPose) -> bool {
+        (self.min_x..=self.max_x).contains(&pose.x) && (self.min_y..=self.max_y).contains(&pose.y)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OdometryMetrics {
+    /// Meters driven, not counting pose resets.
+    pub distance: f64,
+    /// Seconds covered by pose samples.
+    pub duration: f64,
+    pub top_speed: f64,
+    pub max_acceleration: f64,
+    /// Largest turn rate (rad/s) either way; 0 without headings.
+    pub max_angular_velocity: f64,
+    /// Profiles, one entry per pose sample.
+    pub times: Vec<u64>,
+    pub speeds: Vec<f64>,
+    pub accelerations: Vec<f64>,
+    pub angular_velocities: Vec<f64>,
+    /// Seconds spent in each region passed in, in the same order.
+    pub region_times: Vec<f64>,
+}
+
+impl LogStore {
+    /// Metrics of the pose topic `topic` (its first pose, for arrays) over
+    /// `spans` (see [`crate::match_info::TimeRange::spans`]). `None` if the
+    /// topic doesn't hold poses (see [`PoseFormat::from_type`] for `legacy`).
+    pub fn odometry_metrics(
+        &self,
+        topic: &str,
+        legacy: bool,
+        spans: &[(u64, u64)],
+        regions: &[Region],
+    ) -> Option<OdometryMetrics> {
+        let format = PoseFormat::from_type(&self.get_topic_type(topic)?, legacy)?;
+        if !self.has_topic(topic) {
+            return None;
+        }
+
+        let mut metrics = OdometryMetrics {
+            region_times: vec![0.0; regions.len()],
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        for &(start, end) in spans {
+            let Some(data) = self.topic_range(topic, start, end) else {
+                continue;
+            };
+            let ts = data.timestamps();
+            let first = ts.partition_point(|&t| t < start);
+            let last = ts.partition_point(|&t| t <= end);
+            let mut times = Vec::new();
+            let mut poses = Vec::new();
+            for (idx, &time) in ts.iter().enumerate().take(last).skip(first) {
+                buf.clear();
+                format.decode(&data, idx, &mut buf);
+                if let Some(&pose) = buf.first() {
+                    times.push(time);
+                    poses.push(pose);
+                }
+            }
+
+            // Each pose holds until the next one or the end of the span
+            for (i, pose) in poses.iter().enumerate() {
+                let until = times.get(i + 1).copied().unwrap_or(end);
+                let seconds = (until - times[i]) as f64 / 1e6;
+                metrics.duration += seconds;
+                for (region, time) in regions.iter().zip(&mut metrics.region_times) {
+                    if region.contains(pose) {
+                        *time += seconds;
+                    }
+                }
+            }
+
+            // Differentiate each stretch between pose resets and pauses on its own
+            let mut from = 0;
+            for i in 1..=poses.len() {
+                if i < poses.len() && !is_reset(times[i - 1], times[i], &poses[i - 1], &poses[i]) {
+                    metrics.distance += step(&poses[i - 1], &poses[i]);
+                    if times[i] - times[i - 1] <= MAX_GAP {
+                        continue;
+                    }
+                }
+                add_profile(&mut metrics, &times[from..i], &poses[from..i]);
+                from = i;
+            }
+        }
+
+        metrics.top_speed = metrics.speeds.iter().copied().fold(0.0, f64::max);
+        metrics.max_acceleration = metrics.accelerations.iter().copied().fold(0.0, f64::max);
+        metrics.max_angular_velocity = metrics
+            .angular_velocities
+            .iter()
+            .map(|w| w.abs())
+            .fold(0.0, f64::max);
+        Some(metrics)
+    }
+}
+
+fn step(a: &PlanarPose, b: &PlanarPose) -> f64 {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+fn is_reset(t0: u64, t1: u64, a: &PlanarPose, b: &PlanarPose) -> bool {
+    let seconds = (t1 - t0) as f64 / 1e6;
+    step(a, b) > MAX_SPEED * seconds
+}
+
+/// Speed, acceleration and turn rate along one continuous stretch of poses.
+fn add_profile(metrics: &mut OdometryMetrics, times: &[u64], poses: &[PlanarPose]) {
+    let xs: Vec<f64> = poses.iter().map(|p| p.x).collect();
+    let ys: Vec<f64> = poses.iter().map(|p| p.y).collect();
+    let vx = dsp::derivative(times, &xs, SMOOTHING);
+    let vy = dsp::derivative(times, &ys, SMOOTHING);
+    let ax = dsp::derivative(times, &vx, SMOOTHING);
+    let ay = dsp::derivative(times, &vy, SMOOTHING);
+
+    // Headings unwrapped so turning past ±π isn't a jump
+    let mut headings: Vec<f64> = Vec::with_capacity(poses.len());
+    for pose in poses {
+        let heading = pose.heading.unwrap_or(0.0);
+        let unwrapped = match headings.last() {
+            Some(&prev) => prev + angle_difference(heading, prev),
+            None => heading,
+        };
+        headings.push(unwrapped);
+    }
+    let omega = dsp::derivative(times, &headings, SMOOTHING);
+
+    metrics.times.extend_from_slice(times);
+    metrics
+        .speeds
+        .extend(vx.iter().zip(&vy).map(|(x, y)| x.hypot(*y)));
+    metrics
+        .accelerations
+        .extend(ax.iter().zip(&ay).map(|(x, y)| x.hypot(*y)));
+    metrics.angular_velocities.extend(omega);
+}
@@ -0,0 +1,288 @@
+//! Field positions decoded from geometry topics (WPILib structs, or the
+//! legacy `[x, y, rotation]` number arrays), projected onto the field plane.
+
+use crate::log_store::{LogStore, TopicData};
+use byteorder::{ByteOrder, LittleEndian};
+use std::f64::consts::PI;
+
+/// Position on the field (meters, WPILib axes) and heading (radians) when
+/// the type has one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlanarPose {
+    pub x: f64,
+    pub y: f64,
+    pub heading: Option<f64>,
+}
+
+/// Position (meters, WPILib axes) and orientation as a quaternion `(w, x,
+/// y, z)`, the identity for types without a rotation. 2D types sit on the
+/// floor, turned about the vertical.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpatialPose {
+    pub translation: [f64; 3],
+    pub rotation: [f64; 4],
+}
+
+impl SpatialPose {
+    /// Projection onto the field plane; the heading is the yaw.
+    pub fn planar(&self) -> PlanarPose {
+        let [w, x, y, z] = self.rotation;
+        PlanarPose {
+            x: self.translation[0],
+            y: self.translation[1],
+            heading: Some((2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z))),
+        }
+    }
+}
+
+/// One sample of a pose topic: struct bytes, or legacy numbers.
+#[derive(Debug, Clone, Copy)]
+pub enum PoseSample<'a> {
+    Struct(&'a [u8]),
+    Numbers(&'a [f64]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// Translation and rotation angle (Pose2d, Transform2d).
+    Pose2d,
+    /// Translation and quaternion (Pose3d, Transform3d).
+    Pose3d,
+    Translation2d,
+    Translation3d,
+    /// `[x, y, rotation, ...]` doubles.
+    Numbers,
+}
+
+impl Layout {
+    /// Doubles per pose.
+    fn fields(self) -> usize {
+        match self {
+            Layout::Pose2d | Layout::Translation3d | Layout::Numbers => 3,
+            Layout::Pose3d => 7,
+            Layout::Translation2d => 2,
+        }
+    }
+}
+
+/// How to read poses out of the samples of one topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoseFormat {
+    layout: Layout,
+}
+
+impl PoseFormat {
+    /// The format of a topic of type `type_str` (e.g. `struct:Pose2d[]`),
+    /// `None` if it doesn't hold positions. A `double[]` topic is read as
+    /// legacy `[x, y, rotation]` poses only with `legacy`, since most hold
+    /// other numbers.
+    pub fn from_type(type_str: &str, legacy: bool) -> Option<Self> {
+        if type_str == "double[]" {
+            return legacy.then_some(Self {
+                layout: Layout::Numbers,
+            });
+        }
+        let name = type_str.strip_suffix("[]").unwrap_or(type_str);
+        let layout = match name.strip_prefix("struct:")? {
+            "Pose2d" | "Transform2d" => Layout::Pose2d,
+            "Pose3d" | "Transform3d" => Layout::Pose3d,
+            "Translation2d" => Layout::Translation2d,
+            "Translation3d" => Layout::Translation3d,
+            _ => return None,
+        };
+        Some(Self { layout })
+    }
+
+    /// Appends the poses in sample `idx` of `data` to `out`; an array topic
+    /// may hold any number.
+    pub fn decode(&self, data: &TopicData, idx: usize, out: &mut Vec<PlanarPose>) {
+        let sample = match data {
+            TopicData::Raw(_, list) => PoseSample::Struct(list.get(idx).unwrap_or_default()),
+            TopicData::DoubleArray(_, list) => {
+                PoseSample::Numbers(list.get(idx).unwrap_or_default())
+            }
+            _ => return,
+        };
+        self.fields(sample, |f| {
+            let heading = match self.layout {
+                Layout::Pose2d | Layout::Numbers => Some(f[2]),
+                Layout::Pose3d => {
+                    // Yaw of the quaternion (w, x, y, z)
+                    let (w, x, y, z) = (f[3], f[4], f[5], f[6]);
+                    Some((2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z)))
+                }
+                Layout::Translation2d | Layout::Translation3d => None,
+            };
+            out.push(PlanarPose {
+                x: f[0],
+                y: f[1],
+                heading,
+            });
+        });
+    }
+
+    /// Appends the full poses in `sample` to `out`.
+    pub fn decode_spatial(&self, sample: PoseSample, out: &mut Vec<SpatialPose>) {
+        self.fields(sample, |f| {
+            let yaw = |angle: f64| {
+                let (sin, cos) = (angle / 2.0).sin_cos();
+                [cos, 0.0, 0.0, sin]
+            };
+            let (translation, rotation) = match self.layout {
+                Layout::Pose2d | Layout::Numbers => ([f[0], f[1], 0.0], yaw(f[2])),
+                Layout::Pose3d => ([f[0], f[1], f[2]], [f[3], f[4], f[5], f[6]]),
+                Layout::Translation2d => ([f[0], f[1], 0.0], yaw(0.0)),
+                Layout::Translation3d => ([f[0], f[1], f[2]], yaw(0.0)),
+            };
+            out.push(SpatialPose {
+                translation,
+                rotation,
+            });
+        });
+    }
+
+    /// Calls `pose` with the doubles of each pose in `sample`.
+    fn fields(&self, sample: PoseSample, mut pose: impl FnMut(&[f64])) {
+        let count = self.layout.fields();
+        let mut fields = [0.0; 7];
+        match sample {
+            PoseSample::Struct(bytes) if self.layout != Layout::Numbers => {
+                for chunk in bytes.chunks_exact(count * 8) {
+                    LittleEndian::read_f64_into(chunk, &mut fields[..count]);
+                    pose(&fields[..count]);
+                }
+            }
+            PoseSample::Numbers(values) if self.layout == Layout::Numbers => {
+                values.chunks_exact(count).for_each(pose);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The first pose of each sample of a pose topic, for looking up the pose at
+/// any time.
+#[derive(Debug, Clone, Default)]
+pub struct PoseSeries {
+    times: Vec<u64>,
+    poses: Vec<PlanarPose>,
+}
+
+impl PoseSeries {
+    /// `None` if `topic` doesn't hold poses (see [`PoseFormat::from_type`]
+    /// for `legacy`).
+    pub fn read(store: &LogStore, topic: &str, legacy: bool) -> Option<Self> {
+        Self::read_range(store, topic, legacy, 0, u64::MAX)
+    }
+
+    /// Like [`Self::read`], but only needs to cover `start..=end`
+    /// (microseconds), including the pose in effect at `start`.
+    pub fn read_range(
+        store: &LogStore,
+        topic: &str,
+        legacy: bool,
+        start: u64,
+        end: u64,
+    ) -> Option<Self> {
+        let format = PoseFormat::from_type(&store.get_topic_type(topic)?, legacy)?;
+        let data = store.topic_range(topic, start, end)?;
+        let mut series = Self::default();
+        let mut buf = Vec::new();
+        for (idx, &time) in data.timestamps().iter().enumerate() {
+            buf.clear();
+            format.decode(&data, idx, &mut buf);
+            if let Some(&pose) = buf.first() {
+                series.times.push(time);
+                series.poses.push(pose);
+            }
+        }
+        Some(series)
+    }
+
+    /// The pose at `time`, interpolated between samples up to `max_gap`
+    /// microseconds apart. Across longer gaps the pose held still (repeats
+    /// aren't stored), so the earlier one is kept. `None` before the first
+    /// sample.
+    pub fn at(&self, time: u64, max_gap: u64) -> Option<PlanarPose> {
+        let next = self.times.partition_point(|&t| t <= time);
+        let prev = next.checked_sub(1)?;
+        let (a, t0) = (self.poses[prev], self.times[prev]);
+        let Some(&t1) = self.times.get(next) else {
+            return Some(a);
+        };
+        if t1 - t0 > max_gap {
+            return Some(a);
+        }
+        let b = self.poses[next];
+        let frac = (time - t0) as f64 / (t1 - t0) as f64;
+        let heading = a.heading.zip(b.heading).map(|(ha, hb)| {
+            // The short way around
+            ha + angle_difference(hb, ha) * frac
+        });
+        Some(PlanarPose {
+            x: a.x + (b.x - a.x) * frac,
+            y: a.y + (b.y - a.y) * frac,
+            heading: heading.map(|h| angle_difference(h, 0.0)),
+        })
+    }
+}
+
+/// `a - b` wrapped to [-π, π).
+pub fn angle_difference(a: f64, b: f64) -> f64 {
+    (a - b + PI).rem_euclid(2.0 * PI) - PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::column::ListColumn;
+
+    fn bytes(values: &[f64]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn number_arrays_only_when_asked() {
+        assert_eq!(PoseFormat::from_type("double[]", false), None);
+        assert!(PoseFormat::from_type("double[]", true).is_some());
+        assert!(PoseFormat::from_type("struct:Pose2d[]", false).is_some());
+        assert_eq!(PoseFormat::from_type("struct:Rotation2d", true), None);
+    }
+
+    #[test]
+    fn spatial_poses_match_planar_ones() {
+        let angle = 2.0;
+        let (sin, cos) = (angle / 2.0f64).sin_cos();
+        let cases = [
+            ("struct:Pose2d[]", bytes(&[1.0, 2.0, angle, 3.0, 4.0, 0.0])),
+            ("struct:Pose3d", bytes(&[1.0, 2.0, 0.5, cos, 0.0, 0.0, sin])),
+            ("struct:Translation3d", bytes(&[1.0, 2.0, 0.5])),
+        ];
+        for (type_str, sample) in &cases {
+            let format = PoseFormat::from_type(type_str, false).unwrap();
+            let mut spatial = Vec::new();
+            format.decode_spatial(PoseSample::Struct(sample), &mut spatial);
+            let data = TopicData::Raw(vec![1], {
+                let mut list = ListColumn::new();
+                list.push(sample);
+                list
+            });
+            let mut planar = Vec::new();
+            format.decode(&data, 0, &mut planar);
+
+            assert_eq!(spatial.len(), planar.len(), "{}", type_str);
+            for (s, p) in spatial.iter().zip(&planar) {
+                let projected = s.planar();
+                assert_eq!((projected.x, projected.y), (p.x, p.y), "{}", type_str);
+                let heading = p.heading.unwrap_or_default();
+                assert!(angle_difference(projected.heading.unwrap(), heading).abs() < 1e-9);
+            }
+        }
+
+        let legacy = PoseFormat::from_type("double[]", true).unwrap();
+        let mut spatial = Vec::new();
+        legacy.decode_spatial(PoseSample::Numbers(&[1.0, 2.0, angle]), &mut spatial);
+        assert_eq!(spatial[0].translation, [1.0, 2.0, 0.0]);
+        assert!((spatial[0].planar().heading.unwrap() - angle).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,147 @@
+//! How far a pose estimate is from measured poses (vision results, or the
+//! true pose in simulation), for tuning pose estimator standard deviations.
+//! Errors are taken on the field plane, at each measurement.
+
+use crate::log_store::{LogStore, windows};
+use crate::pose::{PoseFormat, PoseSeries, angle_difference};
+use crate::stats::Statistics;
+
+/// Estimate samples further apart than this held still in between.
+const MAX_GAP: u64 = 100_000;
+/// Stretch of the log read at a time (microseconds).
+const POSE_WINDOW: u64 = 60_000_000;
+
+/// Error topics written under the prefix, with their units.
+pub const ERROR_TOPICS: [(&str, &str); 4] = [
+    ("XError", "meters"),
+    ("YError", "meters"),
+    ("TranslationError", "meters"),
+    ("RotationError", "radians"),
+];
+
+impl LogStore {
+    /// Stores the error of the pose topic `estimate` at each sample of
+    /// `measured` (measured minus estimated, the estimate interpolated to the
+    /// measurement time) as double topics `prefix/XError`, `YError`,
+    /// `TranslationError` and `RotationError`. A sample holding several
+    /// poses (e.g. `Pose3d[]` from several cameras) gives their mean error.
+    /// With `legacy`, `double[]` topics count as `[x, y, rotation]` poses.
+    /// Takes the samples logged so far. Returns the statistics of each
+    /// error in [`ERROR_TOPICS`] order, over every measurement (the stored
+    /// topics drop repeated values).
+    pub fn pose_error(
+        &mut self,
+        estimate: &str,
+        measured: &str,
+        legacy: bool,
+        prefix: &str,
+    ) -> Result<[Option<Statistics>; 4], String> {
+        let targets = ERROR_TOPICS.map(|(name, _)| format!("{}/{}", prefix, name));
+        if let Some(target) = targets
+            .iter()
+            .find(|t| *t == estimate || *t == measured || self.is_computed(t))
+        {
+            return Err(format!("{} can't be replaced", target));
+        }
+        let is_pose = |topic: &str| {
+            self.get_topic_type(topic)
+                .and_then(|t| PoseFormat::from_type(&t, legacy))
+                .ok_or_else(|| format!("{} isn't a pose topic", topic))
+        };
+        is_pose(estimate)?;
+        let format = is_pose(measured)?;
+        let (first, last) = self
+            .topic_timestamps(measured)
+            .and_then(|ts| Some((*ts.first()?, *ts.last()?)))
+            .unwrap_or((1, 0));
+
+        // Rotation errors only where both sides have a heading
+        let mut times: [Vec<u64>; 4] = Default::default();
+        let mut errors: [Vec<f64>; 4] = Default::default();
+        let mut poses = Vec::new();
+        // A window at a time, so indexed logs aren't decoded whole; the
+        // estimate reaches far enough past the window to interpolate
+        for (start, end) in windows(first, last, POSE_WINDOW) {
+            let Some(data) = self.topic_range(measured, start, end) else {
+                continue;
+            };
+            let series =
+                PoseSeries::read_range(self, estimate, legacy, start, end.saturating_add(MAX_GAP))
+                    .unwrap_or_default();
+            let ts = data.timestamps();
+            let from = ts.partition_point(|&t| t < start);
+            let to = ts.partition_point(|&t| t <= end);
+            for (idx, &time) in ts.iter().enumerate().take(to).skip(from) {
+                let Some(estimated) = series.at(time, MAX_GAP) else {
+                    continue;
+                };
+                poses.clear();
+                format.decode(&data, idx, &mut poses);
+                if poses.is_empty() {
+                    continue;
+                }
+
+                let mut sums = [0.0; 4];
+                let mut headings = 0;
+                for pose in &poses {
+                    let (dx, dy) = (pose.x - estimated.x, pose.y - estimated.y);
+                    sums[0] += dx;
+                    sums[1] += dy;
+                    sums[2] += dx.hypot(dy);
+                    if let Some((a, b)) = pose.heading.zip(estimated.heading) {
+                        sums[3] += angle_difference(a, b);
+                        headings += 1;
+                    }
+                }
+                let counts = [poses.len(), poses.len(), poses.len(), headings];
+                let columns = times.iter_mut().zip(&mut errors);
+                for ((ts, values), (sum, count)) in columns.zip(sums.iter().zip(counts)) {
+                    if count > 0 {
+                        ts.push(time);
+                        values.push(sum / count as f64);
+                    }
+                }
+            }
+        }
+
+        let end = self.get_last_timestamp();
+        let stats = std::array::from_fn(|i| Statistics::of_series(&times[i], &errors[i], end));
+        for (i, (_, units)) in ERROR_TOPICS.iter().enumerate() {
+            self.store_derived(&targets[i], &times[i], &errors[i], Some(units.to_string()));
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statistics_count_repeated_errors() {
+        let mut store = LogStore::new();
+        store.set_type("/Odometry".into(), "double[]".into());
+        store.set_type("/Vision".into(), "double[]".into());
+        for i in 0..=10 {
+            store.update_double_array("/Odometry".into(), i * 20_000, &[i as f64, 0.0, 0.0]);
+        }
+        // Always 0.5 m ahead in x, then once 1.5 m
+        for (i, offset) in [0.5, 0.5, 0.5, 1.5].into_iter().enumerate() {
+            let time = (i as u64 + 1) * 40_000;
+            let x = time as f64 / 20_000.0 + offset;
+            store.update_double_array("/Vision".into(), time, &[x, 0.0, 0.0]);
+        }
+
+        let [x, _, translation, rotation] = store
+            .pose_error("/Odometry", "/Vision", true, "/Error")
+            .unwrap();
+        let x = x.unwrap();
+        assert_eq!(x.count, 4);
+        assert!((x.mean - 0.75).abs() < 1e-9, "{:?}", x);
+        assert_eq!(translation.unwrap().max, 1.5);
+        assert_eq!(rotation.unwrap().max, 0.0);
+        // The stored topic holds each change only
+        let stored = store.statistics("/Error/XError", 0, 200_000).unwrap();
+        assert_eq!(stored.count, 2);
+    }
+}
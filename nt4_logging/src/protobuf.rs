@@ -0,0 +1,323 @@
+//! Protobuf decoding without generated code, driven by the serialized
+//! `FileDescriptorSet` MCAP files carry as a channel's schema. Messages decode
+//! into an `rmpv::Value` map keyed by field name so they can be flattened
+//! like any other structured value.
+
+use rmpv::Value;
+use std::collections::HashMap;
+use std::io;
+
+// FieldDescriptorProto.Type
+const TYPE_DOUBLE: u64 = 1;
+const TYPE_FLOAT: u64 = 2;
+const TYPE_INT64: u64 = 3;
+const TYPE_UINT64: u64 = 4;
+const TYPE_INT32: u64 = 5;
+const TYPE_FIXED64: u64 = 6;
+const TYPE_FIXED32: u64 = 7;
+const TYPE_BOOL: u64 = 8;
+const TYPE_STRING: u64 = 9;
+const TYPE_MESSAGE: u64 = 11;
+const TYPE_BYTES: u64 = 12;
+const TYPE_UINT32: u64 = 13;
+const TYPE_ENUM: u64 = 14;
+const TYPE_SFIXED32: u64 = 15;
+const TYPE_SFIXED64: u64 = 16;
+const TYPE_SINT32: u64 = 17;
+const TYPE_SINT64: u64 = 18;
+
+const LABEL_REPEATED: u64 = 3;
+
+/// Guards against runaway recursion on malformed data.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+struct FieldDesc {
+    name: String,
+    number: u64,
+    kind: u64,
+    /// Fully qualified message type for message fields, without the leading dot.
+    type_name: String,
+    repeated: bool,
+}
+
+/// Message types from one descriptor set, by fully qualified name.
+#[derive(Debug, Clone, Default)]
+pub struct Descriptors {
+    messages: HashMap<String, Vec<FieldDesc>>,
+}
+
+impl Descriptors {
+    /// Parses a serialized `google.protobuf.FileDescriptorSet`.
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let mut descriptors = Self::default();
+        let mut wire = Wire::new(data);
+        while let Some((number, value)) = wire.next_field()? {
+            if let (1, WireValue::Bytes(file)) = (number, value) {
+                descriptors.parse_file(file)?;
+            }
+        }
+        Ok(descriptors)
+    }
+
+    pub fn contains(&self, message: &str) -> bool {
+        self.messages.contains_key(message)
+    }
+
+    /// Decodes a `message` (fully qualified name) from its wire bytes.
+    pub fn decode(&self, message: &str, data: &[u8]) -> io::Result<Value> {
+        self.decode_message(message, data, 0)
+    }
+
+    fn parse_file(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut package = String::new();
+        let mut messages = Vec::new();
+        let mut wire = Wire::new(data);
+        while let Some((number, value)) = wire.next_field()? {
+            match (number, value) {
+                (2, WireValue::Bytes(name)) => package = String::from_utf8_lossy(name).into_owned(),
+                (4, WireValue::Bytes(message)) => messages.push(message),
+                _ => {}
+            }
+        }
+        for message in messages {
+            self.parse_message(&package, message)?;
+        }
+        Ok(())
+    }
+
+    fn parse_message(&mut self, scope: &str, data: &[u8]) -> io::Result<()> {
+        let mut name = String::new();
+        let mut fields = Vec::new();
+        let mut nested = Vec::new();
+        let mut wire = Wire::new(data);
+        while let Some((number, value)) = wire.next_field()? {
+            match (number, value) {
+                (1, WireValue::Bytes(n)) => name = String::from_utf8_lossy(n).into_owned(),
+                (2, WireValue::Bytes(field)) => fields.push(parse_field(field)?),
+                (3, WireValue::Bytes(message)) => nested.push(message),
+                _ => {}
+            }
+        }
+
+        let full_name = if scope.is_empty() {
+            name
+        } else {
+            format!("{}.{}", scope, name)
+        };
+        for message in nested {
+            self.parse_message(&full_name, message)?;
+        }
+        self.messages.insert(full_name, fields);
+        Ok(())
+    }
+
+    fn decode_message(&self, message: &str, data: &[u8], depth: usize) -> io::Result<Value> {
+        let fields = self
+            .messages
+            .get(message)
+            .ok_or_else(|| invalid(&format!("unknown protobuf message {}", message)))?;
+        if depth > MAX_DEPTH {
+            return Err(invalid("protobuf message nested too deeply"));
+        }
+
+        let mut values: Vec<Option<Value>> = vec![None; fields.len()];
+        let mut wire = Wire::new(data);
+        while let Some((number, value)) = wire.next_field()? {
+            let Some(idx) = fields.iter().position(|f| f.number == number) else {
+                continue;
+            };
+            let field = &fields[idx];
+
+            let mut decoded = Vec::new();
+            match value {
+                WireValue::Bytes(bytes) if field.kind == TYPE_MESSAGE => {
+                    decoded.push(self.decode_message(&field.type_name, bytes, depth + 1)?);
+                }
+                // Packed repeated scalars
+                WireValue::Bytes(bytes) if !matches!(field.kind, TYPE_STRING | TYPE_BYTES) => {
+                    let mut packed = Wire::new(bytes);
+                    while !packed.is_empty() {
+                        let value = match field.kind {
+                            TYPE_DOUBLE | TYPE_FIXED64 | TYPE_SFIXED64 => {
+                                WireValue::Fixed64(packed.fixed64()?)
+                            }
+                            TYPE_FLOAT | TYPE_FIXED32 | TYPE_SFIXED32 => {
+                                WireValue::Fixed32(packed.fixed32()?)
+                            }
+                            _ => WireValue::Varint(packed.varint()?),
+                        };
+                        decoded.extend(scalar(field.kind, value));
+                    }
+                }
+                value => decoded.extend(scalar(field.kind, value)),
+            }
+
+            if field.repeated {
+                if let Value::Array(items) =
+                    values[idx].get_or_insert_with(|| Value::Array(Vec::new()))
+                {
+                    items.extend(decoded);
+                }
+            } else if let Some(value) = decoded.pop() {
+                values[idx] = Some(value);
+            }
+        }
+
+        // Scalars equal to their default are left off the wire, so a missing
+        // field means "zero", not "unchanged"
+        let entries = fields
+            .iter()
+            .zip(values)
+            .filter_map(|(field, value)| {
+                let value = value.or_else(|| default_value(field))?;
+                Some((Value::from(field.name.as_str()), value))
+            })
+            .collect();
+        Ok(Value::Map(entries))
+    }
+}
+
+fn parse_field(data: &[u8]) -> io::Result<FieldDesc> {
+    let mut field = FieldDesc {
+        name: String::new(),
+        number: 0,
+        kind: 0,
+        type_name: String::new(),
+        repeated: false,
+    };
+    let mut wire = Wire::new(data);
+    while let Some((number, value)) = wire.next_field()? {
+        match (number, value) {
+            (1, WireValue::Bytes(name)) => field.name = String::from_utf8_lossy(name).into_owned(),
+            (3, WireValue::Varint(n)) => field.number = n,
+            (4, WireValue::Varint(label)) => field.repeated = label == LABEL_REPEATED,
+            (5, WireValue::Varint(kind)) => field.kind = kind,
+            (6, WireValue::Bytes(name)) => {
+                field.type_name = String::from_utf8_lossy(name)
+                    .trim_start_matches('.')
+                    .to_string();
+            }
+            _ => {}
+        }
+    }
+    Ok(field)
+}
+
+fn default_value(field: &FieldDesc) -> Option<Value> {
+    if field.repeated {
+        return None;
+    }
+    let value = match field.kind {
+        TYPE_DOUBLE | TYPE_FLOAT => Value::F64(0.0),
+        TYPE_BOOL => Value::Boolean(false),
+        TYPE_STRING => Value::from(""),
+        TYPE_MESSAGE | TYPE_BYTES => return None,
+        _ => Value::from(0),
+    };
+    Some(value)
+}
+
+/// Converts one scalar field value by its declared type.
+fn scalar(kind: u64, value: WireValue) -> Option<Value> {
+    let value = match (kind, value) {
+        (TYPE_DOUBLE, WireValue::Fixed64(v)) => Value::F64(f64::from_bits(v)),
+        (TYPE_FLOAT, WireValue::Fixed32(v)) => Value::F64(f32::from_bits(v) as f64),
+        (TYPE_INT64, WireValue::Varint(v)) => Value::from(v as i64),
+        (TYPE_UINT64, WireValue::Varint(v)) => Value::from(v),
+        (TYPE_INT32 | TYPE_ENUM, WireValue::Varint(v)) => Value::from(v as i32),
+        (TYPE_UINT32, WireValue::Varint(v)) => Value::from(v as u32),
+        (TYPE_SINT32 | TYPE_SINT64, WireValue::Varint(v)) => {
+            Value::from((v >> 1) as i64 ^ -((v & 1) as i64))
+        }
+        (TYPE_BOOL, WireValue::Varint(v)) => Value::Boolean(v != 0),
+        (TYPE_FIXED64, WireValue::Fixed64(v)) => Value::from(v),
+        (TYPE_SFIXED64, WireValue::Fixed64(v)) => Value::from(v as i64),
+        (TYPE_FIXED32, WireValue::Fixed32(v)) => Value::from(v),
+        (TYPE_SFIXED32, WireValue::Fixed32(v)) => Value::from(v as i32),
+        (TYPE_STRING, WireValue::Bytes(b)) => Value::from(String::from_utf8_lossy(b).as_ref()),
+        (TYPE_BYTES, WireValue::Bytes(b)) => Value::Binary(b.to_vec()),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+enum WireValue<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+/// Walks the fields of one encoded message.
+struct Wire<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Wire<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("truncated protobuf message"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("protobuf varint too long"))
+    }
+
+    fn fixed64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn fixed32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Next `(field number, value)`, or `None` at the end of the message.
+    fn next_field(&mut self) -> io::Result<Option<(u64, WireValue<'a>)>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => WireValue::Varint(self.varint()?),
+            1 => WireValue::Fixed64(self.fixed64()?),
+            2 => {
+                let len = self.varint()? as usize;
+                WireValue::Bytes(self.take(len)?)
+            }
+            5 => WireValue::Fixed32(self.fixed32()?),
+            other => {
+                return Err(invalid(&format!(
+                    "unsupported protobuf wire type {}",
+                    other
+                )));
+            }
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
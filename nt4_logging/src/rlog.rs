@@ -0,0 +1,274 @@
+//! AdvantageKit RLOG decoding, for `.rlog` files and the live RLOG server
+//! (port 5800). Only revision 2 is supported: records are big-endian and each
+//! field carries its own length, so unknown types can be skipped.
+//!
+//! Record layout after the leading revision byte:
+//! - `0` timestamp: f64 seconds
+//! - `1` key: u16 id, u16-prefixed name, u16-prefixed type string
+//! - `2` field: u16 id, u16 length, value bytes
+
+use crate::batch::{Sample, StoreOp};
+use crate::schema::{Schema, decode_struct_entry};
+use byteorder::{BigEndian, ByteOrder};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::ops::Range;
+use std::time::Duration;
+
+pub const RLOG_PORT: u16 = 5800;
+
+const SUPPORTED_REVISION: u8 = 2;
+
+const RECORD_TIMESTAMP: u8 = 0;
+const RECORD_KEY: u8 = 1;
+const RECORD_FIELD: u8 = 2;
+
+/// Keeps the state RLOG spreads across records (current timestamp, key ids,
+/// schemas), so a file or a server stream can be fed through in pieces.
+#[derive(Default)]
+pub struct RlogDecoder {
+    revision: Option<u8>,
+    timestamp: u64,
+    keys: HashMap<u16, (String, String)>,
+    schemas: HashMap<String, Schema>,
+}
+
+impl RlogDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes a whole buffer: a file, or one server message.
+    pub fn decode(&mut self, data: &[u8], ops: &mut Vec<StoreOp>) -> io::Result<()> {
+        let mut pos = 0;
+        while pos < data.len() {
+            self.decode_record(data, &mut pos, ops)?;
+        }
+        Ok(())
+    }
+
+    /// Decodes the record at `pos` and advances past it. The revision byte
+    /// that opens every stream counts as a record.
+    pub fn decode_record(
+        &mut self,
+        data: &[u8],
+        pos: &mut usize,
+        ops: &mut Vec<StoreOp>,
+    ) -> io::Result<()> {
+        let mut cursor = Cursor { data, pos: *pos };
+
+        if self.revision.is_none() {
+            let revision = cursor.u8()?;
+            if revision != SUPPORTED_REVISION {
+                return Err(invalid(&format!("unsupported RLOG revision {}", revision)));
+            }
+            self.revision = Some(revision);
+            *pos = cursor.pos;
+            return Ok(());
+        }
+
+        match cursor.u8()? {
+            RECORD_TIMESTAMP => {
+                let seconds = cursor.f64()?;
+                self.timestamp = (seconds * 1_000_000.0).max(0.0) as u64;
+            }
+            RECORD_KEY => {
+                let id = cursor.u16()?;
+                let name = topic_name(&cursor.string()?);
+                let type_str = cursor.string()?;
+                ops.push(StoreOp::set_type(name.clone(), type_str.clone()));
+                self.keys.insert(id, (name, type_str));
+            }
+            RECORD_FIELD => {
+                let id = cursor.u16()?;
+                let len = cursor.u16()? as usize;
+                let value = cursor.take(len)?;
+                if let Some((name, type_str)) = self.keys.get(&id) {
+                    decode_struct_entry(
+                        name,
+                        type_str,
+                        value,
+                        self.timestamp,
+                        &mut self.schemas,
+                        ops,
+                    );
+                    if let Some(sample) = decode_value(type_str, value) {
+                        ops.push(StoreOp::sample(name.clone(), self.timestamp, sample));
+                    }
+                }
+            }
+            other => return Err(invalid(&format!("unknown RLOG record type {}", other))),
+        }
+
+        *pos = cursor.pos;
+        Ok(())
+    }
+}
+
+/// Splits a revision 2 stream into one byte range per timestamp record, the
+/// way the robot's server sends cycles. The first range also holds the
+/// revision byte and anything before the first timestamp. Returns each
+/// cycle's timestamp in microseconds.
+pub fn split_cycles(data: &[u8]) -> io::Result<Vec<(u64, Range<usize>)>> {
+    let mut cycles: Vec<(u64, Range<usize>)> = Vec::new();
+    let mut cursor = Cursor { data, pos: 1 };
+    let mut start = 0;
+    let mut timestamp = None;
+
+    while cursor.pos < data.len() {
+        let record = cursor.pos;
+        match cursor.u8()? {
+            RECORD_TIMESTAMP => {
+                if let Some(timestamp) = timestamp {
+                    cycles.push((timestamp, start..record));
+                    start = record;
+                }
+                timestamp = Some((cursor.f64()? * 1_000_000.0).max(0.0) as u64);
+            }
+            RECORD_KEY => {
+                cursor.u16()?;
+                cursor.string()?;
+                cursor.string()?;
+            }
+            RECORD_FIELD => {
+                cursor.u16()?;
+                let len = cursor.u16()? as usize;
+                cursor.take(len)?;
+            }
+            other => return Err(invalid(&format!("unknown RLOG record type {}", other))),
+        }
+    }
+    if start < data.len() {
+        cycles.push((timestamp.unwrap_or(0), start..data.len()));
+    }
+    Ok(cycles)
+}
+
+/// Replays `cycles` of `data` (see [`split_cycles`]) to a client the way the
+/// robot's server sends them: length-prefixed, at the recorded pace with
+/// pauses of at most `max_gap`. Returns once the client disconnects.
+pub fn serve_replay(
+    mut stream: TcpStream,
+    data: &[u8],
+    cycles: &[(u64, Range<usize>)],
+    max_gap: Duration,
+) -> io::Result<()> {
+    // Swallow heartbeats so the client's writes never block
+    let mut heartbeats = stream.try_clone()?;
+    let reader = std::thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        while matches!(heartbeats.read(&mut buf), Ok(n) if n > 0) {}
+    });
+
+    let mut last_timestamp = None;
+    for (timestamp, range) in cycles {
+        if let Some(last) = last_timestamp {
+            let gap = Duration::from_micros(timestamp.saturating_sub(last));
+            std::thread::sleep(gap.min(max_gap));
+        }
+        last_timestamp = Some(*timestamp);
+
+        let message = &data[range.clone()];
+        stream.write_all(&(message.len() as u32).to_be_bytes())?;
+        stream.write_all(message)?;
+    }
+
+    // Stay connected until the client leaves, or it would reconnect and get
+    // the whole log again
+    let _ = reader.join();
+    Ok(())
+}
+
+/// AdvantageKit keys have no leading slash ("RealOutputs/Drive/Pose").
+fn topic_name(key: &str) -> String {
+    if key.starts_with('/') {
+        key.to_string()
+    } else {
+        format!("/{}", key)
+    }
+}
+
+/// Decodes a field value by its key's type string. Like the .wpilog loader,
+/// integers and floats widen to doubles and unknown types stay raw.
+fn decode_value(type_str: &str, value: &[u8]) -> Option<Sample> {
+    let sample = match type_str {
+        "boolean" => Sample::Boolean(*value.first()? != 0),
+        "int64" => Sample::Double(BigEndian::read_i64(value.get(..8)?) as f64),
+        "float" => Sample::Double(BigEndian::read_f32(value.get(..4)?) as f64),
+        "double" => Sample::Double(BigEndian::read_f64(value.get(..8)?)),
+        "string" | "json" => Sample::String(String::from_utf8_lossy(value).into_owned()),
+        "boolean[]" => Sample::BooleanArray(value.iter().map(|&b| b != 0).collect()),
+        "int64[]" => Sample::DoubleArray(
+            value
+                .chunks_exact(8)
+                .map(|b| BigEndian::read_i64(b) as f64)
+                .collect(),
+        ),
+        "float[]" => Sample::DoubleArray(
+            value
+                .chunks_exact(4)
+                .map(|b| BigEndian::read_f32(b) as f64)
+                .collect(),
+        ),
+        "double[]" => Sample::DoubleArray(value.chunks_exact(8).map(BigEndian::read_f64).collect()),
+        "string[]" => {
+            // u32 count, then u32-prefixed strings
+            let mut cursor = Cursor {
+                data: value,
+                pos: 0,
+            };
+            let count = cursor.u32().ok()?;
+            let mut strings = Vec::new();
+            for _ in 0..count {
+                let len = cursor.u32().ok()? as usize;
+                strings.push(String::from_utf8_lossy(cursor.take(len).ok()?).into_owned());
+            }
+            Sample::StringArray(strings)
+        }
+        _ => Sample::Raw(value.to_vec()),
+    };
+    Some(sample)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Big-endian reader over an RLOG buffer.
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("truncated RLOG record"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(BigEndian::read_u16(self.take(2)?))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(BigEndian::read_u32(self.take(4)?))
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(BigEndian::read_f64(self.take(8)?))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+}
@@ -0,0 +1,218 @@
+use crate::batch::BatchWriter;
+use crate::log_store::LogStore;
+use crate::rlog::{RLOG_PORT, RlogDecoder};
+use byteorder::{BigEndian, ByteOrder};
+use godot::prelude::*;
+use parking_lot::RwLock;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The RLOG server drops clients that stop sending this.
+const HEARTBEAT: [u8; 4] = [6, 3, 5, 4];
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const READ_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Live alternative to `NetworkManager` for AdvantageKit's RLOG server. The
+/// robot sends length-prefixed messages: the first replays the log header and
+/// every key, then one per cycle.
+pub struct RlogClient;
+
+impl RlogClient {
+    pub fn new(store: Arc<RwLock<LogStore>>, server_ip: String, generation: u32) -> Self {
+        godot_print!(
+            "rlog: Connecting to {}:{} (Gen: {})",
+            server_ip,
+            RLOG_PORT,
+            generation
+        );
+        std::thread::spawn(move || run(store, server_ip, generation));
+        Self
+    }
+}
+
+fn run(store: Arc<RwLock<LogStore>>, server_ip: String, generation: u32) {
+    let writer = BatchWriter::spawn(store.clone(), generation);
+
+    while store.read().check_generation(generation) {
+        let stream = (server_ip.as_str(), RLOG_PORT)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .and_then(|addr| TcpStream::connect_timeout(&addr, Duration::from_secs(2)).ok());
+
+        if let Some(stream) = stream {
+            godot_print!("rlog: Connected to {}", server_ip);
+            match session(stream, &store, &writer, generation) {
+                Ok(false) => break,
+                Ok(true) => godot_print!("rlog: Server closed the connection"),
+                Err(e) => godot_print!("rlog: Connection lost: {}", e),
+            }
+        }
+        std::thread::sleep(RECONNECT_DELAY);
+    }
+    godot_print!("rlog: Generation {} is stale. Stopping thread.", generation);
+}
+
+/// Reads messages until the server goes away (`Ok(true)`, so reconnect) or
+/// the store moves to a new generation (`Ok(false)`).
+fn session(
+    mut stream: TcpStream,
+    store: &RwLock<LogStore>,
+    writer: &BatchWriter,
+    generation: u32,
+) -> std::io::Result<bool> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_nodelay(true)?;
+
+    // The server starts over with the header for every connection
+    let mut decoder = RlogDecoder::new();
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 64 * 1024];
+    let mut last_heartbeat: Option<Instant> = None;
+
+    loop {
+        if last_heartbeat.is_none_or(|t| t.elapsed() >= HEARTBEAT_INTERVAL) {
+            stream.write_all(&HEARTBEAT)?;
+            last_heartbeat = Some(Instant::now());
+        }
+
+        match stream.read(&mut chunk) {
+            Ok(0) => return Ok(true),
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                if !store.read().check_generation(generation) {
+                    return Ok(false);
+                }
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+
+        // Decode every complete message in the buffer
+        let mut consumed = 0;
+        let mut ops = Vec::new();
+        while buffer.len() - consumed >= 4 {
+            let len = BigEndian::read_u32(&buffer[consumed..consumed + 4]) as usize;
+            let end = consumed + 4 + len;
+            if buffer.len() < end {
+                break;
+            }
+            decoder.decode(&buffer[consumed + 4..end], &mut ops)?;
+            consumed = end;
+        }
+        buffer.drain(..consumed);
+
+        if !writer.send(ops) {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::Sample;
+    use crate::rlog::{serve_replay, split_cycles};
+    use std::net::TcpListener;
+
+    /// 25 cycles 20ms apart: a struct, double, boolean, string, double[] and
+    /// int64 key.
+    const FIXTURE: &[u8] = include_bytes!("../tests/fixtures/replay.rlog");
+
+    #[test]
+    fn replay_server_round_trip() {
+        let cycles = split_cycles(FIXTURE).unwrap();
+        assert_eq!(cycles.len(), 25);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_replay(stream, FIXTURE, &cycles, Duration::from_millis(1))
+        });
+
+        let store = Arc::new(RwLock::new(LogStore::new()));
+        let client = {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                let writer = BatchWriter::spawn(store.clone(), 0);
+                session(TcpStream::connect(addr)?, &store, &writer, 0)
+            })
+        };
+
+        // The last cycle holds the last speed
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while store
+            .read()
+            .get_double("/RealOutputs/Drive/Speed", u64::MAX, -1.0)
+            != 12.0
+        {
+            assert!(Instant::now() < deadline, "replay didn't arrive");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // A new generation ends the session, and the replay with it
+        store.write().generation += 1;
+        assert!(!client.join().unwrap().unwrap());
+        server.join().unwrap().unwrap();
+
+        let store = store.read();
+        assert_eq!(
+            store.get_double("/RealOutputs/Drive/Speed", 100_000, -1.0),
+            2.5
+        );
+        assert_eq!(
+            store.get_double("/RealOutputs/Drive/Position/x", 200_000, -1.0),
+            1.0
+        );
+        assert_eq!(
+            store.get_double("/RealOutputs/Drive/Position/y", 200_000, -1.0),
+            2.0
+        );
+        assert!(!store.get_boolean("/DriverStation/Enabled", 190_000, true));
+        assert!(store.get_boolean("/DriverStation/Enabled", 200_000, false));
+        assert_eq!(
+            store.get_string("/RealOutputs/Mode", 199_999).unwrap(),
+            "Disabled"
+        );
+        assert_eq!(
+            store.get_string("/RealOutputs/Mode", 200_000).unwrap(),
+            "Auto"
+        );
+        assert_eq!(
+            store
+                .get_double_array("/RealOutputs/Drive/Currents", 100_000)
+                .unwrap()[..],
+            [5.0, 6.0, 7.0]
+        );
+        assert_eq!(store.get_double("/Timestamp", 480_000, -1.0), 480_000.0);
+        assert_eq!(
+            store.get_topic_type("/RealOutputs/Drive/Position").unwrap(),
+            "struct:Translation2d"
+        );
+
+        // Everything else matches decoding the file in one go
+        let mut ops = Vec::new();
+        RlogDecoder::new().decode(FIXTURE, &mut ops).unwrap();
+        let mut expected = LogStore::new();
+        expected.apply_all(ops);
+        let mut topics: Vec<&str> = expected.topic_names().collect();
+        let mut received: Vec<&str> = store.topic_names().collect();
+        topics.sort_unstable();
+        received.sort_unstable();
+        assert_eq!(topics, received);
+        for topic in topics {
+            for time in (0..500_000).step_by(10_000) {
+                let sample: Option<Sample> = store.get_sample(topic, time);
+                assert_eq!(
+                    sample,
+                    expected.get_sample(topic, time),
+                    "{} at {}",
+                    topic,
+                    time
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! ROS message decoding from the `.msg` definitions MCAP embeds as a schema:
+//! `ros1msg` schemas with ROS 1 serialization, `ros2msg` schemas with CDR.
+//! Messages decode into an `rmpv::Value` map keyed by field name.
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use rmpv::Value;
+use std::collections::HashMap;
+use std::io;
+
+/// Guards against runaway recursion on malformed definitions.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+struct RosField {
+    name: String,
+    /// Builtin type name or resolved `package/Type`.
+    kind: String,
+    array: ArrayKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArrayKind {
+    None,
+    /// `T[]` and bounded `T[<=N]`: length-prefixed.
+    Dynamic,
+    Fixed(usize),
+}
+
+/// A message definition with every type it depends on.
+#[derive(Debug, Clone)]
+pub struct RosSchema {
+    root: String,
+    types: HashMap<String, Vec<RosField>>,
+}
+
+impl RosSchema {
+    /// Parses a concatenated definition: the root message first, then each
+    /// dependency after a `====` line and a `MSG: package/Type` line.
+    pub fn parse(name: &str, definition: &str) -> Self {
+        let root = normalize(name);
+        let mut types = HashMap::new();
+        let mut current = root.clone();
+        let mut fields = Vec::new();
+
+        for line in definition.lines() {
+            let line = line.trim();
+            if line.starts_with("===") {
+                types.insert(std::mem::take(&mut current), std::mem::take(&mut fields));
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("MSG:") {
+                current = normalize(name.trim());
+                continue;
+            }
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut tokens = line.split_whitespace();
+            let (Some(kind), Some(name)) = (tokens.next(), tokens.next()) else {
+                continue;
+            };
+            // Constants (`uint8 MODE=1`) take no space in the message
+            if name.contains('=') || tokens.next().is_some_and(|t| t.starts_with('=')) {
+                continue;
+            }
+            fields.push(parse_field(kind, name, package(&current)));
+        }
+        types.insert(current, fields);
+
+        Self { root, types }
+    }
+
+    /// Decodes one message. `cdr` selects ROS 2 CDR (with its 4-byte
+    /// encapsulation header) over ROS 1 serialization.
+    pub fn decode(&self, data: &[u8], cdr: bool) -> io::Result<Value> {
+        let mut reader = Reader::new(data, cdr)?;
+        self.decode_message(&self.root, &mut reader, 0)
+    }
+
+    fn fields(&self, kind: &str) -> Option<&[RosField]> {
+        if let Some(fields) = self.types.get(kind) {
+            return Some(fields);
+        }
+        // Unqualified references are sometimes resolved against the wrong
+        // package; fall back to a unique match on the type name alone
+        let suffix = format!("/{}", kind.rsplit('/').next()?);
+        let mut matches = self.types.iter().filter(|(k, _)| k.ends_with(&suffix));
+        match (matches.next(), matches.next()) {
+            (Some((_, fields)), None) => Some(fields),
+            _ => None,
+        }
+    }
+
+    fn decode_message(&self, kind: &str, reader: &mut Reader, depth: usize) -> io::Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("ROS message nested too deeply"));
+        }
+        let Some(fields) = self.fields(kind) else {
+            return builtin_message(kind, reader);
+        };
+
+        let mut entries = Vec::with_capacity(fields.len());
+        for field in fields {
+            let count = match field.array {
+                ArrayKind::None => None,
+                ArrayKind::Dynamic => Some(reader.u32()? as usize),
+                ArrayKind::Fixed(n) => Some(n),
+            };
+            let value = match count {
+                None => self.decode_value(&field.kind, reader, depth)?,
+                // Byte arrays (images, blobs) stay binary
+                Some(n) if matches!(field.kind.as_str(), "uint8" | "byte" | "char") => {
+                    Value::Binary(reader.take(n)?.to_vec())
+                }
+                Some(n) => {
+                    let mut items = Vec::with_capacity(n.min(reader.remaining()));
+                    for _ in 0..n {
+                        items.push(self.decode_value(&field.kind, reader, depth)?);
+                    }
+                    Value::Array(items)
+                }
+            };
+            entries.push((Value::from(field.name.as_str()), value));
+        }
+        Ok(Value::Map(entries))
+    }
+
+    fn decode_value(&self, kind: &str, reader: &mut Reader, depth: usize) -> io::Result<Value> {
+        let value = match kind {
+            "bool" => Value::Boolean(reader.take(1)?[0] != 0),
+            "int8" => Value::from(reader.take(1)?[0] as i8),
+            "uint8" | "byte" | "char" => Value::from(reader.take(1)?[0]),
+            "int16" => Value::from(reader.u16()? as i16),
+            "uint16" => Value::from(reader.u16()?),
+            "int32" => Value::from(reader.u32()? as i32),
+            "uint32" => Value::from(reader.u32()?),
+            "int64" => Value::from(reader.u64()? as i64),
+            "uint64" => Value::from(reader.u64()?),
+            "float32" => Value::F64(f32::from_bits(reader.u32()?) as f64),
+            "float64" => Value::F64(f64::from_bits(reader.u64()?)),
+            "string" => Value::from(reader.string()?.as_str()),
+            // ROS 1 primitives
+            "time" => Value::Map(vec![
+                (Value::from("sec"), Value::from(reader.u32()?)),
+                (Value::from("nsec"), Value::from(reader.u32()?)),
+            ]),
+            "duration" => Value::Map(vec![
+                (Value::from("sec"), Value::from(reader.u32()? as i32)),
+                (Value::from("nsec"), Value::from(reader.u32()? as i32)),
+            ]),
+            _ => self.decode_message(kind, reader, depth + 1)?,
+        };
+        Ok(value)
+    }
+}
+
+/// ROS 2 time types, for the rare writer that leaves their definitions out.
+fn builtin_message(kind: &str, reader: &mut Reader) -> io::Result<Value> {
+    match kind {
+        "builtin_interfaces/Time" | "builtin_interfaces/Duration" => Ok(Value::Map(vec![
+            (Value::from("sec"), Value::from(reader.u32()? as i32)),
+            (Value::from("nanosec"), Value::from(reader.u32()?)),
+        ])),
+        _ => Err(invalid(&format!("no definition for ROS type {}", kind))),
+    }
+}
+
+/// `geometry_msgs/msg/Pose` and `geometry_msgs/Pose` name the same type.
+fn normalize(name: &str) -> String {
+    name.replace("/msg/", "/")
+}
+
+fn package(kind: &str) -> &str {
+    kind.split('/').next().unwrap_or_default()
+}
+
+fn is_builtin(kind: &str) -> bool {
+    matches!(
+        kind,
+        "bool"
+            | "byte"
+            | "char"
+            | "int8"
+            | "uint8"
+            | "int16"
+            | "uint16"
+            | "int32"
+            | "uint32"
+            | "int64"
+            | "uint64"
+            | "float32"
+            | "float64"
+            | "string"
+            | "wstring"
+            | "time"
+            | "duration"
+    )
+}
+
+/// Parses `float64[3] covariance`, `string<=8[] names`, `Header header`, ...
+fn parse_field(kind: &str, name: &str, package: &str) -> RosField {
+    let (base, array) = match kind.strip_suffix(']').and_then(|k| k.rsplit_once('[')) {
+        Some((base, size)) => match size.parse() {
+            Ok(n) => (base, ArrayKind::Fixed(n)),
+            Err(_) => (base, ArrayKind::Dynamic),
+        },
+        None => (kind, ArrayKind::None),
+    };
+    // Bounded strings decode like plain ones
+    let base = base.split("<=").next().unwrap_or(base);
+
+    let kind = if is_builtin(base) {
+        base.to_string()
+    } else if base == "Header" {
+        "std_msgs/Header".to_string()
+    } else if base.contains('/') {
+        normalize(base)
+    } else {
+        format!("{}/{}", package, base)
+    };
+    RosField {
+        name: name.to_string(),
+        kind,
+        array,
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Reads ROS 1 (little-endian, packed) or CDR (either byte order, primitives
+/// aligned to their size relative to the end of the encapsulation header).
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    cdr: bool,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], cdr: bool) -> io::Result<Self> {
+        let mut reader = Self {
+            data,
+            pos: 0,
+            cdr,
+            big_endian: false,
+        };
+        if cdr {
+            let header = reader.take(4)?;
+            // Encapsulation kinds 0x0000/0x0002 are big-endian, 0x0001/0x0003 little
+            reader.big_endian = header[1] & 1 == 0;
+        }
+        Ok(reader)
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("truncated ROS message"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn align(&mut self, size: usize) {
+        if self.cdr {
+            let offset = self.pos - 4;
+            self.pos = 4 + offset.div_ceil(size) * size;
+        }
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        self.align(2);
+        let bytes = self.take(2)?;
+        Ok(if self.big_endian {
+            BigEndian::read_u16(bytes)
+        } else {
+            LittleEndian::read_u16(bytes)
+        })
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.align(4);
+        let bytes = self.take(4)?;
+        Ok(if self.big_endian {
+            BigEndian::read_u32(bytes)
+        } else {
+            LittleEndian::read_u32(bytes)
+        })
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        self.align(8);
+        let bytes = self.take(8)?;
+        Ok(if self.big_endian {
+            BigEndian::read_u64(bytes)
+        } else {
+            LittleEndian::read_u64(bytes)
+        })
+    }
+
+    /// CDR counts the trailing NUL in the length; ROS 1 has none.
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
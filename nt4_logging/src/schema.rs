@@ -1,3 +1,4 @@
+use crate::batch::{Sample, StoreOp};
 use byteorder::{ByteOrder, LittleEndian};
 use rmpv::Value;
 use std::collections::HashMap;
@@ -128,7 +129,7 @@ pub fn decode_struct(
                 // Schema names in map usually match the type name (e.g. "Pose3d")
                 // Or "struct:Pose3d" -> we strip "struct:" when storing?
                 // Let's assume schemas are stored by their short name "Pose3d".
-                if let Some(nested_schema) = schemas.get(other_type) {
+                if let Some(nested_schema) = lookup(schemas, other_type) {
                     // Recurse
                     // We need to know how many bytes the nested struct takes.
                     // The schema.size might be 0 if it contains nested types.
@@ -179,10 +180,141 @@ pub fn calculate_size(schema: &Schema, schemas: &HashMap<String, Schema>) -> usi
             size += s;
         } else {
             // Nested
-            if let Some(nested) = schemas.get(&field.type_str) {
+            if let Some(nested) = lookup(schemas, &field.type_str) {
                 size += calculate_size(nested, schemas);
             }
         }
     }
     size
 }
+
+/// Schemas are registered under their topic suffix (`struct:Pose2d`) while
+/// fields name nested types bare (`Translation2d`), so accept either.
+fn lookup<'a>(schemas: &'a HashMap<String, Schema>, type_str: &str) -> Option<&'a Schema> {
+    schemas
+        .get(type_str)
+        .or_else(|| schemas.get(&format!("struct:{}", type_str)))
+}
+
+/// Flattens a `struct:Name` (or `struct:Name[]`) payload into one child topic
+/// per field, `topic/field/subfield`. Arrays become one array sample per field.
+/// Shared by live NT4 updates and log loading so both produce the same topics.
+pub fn flatten_struct(
+    topic_name: &str,
+    struct_name: &str,
+    is_array: bool,
+    data: &[u8],
+    timestamp: u64,
+    schemas: &HashMap<String, Schema>,
+    ops: &mut Vec<StoreOp>,
+) {
+    let Some(schema) = lookup(schemas, struct_name) else {
+        return;
+    };
+
+    if !is_array {
+        for (field_path, val) in decode_struct(schema, data, schemas) {
+            let full_path = format!("{}/{}", topic_name, field_path);
+            if let Some(sample) = scalar_sample(val) {
+                ops.push(StoreOp::sample(full_path, timestamp, sample));
+            }
+        }
+        return;
+    }
+
+    let size = calculate_size(schema, schemas);
+    let mut cursor = 0;
+    let mut soa: HashMap<String, Vec<Value>> = HashMap::new();
+    while size > 0 && cursor + size <= data.len() {
+        let decoded = decode_struct(schema, &data[cursor..cursor + size], schemas);
+        for (field, val) in decoded {
+            soa.entry(field).or_default().push(val);
+        }
+        cursor += size;
+    }
+    for (field_path, values) in soa {
+        let full_path = format!("{}/{}", topic_name, field_path);
+        if let Some(sample) = array_sample(&values) {
+            ops.push(StoreOp::sample(full_path, timestamp, sample));
+        }
+    }
+}
+
+/// Handles the struct side of a log entry: registers `/.schema/` payloads as
+/// schemas and flattens `struct:` payloads into child topics. Used by the log
+/// loaders, which see schemas and structs as plain typed entries.
+pub fn decode_struct_entry(
+    topic_name: &str,
+    type_str: &str,
+    payload: &[u8],
+    timestamp: u64,
+    schemas: &mut HashMap<String, Schema>,
+    ops: &mut Vec<StoreOp>,
+) {
+    // NT-mirrored logs prefix names with "NT:", so match the schema dir anywhere
+    if let Some((_, schema_key)) = topic_name.split_once("/.schema/")
+        && let Ok(schema_str) = std::str::from_utf8(payload)
+    {
+        let schema = Schema::new(schema_key.to_string(), schema_str);
+        schemas.insert(schema_key.to_string(), schema);
+    }
+
+    flatten_struct_entry(topic_name, type_str, payload, timestamp, schemas, ops);
+}
+
+/// Flattens a `struct:Name` or `struct:Name[]` entry payload with the schemas
+/// seen so far; other types push nothing.
+pub fn flatten_struct_entry(
+    topic_name: &str,
+    type_str: &str,
+    payload: &[u8],
+    timestamp: u64,
+    schemas: &HashMap<String, Schema>,
+    ops: &mut Vec<StoreOp>,
+) {
+    let Some(struct_name) = type_str.strip_prefix("struct:") else {
+        return;
+    };
+    let (struct_name, is_array) = match struct_name.strip_suffix("[]") {
+        Some(element) => (element, true),
+        None => (struct_name, false),
+    };
+    flatten_struct(
+        topic_name,
+        struct_name,
+        is_array,
+        payload,
+        timestamp,
+        schemas,
+        ops,
+    );
+}
+
+pub fn scalar_sample(value: Value) -> Option<Sample> {
+    match value {
+        Value::Boolean(b) => Some(Sample::Boolean(b)),
+        Value::F32(f) => Some(Sample::Double(f as f64)),
+        Value::F64(f) => Some(Sample::Double(f)),
+        Value::Integer(i) => i.as_f64().map(Sample::Double),
+        _ => None,
+    }
+}
+
+/// Array samples take their element type from the first element.
+pub fn array_sample(values: &[Value]) -> Option<Sample> {
+    match values.first()? {
+        Value::Boolean(_) => Some(Sample::BooleanArray(
+            values.iter().filter_map(|v| v.as_bool()).collect(),
+        )),
+        Value::F64(_) | Value::F32(_) | Value::Integer(_) => Some(Sample::DoubleArray(
+            values.iter().filter_map(|v| v.as_f64()).collect(),
+        )),
+        Value::String(_) => Some(Sample::StringArray(
+            values
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect(),
+        )),
+        _ => None,
+    }
+}
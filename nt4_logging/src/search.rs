@@ -0,0 +1,166 @@
+//! Finding when a condition over topics held, e.g. every time the shooter
+//! slowed down while enabled: `/Shooter/RPM < 3000 && enabled`.
+//!
+//! Conditions are [`Expression`]s, true where nonzero, that may also use the
+//! robot mode as the words `enabled`, `disabled`, `auto`, `teleop`, `test`
+//! and `estop`. A condition is false until every topic in it has a sample.
+
+use crate::computed::number;
+use crate::expr::Expression;
+use crate::log_store::{LogStore, windows};
+use crate::modes::{ModeInterval, RobotMode};
+
+const MODE_NAMES: &[&str] = &["enabled", "disabled", "auto", "teleop", "test", "estop"];
+/// Stretch of the log read at a time (microseconds).
+const SEARCH_WINDOW: u64 = 60_000_000;
+
+/// Which part of each span of the condition holding to report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Edge {
+    /// The whole span.
+    #[default]
+    None,
+    /// When the condition became true.
+    Rising,
+    /// When it became false again.
+    Falling,
+}
+
+impl Edge {
+    /// `""`, `"rising"` or `"falling"`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "" => Some(Edge::None),
+            "rising" => Some(Edge::Rising),
+            "falling" => Some(Edge::Falling),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub condition: String,
+    /// Spans shorter than this (microseconds) are left out, also for edges.
+    pub min_duration: u64,
+    pub edge: Edge,
+}
+
+/// Where a query matched (microseconds); `start == end` for an edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub start: u64,
+    pub end: u64,
+}
+
+fn mode_flag(name: &str, mode: RobotMode) -> bool {
+    match name {
+        "enabled" => matches!(mode, RobotMode::Auto | RobotMode::Teleop | RobotMode::Test),
+        "disabled" => mode == RobotMode::Disabled,
+        "auto" => mode == RobotMode::Auto,
+        "teleop" => mode == RobotMode::Teleop,
+        "test" => mode == RobotMode::Test,
+        _ => mode == RobotMode::EStop,
+    }
+}
+
+impl LogStore {
+    /// Matches of `query` in time order, with the robot mode taken from
+    /// `modes` (see [`crate::modes::ModeTracker`]). Fails with a message if
+    /// the condition doesn't parse or names a missing topic.
+    pub fn search(&self, query: &Query, modes: &[ModeInterval]) -> Result<Vec<Match>, String> {
+        let expr = Expression::parse_with_names(&query.condition, MODE_NAMES)?;
+
+        let mut modes_seen = Vec::new();
+        let mut topics = Vec::new();
+        for (idx, name) in expr.inputs().iter().enumerate() {
+            if MODE_NAMES.contains(&name.as_str()) {
+                // Mode flags change at interval boundaries, false before the first
+                modes_seen.push((0, idx, 0.0));
+                modes_seen.extend(modes.iter().map(|interval| {
+                    let flag = mode_flag(name, interval.mode);
+                    (interval.start, idx, if flag { 1.0 } else { 0.0 })
+                }));
+            } else if self.has_topic(name) {
+                topics.push((idx, name.as_str()));
+            } else {
+                return Err(format!("{} doesn't exist", name));
+            }
+        }
+        modes_seen.sort_by_key(|&(time, _, _)| time);
+
+        let bounds = topics
+            .iter()
+            .filter_map(|(_, name)| self.topic_timestamps(name))
+            .filter_map(|ts| Some((*ts.first()?, *ts.last()?)))
+            .chain(
+                modes
+                    .iter()
+                    .map(|interval| (interval.start, interval.start)),
+            );
+        let (first, last) = bounds.fold((u64::MAX, 0), |(first, last), (a, b)| {
+            (first.min(a), last.max(b))
+        });
+
+        // Spans the condition held, and whether it became false again. The
+        // log is walked a window at a time so an indexed log is never decoded
+        // whole
+        let mut spans = Vec::new();
+        let mut held_since = None;
+        let mut state = expr.new_state();
+        let mut samples = Vec::new();
+        let mut next_mode = 0;
+        let mut group = Vec::new();
+        for (start, end) in windows(first, last, SEARCH_WINDOW) {
+            samples.clear();
+            while let Some(&sample) = modes_seen.get(next_mode).filter(|s| s.0 <= end) {
+                samples.push(sample);
+                next_mode += 1;
+            }
+            for &(idx, name) in &topics {
+                let Some(data) = self.topic_range(name, start, end) else {
+                    continue;
+                };
+                let ts = data.timestamps();
+                let from = ts.partition_point(|&t| t < start);
+                let to = ts.partition_point(|&t| t <= end);
+                for (i, &time) in ts.iter().enumerate().take(to).skip(from) {
+                    if let Some(value) = number(&data, i) {
+                        samples.push((time, idx, value));
+                    }
+                }
+            }
+            samples.sort_by_key(|&(time, _, _)| time);
+
+            for chunk in samples.chunk_by(|a, b| a.0 == b.0) {
+                let time = chunk[0].0;
+                group.clear();
+                group.extend(chunk.iter().map(|&(_, idx, value)| (idx, value)));
+                let holds = expr
+                    .step(&mut state, time, &group)
+                    .is_some_and(|value| value != 0.0 && !value.is_nan());
+                match held_since {
+                    None if holds => held_since = Some(time),
+                    Some(start) if !holds => {
+                        spans.push((start, time, true));
+                        held_since = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let Some(start) = held_since {
+            spans.push((start, self.get_last_timestamp().max(start), false));
+        }
+
+        let matches = spans
+            .into_iter()
+            .filter(|&(start, end, _)| end - start >= query.min_duration)
+            .filter_map(|(start, end, ended)| match query.edge {
+                Edge::None => Some(Match { start, end }),
+                Edge::Rising => Some(Match { start, end: start }),
+                Edge::Falling => ended.then_some(Match { start: end, end }),
+            });
+        Ok(matches.collect())
+    }
+}
@@ -0,0 +1,553 @@
+//! Decoded sessions cached on disk under a hash of the source file's
+//! contents, so reopening a log (or a copy of it) skips parsing and struct
+//! decoding. A cache is the store's columns written out as-is (little-endian,
+//! 8-byte aligned) and is streamed straight back into them.
+//!
+//! Caches aren't memory-mapped: the store's columns are growable `Vec`s that
+//! live data and merged logs append to, so they can't borrow from a mapping
+//! without copy-on-write in every column type. Reading still copies the data
+//! once, but skips the decoding that makes parsing slow.
+
+use crate::column::{ListColumn, StringArrayColumn, StringColumn};
+use crate::log_store::{LogStore, TopicData};
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::RwLock;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use twox_hash::XxHash3_64;
+
+const MAGIC: &[u8; 8] = b"FRCVSESS";
+/// Bump when the layout or anything about decoding changes, so old caches are
+/// parsed again instead of trusted.
+const VERSION: u32 = 3;
+/// Caches kept on disk; the least recently written are deleted first.
+const MAX_SESSIONS: usize = 16;
+const EXTENSION: &str = "session";
+/// Read size while hashing the source.
+const HASH_BUFFER: usize = 1 << 20;
+
+const KIND_DOUBLE: u8 = 0;
+const KIND_BOOLEAN: u8 = 1;
+const KIND_STRING: u8 = 2;
+const KIND_DOUBLE_ARRAY: u8 = 3;
+const KIND_BOOLEAN_ARRAY: u8 = 4;
+const KIND_STRING_ARRAY: u8 = 5;
+const KIND_RAW: u8 = 6;
+
+/// Hash of the whole source, naming its cache and checked again before the
+/// cache is trusted; leaves `file` rewound.
+pub fn content_hash(file: &mut File) -> io::Result<u64> {
+    file.rewind()?;
+    let mut hasher = XxHash3_64::new();
+    let mut reader = BufReader::with_capacity(HASH_BUFFER, Read::by_ref(file));
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        hasher.write(buf);
+        let len = buf.len();
+        reader.consume(len);
+    }
+    drop(reader);
+    file.rewind()?;
+    Ok(hasher.finish())
+}
+
+/// Where the cache for a source with `key` lives in `dir`.
+pub fn cache_path(dir: &Path, key: u64) -> PathBuf {
+    dir.join(format!("{:016x}.{}", key, EXTENSION))
+}
+
+/// Saves every loaded topic of `store` (types, metadata, schemas and struct
+/// fields included) as the cache for the source with hash `key`. Topics of an indexed log are left
+/// out; they are read from their own file anyway. Each topic is copied out
+/// under the read lock and written after it's released; nothing is saved if
+/// the store moves past `generation` meanwhile.
+pub fn write(store: &RwLock<LogStore>, generation: u32, dir: &Path, key: u64) -> io::Result<()> {
+    let topics: Vec<String> = {
+        let store = store.read();
+        if !store.check_generation(generation) {
+            return Ok(());
+        }
+        store
+            .topic_order
+            .iter()
+            .filter(|topic| !store.is_computed(topic) && store.data.contains_key(*topic))
+            .cloned()
+            .collect()
+    };
+
+    fs::create_dir_all(dir)?;
+    let path = cache_path(dir, key);
+    // Written under another name first so a reader never sees half a cache
+    let temp = path.with_extension("tmp");
+
+    let mut out = Out {
+        writer: BufWriter::new(File::create(&temp)?),
+        position: 0,
+    };
+    out.bytes(MAGIC)?;
+    out.u32(VERSION)?;
+    out.u32(0)?;
+    out.u64(key)?;
+
+    out.u64(topics.len() as u64)?;
+    for topic in &topics {
+        let copied = {
+            let store = store.read();
+            store
+                .data
+                .get(topic)
+                .filter(|_| store.check_generation(generation))
+                .map(|data| {
+                    let type_str = store.topic_types.get(topic).cloned().unwrap_or_default();
+                    let metadata = store.topic_metadata.get(topic).cloned().unwrap_or_default();
+                    (type_str, metadata, data.clone())
+                })
+        };
+        let Some((type_str, metadata, data)) = copied else {
+            // Cleared or reloaded while writing
+            drop(out);
+            return fs::remove_file(&temp);
+        };
+        out.string(topic)?;
+        out.string(&type_str)?;
+        out.string(&metadata)?;
+        write_column(&mut out, &data)?;
+    }
+    out.writer.flush()?;
+    drop(out);
+
+    fs::rename(&temp, &path)?;
+    prune(dir);
+    Ok(())
+}
+
+/// Reads the cache at `path` into a fresh store. Fails if it isn't a cache of
+/// this version for the source with hash `key`.
+pub fn read(path: &Path, key: u64) -> io::Result<LogStore> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut cursor = Cursor {
+        reader: BufReader::new(file),
+        pos: 0,
+        len,
+    };
+
+    if cursor.take(8)? != MAGIC {
+        return Err(invalid("not a session cache"));
+    }
+    if cursor.u32()? != VERSION {
+        return Err(invalid("session cache from another version"));
+    }
+    cursor.u32()?;
+    if cursor.u64()? != key {
+        return Err(invalid("session cache is for another file"));
+    }
+
+    let mut store = LogStore::new();
+    for _ in 0..cursor.u64()? {
+        let topic = cursor.string()?;
+        let type_str = cursor.string()?;
+        let metadata = cursor.string()?;
+        let data = read_column(&mut cursor)?;
+        if !type_str.is_empty() {
+            store.set_type(topic.clone(), type_str);
+        }
+        if !metadata.is_empty() {
+            store.set_metadata(topic.clone(), metadata);
+        }
+        store.insert_topic(topic, data);
+    }
+    Ok(store)
+}
+
+/// Deletes the oldest caches beyond [`MAX_SESSIONS`].
+fn prune(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut sessions: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == EXTENSION))
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect();
+    if sessions.len() <= MAX_SESSIONS {
+        return;
+    }
+    sessions.sort_unstable();
+    for (_, path) in &sessions[..sessions.len() - MAX_SESSIONS] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn write_column(out: &mut Out, data: &TopicData) -> io::Result<()> {
+    let kind = match data {
+        TopicData::Double(..) => KIND_DOUBLE,
+        TopicData::Boolean(..) => KIND_BOOLEAN,
+        TopicData::String(..) => KIND_STRING,
+        TopicData::DoubleArray(..) => KIND_DOUBLE_ARRAY,
+        TopicData::BooleanArray(..) => KIND_BOOLEAN_ARRAY,
+        TopicData::StringArray(..) => KIND_STRING_ARRAY,
+        TopicData::Raw(..) => KIND_RAW,
+    };
+    out.bytes(&[kind])?;
+    out.align()?;
+    let timestamps = data.timestamps();
+    out.u64(timestamps.len() as u64)?;
+    out.u64s(timestamps.iter().copied())?;
+
+    match data {
+        TopicData::Double(_, vals) => out.u64s(vals.iter().map(|v| v.to_bits())),
+        TopicData::Boolean(_, vals) => {
+            out.bytes(&vals.iter().map(|&v| v as u8).collect::<Vec<_>>())?;
+            out.align()
+        }
+        TopicData::String(_, vals) => out.byte_list(vals.bytes()),
+        TopicData::DoubleArray(_, vals) => {
+            out.offsets(vals.offsets())?;
+            out.u64s(vals.values().iter().map(|v| v.to_bits()))
+        }
+        TopicData::BooleanArray(_, vals) => {
+            out.offsets(vals.offsets())?;
+            out.bytes(&vals.values().iter().map(|&v| v as u8).collect::<Vec<_>>())?;
+            out.align()
+        }
+        TopicData::StringArray(_, vals) => {
+            out.offsets(vals.offsets())?;
+            out.u64(vals.strings().len() as u64)?;
+            out.byte_list(vals.strings().bytes())
+        }
+        TopicData::Raw(_, vals) => out.byte_list(vals),
+    }
+}
+
+fn read_column(cursor: &mut Cursor<impl Read>) -> io::Result<TopicData> {
+    let kind = cursor.take(1)?[0];
+    cursor.align()?;
+    let count = cursor.len()?;
+    let timestamps = cursor.u64s(count)?;
+
+    let data = match kind {
+        KIND_DOUBLE => TopicData::Double(timestamps, cursor.f64s(count)?),
+        KIND_BOOLEAN => {
+            let vals = cursor.bools(count)?;
+            cursor.align()?;
+            TopicData::Boolean(timestamps, vals)
+        }
+        KIND_STRING => TopicData::String(timestamps, string_column(cursor, count)?),
+        KIND_DOUBLE_ARRAY => {
+            let offsets = cursor.offsets(count)?;
+            let len = offsets.last().copied().unwrap_or_default();
+            let values = cursor.f64s(len)?;
+            TopicData::DoubleArray(timestamps, list(offsets, values)?)
+        }
+        KIND_BOOLEAN_ARRAY => {
+            let offsets = cursor.offsets(count)?;
+            let len = offsets.last().copied().unwrap_or_default();
+            let values = cursor.bools(len)?;
+            cursor.align()?;
+            TopicData::BooleanArray(timestamps, list(offsets, values)?)
+        }
+        KIND_STRING_ARRAY => {
+            let offsets = cursor.offsets(count)?;
+            let strings = cursor.len()?;
+            let strings = string_column(cursor, strings)?;
+            let column = StringArrayColumn::from_parts(offsets, strings)
+                .ok_or_else(|| invalid("corrupt string array column"))?;
+            TopicData::StringArray(timestamps, column)
+        }
+        KIND_RAW => TopicData::Raw(timestamps, byte_list(cursor, count)?),
+        other => return Err(invalid(&format!("unknown column kind {}", other))),
+    };
+    Ok(data)
+}
+
+fn list<T>(offsets: Vec<usize>, values: Vec<T>) -> io::Result<ListColumn<T>> {
+    ListColumn::from_parts(offsets, values).ok_or_else(|| invalid("corrupt list column"))
+}
+
+fn byte_list(cursor: &mut Cursor<impl Read>, count: usize) -> io::Result<ListColumn<u8>> {
+    let offsets = cursor.offsets(count)?;
+    let len = offsets.last().copied().unwrap_or_default();
+    let values = cursor.take(len)?;
+    cursor.align()?;
+    list(offsets, values)
+}
+
+fn string_column(cursor: &mut Cursor<impl Read>, count: usize) -> io::Result<StringColumn> {
+    StringColumn::from_bytes(byte_list(cursor, count)?)
+        .ok_or_else(|| invalid("corrupt string column"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Writer that tracks its position to keep columns 8-byte aligned.
+struct Out {
+    writer: BufWriter<File>,
+    position: u64,
+}
+
+impl Out {
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn align(&mut self) -> io::Result<()> {
+        let pad = self.position.next_multiple_of(8) - self.position;
+        self.bytes(&[0u8; 8][..pad as usize])
+    }
+
+    fn u32(&mut self, value: u32) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn u64(&mut self, value: u64) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn u64s(&mut self, values: impl Iterator<Item = u64>) -> io::Result<()> {
+        for value in values {
+            self.u64(value)?;
+        }
+        Ok(())
+    }
+
+    fn offsets(&mut self, offsets: &[usize]) -> io::Result<()> {
+        self.u64s(offsets.iter().map(|&o| o as u64))
+    }
+
+    fn string(&mut self, value: &str) -> io::Result<()> {
+        self.u32(value.len() as u32)?;
+        self.bytes(value.as_bytes())
+    }
+
+    fn byte_list(&mut self, list: &ListColumn<u8>) -> io::Result<()> {
+        self.offsets(list.offsets())?;
+        self.bytes(list.values())?;
+        self.align()
+    }
+}
+
+/// Reads columns straight out of the cache file into their vectors.
+struct Cursor<R> {
+    reader: R,
+    pos: u64,
+    len: u64,
+}
+
+impl<R: Read> Cursor<R> {
+    /// Fails without reading if fewer than `len` bytes are left, so a corrupt
+    /// count can't make us allocate more than the file holds.
+    fn check(&self, len: u64) -> io::Result<()> {
+        if len > self.len - self.pos {
+            return Err(invalid("truncated session cache"));
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        self.check(len as u64)?;
+        let mut bytes = vec![0; len];
+        self.reader.read_exact(&mut bytes)?;
+        self.pos += len as u64;
+        Ok(bytes)
+    }
+
+    fn align(&mut self) -> io::Result<()> {
+        let pad = self.pos.next_multiple_of(8) - self.pos;
+        self.take(pad as usize).map(drop)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(LittleEndian::read_u32(&self.take(4)?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(LittleEndian::read_u64(&self.take(8)?))
+    }
+
+    /// A count, checked against what is left in the file.
+    fn len(&mut self) -> io::Result<usize> {
+        let len = self.u64()?;
+        self.check(len)?;
+        Ok(len as usize)
+    }
+
+    /// `count` items of `size` bytes each, decoded through a small buffer.
+    fn items<T>(
+        &mut self,
+        count: usize,
+        size: usize,
+        item: impl Fn(&[u8]) -> T,
+    ) -> io::Result<Vec<T>> {
+        self.check((count as u64).saturating_mul(size as u64))?;
+        let mut items = Vec::with_capacity(count);
+        let mut buf = [0u8; 8192];
+        while items.len() < count {
+            let bytes = &mut buf[..(count - items.len()).min(8192 / size) * size];
+            self.reader.read_exact(bytes)?;
+            items.extend(bytes.chunks_exact(size).map(&item));
+        }
+        self.pos += (count * size) as u64;
+        Ok(items)
+    }
+
+    fn u64s(&mut self, count: usize) -> io::Result<Vec<u64>> {
+        self.items(count, 8, LittleEndian::read_u64)
+    }
+
+    fn f64s(&mut self, count: usize) -> io::Result<Vec<f64>> {
+        self.items(count, 8, LittleEndian::read_f64)
+    }
+
+    fn bools(&mut self, count: usize) -> io::Result<Vec<bool>> {
+        self.items(count, 1, |b| b[0] != 0)
+    }
+
+    /// `count + 1` list offsets.
+    fn offsets(&mut self, count: usize) -> io::Result<Vec<usize>> {
+        self.items(count + 1, 8, |b| LittleEndian::read_u64(b) as usize)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(&self.take(len)?).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::Sample;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// A store with every column kind, including empty lists and strings.
+    fn every_kind() -> LogStore {
+        let ts = vec![1_000, 2_000, 3_000];
+        let mut strings = StringColumn::new();
+        let mut doubles = ListColumn::new();
+        let mut booleans = ListColumn::new();
+        let mut string_arrays = StringArrayColumn::new();
+        let mut raw = ListColumn::new();
+        for i in 0..3 {
+            strings.push(["", "auto", "teleop ✓"][i]);
+            doubles.push(&[1.5, -0.25, f64::NAN][..i]);
+            booleans.push(&[true, false, true][i..]);
+            string_arrays.push(&["a", "", "long enough to pad"][..i]);
+            raw.push(&[0u8, 1, 2, 3, 4, 5, 6, 7, 8][..i * 4]);
+        }
+
+        let mut store = LogStore::new();
+        let columns = [
+            (
+                "/Double",
+                TopicData::Double(ts.clone(), vec![0.5, -1.0, 2.0]),
+            ),
+            (
+                "/Boolean",
+                TopicData::Boolean(ts.clone(), vec![true, false, true]),
+            ),
+            ("/String", TopicData::String(ts.clone(), strings)),
+            ("/DoubleArray", TopicData::DoubleArray(ts.clone(), doubles)),
+            (
+                "/BooleanArray",
+                TopicData::BooleanArray(ts.clone(), booleans),
+            ),
+            (
+                "/StringArray",
+                TopicData::StringArray(ts.clone(), string_arrays),
+            ),
+            ("/Raw", TopicData::Raw(ts, raw)),
+        ];
+        for (topic, data) in columns {
+            store.insert_topic(topic.to_string(), data);
+        }
+        store.set_type("/Raw".to_string(), "struct:Pose2d".to_string());
+        store.set_metadata("/Double".to_string(), r#"{"units":"m"}"#.to_string());
+        store
+    }
+
+    #[test]
+    fn round_trips_every_column_kind() {
+        let dir = temp_dir("session_round_trip");
+        let store = RwLock::new(every_kind());
+        write(&store, 0, &dir, 7).unwrap();
+        let cached = read(&cache_path(&dir, 7), 7).unwrap();
+        let store = store.read();
+
+        assert_eq!(cached.topic_order, store.topic_order);
+        assert_eq!(cached.topic_types, store.topic_types);
+        assert_eq!(cached.topic_metadata, store.topic_metadata);
+        for topic in &store.topic_order {
+            let (original, copy) = (&store.data[topic], &cached.data[topic]);
+            assert_eq!(copy.timestamps(), original.timestamps(), "{}", topic);
+            for i in 0..3 {
+                match (copy.sample(i).unwrap(), original.sample(i).unwrap()) {
+                    // NaN never equals itself; compare the bits
+                    (Sample::DoubleArray(a), Sample::DoubleArray(b)) => {
+                        let bits =
+                            |v: Vec<f64>| v.into_iter().map(f64::to_bits).collect::<Vec<_>>();
+                        assert_eq!(bits(a), bits(b), "{}", topic);
+                    }
+                    (a, b) => assert_eq!(a, b, "{}", topic),
+                }
+            }
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_other_sources_and_truncation() {
+        let dir = temp_dir("session_rejects");
+        write(&RwLock::new(every_kind()), 0, &dir, 7).unwrap();
+        let path = cache_path(&dir, 7);
+        assert!(read(&path, 8).is_err());
+
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 5]).unwrap();
+        assert!(read(&path, 7).is_err());
+
+        // A cleared store leaves no cache behind
+        write(&RwLock::new(every_kind()), 1, &dir, 8).unwrap();
+        assert!(!cache_path(&dir, 8).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn content_hash_covers_the_whole_file() {
+        let dir = temp_dir("session_hash");
+        fs::create_dir_all(&dir).unwrap();
+        let (path, copy) = (dir.join("a.wpilog"), dir.join("b.wpilog"));
+        let mut bytes = vec![7u8; 3 << 20];
+        fs::write(&path, &bytes).unwrap();
+        let original = content_hash(&mut File::open(&path).unwrap()).unwrap();
+
+        // A copy elsewhere finds the same cache
+        fs::copy(&path, &copy).unwrap();
+        assert_eq!(
+            content_hash(&mut File::open(&copy).unwrap()).unwrap(),
+            original
+        );
+
+        // A same-length edit in the middle doesn't
+        bytes[3 << 19] = 8;
+        fs::write(&path, &bytes).unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert_ne!(content_hash(&mut file).unwrap(), original);
+        assert_eq!(file.stream_position().unwrap(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
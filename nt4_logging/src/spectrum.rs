@@ -0,0 +1,147 @@
+//! Frequency content of a numeric topic, for finding oscillations (a
+//! mistuned PID loop, drivetrain vibration) in the log.
+
+use crate::computed::number;
+use crate::dsp::{self, Spectrum, Window};
+use crate::log_store::LogStore;
+
+/// Longest FFT segment; longer ranges are averaged over segments (Welch).
+const MAX_SEGMENT: usize = 1024;
+/// Fewest samples worth transforming.
+const MIN_SAMPLES: usize = 8;
+/// Percentile of the sample intervals taken as the logging period; low, but
+/// above the odd short interval from timestamp jitter.
+const RATE_PERCENTILE: usize = 5;
+/// Cap on resampled points, so a long range of fast samples stays cheap.
+const MAX_POINTS: u64 = 1 << 20;
+/// Dominant frequencies reported.
+const PEAKS: usize = 5;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopicSpectrum {
+    /// Rate (Hz) the topic was resampled at.
+    pub sample_rate: f64,
+    pub spectrum: Spectrum,
+    /// Strongest local maxima above DC as (frequency, magnitude), strongest
+    /// first.
+    pub peaks: Vec<(f64, f64)>,
+}
+
+impl LogStore {
+    /// Spectrum of the double or boolean topic `topic` between `start` and
+    /// `end` (microseconds). The samples are resampled, holding each value,
+    /// at a low percentile of the intervals between them: repeated values
+    /// aren't stored, so most intervals of a topic that often holds still
+    /// are longer than it was logged at.
+    /// Fails with a message if the topic isn't numeric or has too few
+    /// samples in the range.
+    pub fn spectrum(
+        &self,
+        topic: &str,
+        start: u64,
+        end: u64,
+        window: Window,
+    ) -> Result<TopicSpectrum, String> {
+        let data = self
+            .topic_range(topic, start, end)
+            .ok_or_else(|| format!("{} doesn't exist", topic))?;
+        let ts = data.timestamps();
+        let first = ts.partition_point(|&t| t < start);
+        let last = ts.partition_point(|&t| t <= end);
+        let mut times = Vec::new();
+        let mut values = Vec::new();
+        for (idx, &time) in ts.iter().enumerate().take(last).skip(first) {
+            match number(&data, idx) {
+                Some(value) if !value.is_nan() => {
+                    times.push(time);
+                    values.push(value);
+                }
+                Some(_) => {}
+                None => return Err(format!("{} isn't numeric", topic)),
+            }
+        }
+        drop(data);
+        if times.len() < MIN_SAMPLES {
+            return Err(format!("{} has too few samples in the range", topic));
+        }
+
+        let mut intervals: Vec<u64> = times
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .filter(|&interval| interval > 0)
+            .collect();
+        intervals.sort_unstable();
+        let span = times[times.len() - 1] - times[0];
+        let logged = intervals
+            .get(intervals.len() * RATE_PERCENTILE / 100)
+            .copied()
+            .unwrap_or(1);
+        let period = logged.max(span / MAX_POINTS).max(1);
+        let count = (span / period) as usize + 1;
+        if count < MIN_SAMPLES {
+            return Err(format!("{} has too few samples in the range", topic));
+        }
+        let uniform = dsp::resample_hold(&times, &values, times[0], period, count);
+
+        let sample_rate = 1e6 / period as f64;
+        let segment = MAX_SEGMENT.min(1 << count.ilog2());
+        let spectrum = dsp::welch(&uniform, sample_rate, segment, window);
+        let peaks = peaks(&spectrum);
+        Ok(TopicSpectrum {
+            sample_rate,
+            spectrum,
+            peaks,
+        })
+    }
+}
+
+fn peaks(spectrum: &Spectrum) -> Vec<(f64, f64)> {
+    let m = &spectrum.magnitudes;
+    let mut peaks: Vec<(f64, f64)> = (1..m.len())
+        .filter(|&k| m[k] > m[k - 1] && m.get(k + 1).is_none_or(|&next| m[k] >= next))
+        .map(|k| (spectrum.frequencies[k], m[k]))
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    peaks.truncate(PEAKS);
+    peaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strongest(store: &LogStore, topic: &str) -> f64 {
+        let result = store.spectrum(topic, 0, u64::MAX, Window::Hann).unwrap();
+        result.peaks[0].0
+    }
+
+    #[test]
+    fn finds_a_sine() {
+        let mut store = LogStore::new();
+        for i in 0..1000u64 {
+            let time = i * 5_000;
+            let value = (2.0 * std::f64::consts::PI * 12.5 * time as f64 / 1e6).sin();
+            store.update_double("/sine".into(), time, value);
+        }
+        let result = store.spectrum("/sine", 0, u64::MAX, Window::Hann).unwrap();
+        assert_eq!(result.sample_rate, 200.0);
+        assert!((result.peaks[0].0 - 12.5).abs() < 0.5, "{:?}", result.peaks);
+    }
+
+    #[test]
+    fn mostly_still_topics_keep_their_rate() {
+        // A 20 Hz burst at 200 Hz, then a slow drift for longer, which
+        // makes most intervals 50 ms
+        let mut store = LogStore::new();
+        for i in 0..200u64 {
+            let time = i * 5_000;
+            let value = (2.0 * std::f64::consts::PI * 20.0 * time as f64 / 1e6).sin();
+            store.update_double("/burst".into(), time, value);
+        }
+        for i in 0..300u64 {
+            let time = 1_000_000 + i * 50_000;
+            store.update_double("/burst".into(), time, i as f64 * 1e-4);
+        }
+        assert!((strongest(&store, "/burst") - 20.0).abs() < 0.5);
+    }
+}
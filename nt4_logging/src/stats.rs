@@ -0,0 +1,161 @@
+//! Summary statistics of a numeric topic over a time range.
+
+use crate::log_store::{LogStore, TopicData};
+
+/// Percentiles reported by [`Statistics`].
+pub const PERCENTILES: [f64; 7] = [1.0, 5.0, 25.0, 50.0, 75.0, 95.0, 99.0];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    /// Samples in the range, counting the value held at its start.
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub rms: f64,
+    /// Population standard deviation.
+    pub std_dev: f64,
+    /// One per entry of [`PERCENTILES`], interpolated between samples.
+    pub percentiles: [f64; PERCENTILES.len()],
+    /// Mean with each sample weighted by how long it was held in the range.
+    pub time_weighted_mean: f64,
+}
+
+impl LogStore {
+    /// Statistics of `topic` from `start` to `end` (microseconds, inclusive).
+    /// Works on double and boolean (0/1) topics, struct fields (which are
+    /// stored as child topics), one element of a double array written
+    /// `topic/index`, or every element of a double array pooled. `None` when
+    /// the topic has no value in the range.
+    pub fn statistics(&self, topic: &str, start: u64, end: u64) -> Option<Statistics> {
+        if end < start {
+            return None;
+        }
+        let (data, element) = match self.topic_range(topic, start, end) {
+            Some(data) => (data, None),
+            None => {
+                let (parent, index) = topic.rsplit_once('/')?;
+                let index = index.parse::<usize>().ok()?;
+                (self.topic_range(parent, start, end)?, Some(index))
+            }
+        };
+        if element.is_some() && !matches!(data.as_ref(), TopicData::DoubleArray(..)) {
+            return None;
+        }
+
+        // Samples in the range, plus the one in effect at `start`
+        let ts = data.timestamps();
+        let mut first = ts.partition_point(|&t| t < start);
+        let last = ts.partition_point(|&t| t <= end);
+        if first > 0 && ts.get(first) != Some(&start) {
+            first -= 1;
+        }
+
+        let mut samples = Vec::new();
+        let mut weighted = 0.0;
+        let mut held = 0;
+        let mut levels = 0.0;
+        let mut level_count = 0;
+        let mut buf = Vec::new();
+        for idx in first..last {
+            buf.clear();
+            sample_values(&data, element, idx, &mut buf);
+            buf.retain(|v| !v.is_nan());
+            if buf.is_empty() {
+                continue;
+            }
+            samples.extend_from_slice(&buf);
+
+            let level = buf.iter().sum::<f64>() / buf.len() as f64;
+            let from = ts[idx].max(start);
+            let to = if idx + 1 < last { ts[idx + 1] } else { end };
+            weighted += level * (to - from) as f64;
+            held += to - from;
+            levels += level;
+            level_count += 1;
+        }
+        if samples.is_empty() {
+            return None;
+        }
+        let time_weighted_mean = if held > 0 {
+            weighted / held as f64
+        } else {
+            levels / level_count as f64
+        };
+        Some(Statistics::summarize(samples, time_weighted_mean))
+    }
+}
+
+impl Statistics {
+    /// Statistics of `values` taken at `times`, each held until the next and
+    /// the last until `end`. Unlike [`LogStore::statistics`], repeated values
+    /// all count, as they are before being stored. `None` if every value is
+    /// NaN.
+    pub fn of_series(times: &[u64], values: &[f64], end: u64) -> Option<Self> {
+        let mut samples = Vec::with_capacity(values.len());
+        let mut weighted = 0.0;
+        let mut held = 0;
+        for (idx, (&time, &value)) in times.iter().zip(values).enumerate() {
+            if value.is_nan() {
+                continue;
+            }
+            samples.push(value);
+            let to = times.get(idx + 1).copied().unwrap_or(end).max(time);
+            weighted += value * (to - time) as f64;
+            held += to - time;
+        }
+        if samples.is_empty() {
+            return None;
+        }
+        let time_weighted_mean = if held > 0 {
+            weighted / held as f64
+        } else {
+            samples.iter().sum::<f64>() / samples.len() as f64
+        };
+        Some(Self::summarize(samples, time_weighted_mean))
+    }
+
+    fn summarize(mut samples: Vec<f64>, time_weighted_mean: f64) -> Self {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        let rms = (samples.iter().map(|v| v * v).sum::<f64>() / n).sqrt();
+        samples.sort_by(f64::total_cmp);
+
+        Statistics {
+            count: samples.len(),
+            min: samples[0],
+            max: samples[samples.len() - 1],
+            mean,
+            rms,
+            std_dev: variance.sqrt(),
+            percentiles: PERCENTILES.map(|p| percentile(&samples, p)),
+            time_weighted_mean,
+        }
+    }
+}
+
+/// Values of sample `idx`: one for a scalar or array element, every element
+/// of a pooled array.
+fn sample_values(data: &TopicData, element: Option<usize>, idx: usize, out: &mut Vec<f64>) {
+    match data {
+        TopicData::Double(_, vals) => out.push(vals[idx]),
+        TopicData::Boolean(_, vals) => out.push(if vals[idx] { 1.0 } else { 0.0 }),
+        TopicData::DoubleArray(_, list) => {
+            let items = list.get(idx).unwrap_or_default();
+            match element {
+                Some(element) => out.extend(items.get(element)),
+                None => out.extend_from_slice(items),
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The `p`th percentile of `sorted`, interpolating between neighbours.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let pos = p / 100.0 * (sorted.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = (lo + 1).min(sorted.len() - 1);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
@@ -0,0 +1,468 @@
+use crate::batch::Sample;
+use byteorder::{ByteOrder, LittleEndian};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 6] = b"WPILOG";
+
+/// Entry announced by a start control record.
+#[derive(Debug, Clone)]
+pub struct EntryInfo {
+    pub name: String,
+    pub type_str: String,
+    pub metadata: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Record {
+    Start {
+        entry: u32,
+        info: EntryInfo,
+    },
+    Finish {
+        entry: u32,
+    },
+    SetMetadata {
+        entry: u32,
+        metadata: String,
+    },
+    Data {
+        entry: u32,
+        timestamp: u64,
+        payload: Vec<u8>,
+    },
+}
+
+/// Reads a .wpilog one record at a time, so a file never has to be held in
+/// memory at once.
+pub struct WpilogReader<R> {
+    reader: R,
+    /// Bytes consumed so far, header included.
+    position: u64,
+    /// Size of the whole file.
+    len: u64,
+    entries: HashMap<u32, EntryInfo>,
+}
+
+impl<R: Read> WpilogReader<R> {
+    /// Checks the header and leaves the reader on the first record. `len` is
+    /// the size of the file, so records can't claim more than is left.
+    pub fn new(mut reader: R, len: u64) -> io::Result<Self> {
+        let mut header = [0u8; 12];
+        reader.read_exact(&mut header)?;
+        if &header[..6] != MAGIC {
+            return Err(invalid("not a WPILOG file"));
+        }
+        let version = LittleEndian::read_u16(&header[6..8]);
+        if version < 0x0100 {
+            return Err(invalid(&format!(
+                "unsupported WPILOG version {:#06x}",
+                version
+            )));
+        }
+        let extra_len = LittleEndian::read_u32(&header[8..12]) as u64;
+        io::copy(&mut (&mut reader).take(extra_len), &mut io::sink())?;
+
+        Ok(Self {
+            reader,
+            position: 12 + extra_len,
+            len,
+            entries: HashMap::new(),
+        })
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Entry currently registered under `entry`.
+    pub fn entry(&self, entry: u32) -> Option<&EntryInfo> {
+        self.entries.get(&entry)
+    }
+
+    /// Next record, or `None` at the end of the file. A record cut off by the
+    /// end of the file (a log still being written) is an error.
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let remaining = self.len.saturating_sub(self.position);
+        let Some((entry, timestamp, payload, len)) = read_record(&mut self.reader, remaining)?
+        else {
+            return Ok(None);
+        };
+        self.position += len;
+
+        if entry != 0 {
+            return Ok(Some(Record::Data {
+                entry,
+                timestamp,
+                payload,
+            }));
+        }
+        self.control_record(&payload).map(Some)
+    }
+
+    fn control_record(&mut self, payload: &[u8]) -> io::Result<Record> {
+        let mut cursor = Cursor(payload);
+        let kind = cursor.u8()?;
+        let entry = cursor.u32()?;
+        match kind {
+            0 => {
+                let info = EntryInfo {
+                    name: cursor.string()?,
+                    type_str: cursor.string()?,
+                    metadata: cursor.string()?,
+                };
+                self.entries.insert(entry, info.clone());
+                Ok(Record::Start { entry, info })
+            }
+            1 => {
+                self.entries.remove(&entry);
+                Ok(Record::Finish { entry })
+            }
+            2 => {
+                let metadata = cursor.string()?;
+                if let Some(info) = self.entries.get_mut(&entry) {
+                    info.metadata = metadata.clone();
+                }
+                Ok(Record::SetMetadata { entry, metadata })
+            }
+            other => Err(invalid(&format!("unknown control record {}", other))),
+        }
+    }
+}
+
+/// A record as stored: `(entry, timestamp, payload, bytes on disk)`. Entry 0
+/// is a control record.
+pub type RawRecord = (u32, u64, Vec<u8>, u64);
+
+/// Reads one raw record with `remaining` bytes left in the file; `None` at
+/// the end of the file. Fails on a record cut off or claiming more bytes than
+/// remain.
+pub fn read_record(reader: &mut impl Read, remaining: u64) -> io::Result<Option<RawRecord>> {
+    let mut bitfield = [0u8; 1];
+    if reader.read(&mut bitfield)? == 0 {
+        return Ok(None);
+    }
+    let entry_len = (bitfield[0] & 0x3) as usize + 1;
+    let size_len = ((bitfield[0] >> 2) & 0x3) as usize + 1;
+    let time_len = ((bitfield[0] >> 4) & 0x7) as usize + 1;
+
+    let mut header = [0u8; 16];
+    let header = &mut header[..entry_len + size_len + time_len];
+    fill(reader, header)?;
+    let entry = read_uint(&header[..entry_len]) as u32;
+    let size = read_uint(&header[entry_len..entry_len + size_len]);
+    let timestamp = read_uint(&header[entry_len + size_len..]);
+
+    let len = 1 + header.len() as u64 + size;
+    if len > remaining {
+        return Err(truncated());
+    }
+    let mut payload = vec![0u8; size as usize];
+    fill(reader, &mut payload)?;
+    Ok(Some((entry, timestamp, payload, len)))
+}
+
+/// `read_exact` that reports running out of file as a truncated log.
+fn fill(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => truncated(),
+        _ => e,
+    })
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "log file is truncated")
+}
+
+/// Decodes a data record payload by its entry type. Integer and float types
+/// widen to doubles like the network path does; anything not listed (structs,
+/// protobuf, msgpack, ...) is kept as raw bytes.
+pub fn decode_payload(type_str: &str, payload: &[u8]) -> Option<Sample> {
+    let sample = match type_str {
+        "double" => Sample::Double(LittleEndian::read_f64(payload.get(..8)?)),
+        "float" => Sample::Double(LittleEndian::read_f32(payload.get(..4)?) as f64),
+        "int64" => Sample::Double(LittleEndian::read_i64(payload.get(..8)?) as f64),
+        "boolean" => Sample::Boolean(*payload.first()? != 0),
+        "string" | "json" => Sample::String(String::from_utf8_lossy(payload).into_owned()),
+        "double[]" => Sample::DoubleArray(
+            payload
+                .chunks_exact(8)
+                .map(LittleEndian::read_f64)
+                .collect(),
+        ),
+        "float[]" => Sample::DoubleArray(
+            payload
+                .chunks_exact(4)
+                .map(|b| LittleEndian::read_f32(b) as f64)
+                .collect(),
+        ),
+        "int64[]" => Sample::DoubleArray(
+            payload
+                .chunks_exact(8)
+                .map(|b| LittleEndian::read_i64(b) as f64)
+                .collect(),
+        ),
+        "boolean[]" => Sample::BooleanArray(payload.iter().map(|&b| b != 0).collect()),
+        "string[]" => {
+            let mut cursor = Cursor(payload);
+            let count = cursor.u32().ok()?;
+            let mut strings = Vec::new();
+            for _ in 0..count {
+                strings.push(cursor.string().ok()?);
+            }
+            Sample::StringArray(strings)
+        }
+        _ => Sample::Raw(payload.to_vec()),
+    };
+    Some(sample)
+}
+
+/// Writes a .wpilog record by record.
+pub struct WpilogWriter<W: Write> {
+    writer: W,
+    next_entry: u32,
+}
+
+impl<W: Write> WpilogWriter<W> {
+    pub fn new(mut writer: W, extra_header: &str) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&0x0100u16.to_le_bytes())?;
+        writer.write_all(&(extra_header.len() as u32).to_le_bytes())?;
+        writer.write_all(extra_header.as_bytes())?;
+        Ok(Self {
+            writer,
+            next_entry: 1,
+        })
+    }
+
+    /// Announces a new entry and returns its id for [`Self::append`].
+    pub fn start_entry(
+        &mut self,
+        name: &str,
+        type_str: &str,
+        metadata: &str,
+        timestamp: u64,
+    ) -> io::Result<u32> {
+        let entry = self.next_entry;
+        self.next_entry += 1;
+
+        let mut payload = vec![0u8];
+        payload.extend_from_slice(&entry.to_le_bytes());
+        for s in [name, type_str, metadata] {
+            payload.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            payload.extend_from_slice(s.as_bytes());
+        }
+        self.write_record(0, timestamp, &payload)?;
+        Ok(entry)
+    }
+
+    pub fn append(&mut self, entry: u32, timestamp: u64, payload: &[u8]) -> io::Result<()> {
+        self.write_record(entry, timestamp, payload)
+    }
+
+    /// Flushes and hands back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_record(&mut self, entry: u32, timestamp: u64, payload: &[u8]) -> io::Result<()> {
+        // Each header field uses as few bytes as its value needs
+        let entry_len = uint_len(entry as u64);
+        let size_len = uint_len(payload.len() as u64);
+        let time_len = uint_len(timestamp);
+        let bitfield = (entry_len - 1) | ((size_len - 1) << 2) | ((time_len - 1) << 4);
+
+        let mut header = Vec::with_capacity(17);
+        header.push(bitfield as u8);
+        header.extend_from_slice(&(entry as u64).to_le_bytes()[..entry_len]);
+        header.extend_from_slice(&(payload.len() as u64).to_le_bytes()[..size_len]);
+        header.extend_from_slice(&timestamp.to_le_bytes()[..time_len]);
+        self.writer.write_all(&header)?;
+        self.writer.write_all(payload)
+    }
+}
+
+/// Encodes a sample as a data record payload for an entry of `type_str`, the
+/// inverse of [`decode_payload`]. The type only picks the numeric width.
+pub fn encode_payload(type_str: &str, sample: &Sample) -> Vec<u8> {
+    match sample {
+        Sample::Double(v) => match type_str {
+            "float" => (*v as f32).to_le_bytes().to_vec(),
+            "int64" => (*v as i64).to_le_bytes().to_vec(),
+            _ => v.to_le_bytes().to_vec(),
+        },
+        Sample::Boolean(v) => vec![*v as u8],
+        Sample::String(v) => v.as_bytes().to_vec(),
+        Sample::DoubleArray(vals) => match type_str {
+            "float[]" => vals
+                .iter()
+                .flat_map(|v| (*v as f32).to_le_bytes())
+                .collect(),
+            "int64[]" => vals
+                .iter()
+                .flat_map(|v| (*v as i64).to_le_bytes())
+                .collect(),
+            _ => vals.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        },
+        Sample::BooleanArray(vals) => vals.iter().map(|&v| v as u8).collect(),
+        Sample::StringArray(vals) => {
+            let mut payload = (vals.len() as u32).to_le_bytes().to_vec();
+            for v in vals {
+                payload.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                payload.extend_from_slice(v.as_bytes());
+            }
+            payload
+        }
+        Sample::Raw(bytes) => bytes.clone(),
+    }
+}
+
+/// Bytes needed to hold `value`, at least 1.
+fn uint_len(value: u64) -> usize {
+    (8 - value.leading_zeros() as usize / 8).max(1)
+}
+
+fn read_uint(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &b| (acc << 8) | b as u64)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Little-endian reader over a control record or string array payload.
+struct Cursor<'a>(&'a [u8]);
+
+impl Cursor<'_> {
+    fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.0.len() < len {
+            return Err(invalid("truncated record"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<(&'static str, Sample)> {
+        vec![
+            ("double", Sample::Double(1.5)),
+            ("float", Sample::Double(-0.25)),
+            ("int64", Sample::Double(-42.0)),
+            ("boolean", Sample::Boolean(true)),
+            ("string", Sample::String("héllo".into())),
+            ("double[]", Sample::DoubleArray(vec![1.0, f64::MAX, -3.5])),
+            ("int64[]", Sample::DoubleArray(vec![7.0, -8.0])),
+            ("boolean[]", Sample::BooleanArray(vec![true, false, true])),
+            (
+                "string[]",
+                Sample::StringArray(vec!["a".into(), String::new(), "ccc".into()]),
+            ),
+            ("raw", Sample::Raw(vec![0, 255, 3])),
+        ]
+    }
+
+    fn write_log() -> Vec<u8> {
+        let mut writer = WpilogWriter::new(Vec::new(), "extra").unwrap();
+        for (i, (type_str, sample)) in samples().iter().enumerate() {
+            let name = format!("/Entry{}", i);
+            let entry = writer.start_entry(&name, type_str, "{}", 0).unwrap();
+            // Timestamps wide enough to need every header width
+            let timestamp = 1u64 << (i * 6);
+            let payload = encode_payload(type_str, sample);
+            writer.append(entry, timestamp, &payload).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn read_all(bytes: &[u8]) -> io::Result<Vec<Record>> {
+        let mut reader = WpilogReader::new(bytes, bytes.len() as u64)?;
+        let mut records = Vec::new();
+        while let Some(record) = reader.next_record()? {
+            records.push(record);
+        }
+        assert_eq!(reader.position(), bytes.len() as u64);
+        Ok(records)
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = write_log();
+        let records = read_all(&bytes).unwrap();
+        let expected = samples();
+        assert_eq!(records.len(), expected.len() * 2);
+
+        for (i, pair) in records.chunks(2).enumerate() {
+            let (type_str, sample) = &expected[i];
+            let Record::Start { entry, info } = &pair[0] else {
+                panic!("expected a start record, got {:?}", pair[0]);
+            };
+            assert_eq!(info.name, format!("/Entry{}", i));
+            assert_eq!(info.type_str, *type_str);
+            assert_eq!(info.metadata, "{}");
+            let Record::Data {
+                entry: data_entry,
+                timestamp,
+                payload,
+            } = &pair[1]
+            else {
+                panic!("expected a data record, got {:?}", pair[1]);
+            };
+            assert_eq!(data_entry, entry);
+            assert_eq!(*timestamp, 1u64 << (i * 6));
+            let decoded = decode_payload(type_str, payload).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", sample));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_header() {
+        let mut bytes = write_log();
+        bytes[0] = b'X';
+        assert!(WpilogReader::new(&bytes[..], bytes.len() as u64).is_err());
+        assert!(WpilogReader::new(&bytes[..5], 5).is_err());
+    }
+
+    #[test]
+    fn reports_truncated_file() {
+        let bytes = write_log();
+        // Cut inside the last record's payload, and inside its header
+        for cut in [1, 4] {
+            let short = &bytes[..bytes.len() - cut];
+            let err = read_all(short).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_record() {
+        let mut bytes = write_log();
+        // A data record claiming 4 GiB of payload
+        bytes.extend_from_slice(&[0b0000_1100, 1, 0xff, 0xff, 0xff, 0xff, 0]);
+        let err = read_all(&bytes).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let mut cursor = &bytes[bytes.len() - 7..];
+        assert!(read_record(&mut cursor, 7).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+#!/usr/bin/env python3
+"""Writes the small MCAP fixtures the loader tests read, one per message
+encoding: protobuf, ROS 1, ROS 2 (CDR), JSON and CBOR. Each holds three
+messages 20ms apart starting at 1s, inside one uncompressed chunk, the way
+Foxglove and the ROS tools lay files out. Run from this directory."""
+
+import struct
+
+MAGIC = b"\x89MCAP0\r\n"
+TIMES = [1_000_000_000, 1_020_000_000, 1_040_000_000]
+
+
+def u16(v):
+    return struct.pack("<H", v)
+
+
+def u32(v):
+    return struct.pack("<I", v)
+
+
+def u64(v):
+    return struct.pack("<Q", v)
+
+
+def string(s):
+    b = s.encode() if isinstance(s, str) else s
+    return u32(len(b)) + b
+
+
+def record(opcode, body):
+    return bytes([opcode]) + u64(len(body)) + body
+
+
+def schema(id, name, encoding, data):
+    return record(0x03, u16(id) + string(name) + string(encoding) + string(data))
+
+
+def channel(id, schema_id, topic, encoding):
+    return record(0x04, u16(id) + u16(schema_id) + string(topic) + string(encoding) + u32(0))
+
+
+def message(channel_id, seq, time, data):
+    return record(0x05, u16(channel_id) + u32(seq) + u64(time) + u64(time) + data)
+
+
+def write(path, records):
+    chunk_records = b"".join(records)
+    chunk = (
+        u64(TIMES[0]) + u64(TIMES[-1]) + u64(len(chunk_records)) + u32(0)
+        + string("") + u64(len(chunk_records)) + chunk_records
+    )
+    out = MAGIC + record(0x01, string("") + string("make_mcap.py"))
+    out += record(0x06, chunk)
+    out += record(0x0F, u32(0))
+    out += record(0x02, bytes(20))
+    out += MAGIC
+    with open(path, "wb") as f:
+        f.write(out)
+
+
+# --- protobuf ---
+
+def varint(v):
+    out = bytearray()
+    while True:
+        byte = v & 0x7F
+        v >>= 7
+        if v:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def pb_bytes(number, data):
+    data = data.encode() if isinstance(data, str) else data
+    return varint(number << 3 | 2) + varint(len(data)) + data
+
+
+def pb_varint(number, v):
+    return varint(number << 3) + varint(v)
+
+
+def pb_double(number, v):
+    return varint(number << 3 | 1) + struct.pack("<d", v)
+
+
+def pb_field(name, number, kind, type_name=None, repeated=False):
+    body = pb_bytes(1, name) + pb_varint(3, number) + pb_varint(4, 3 if repeated else 1)
+    body += pb_varint(5, kind)
+    if type_name:
+        body += pb_bytes(6, type_name)
+    return pb_bytes(2, body)
+
+
+def pb_message(name, fields):
+    return pb_bytes(4, pb_bytes(1, name) + b"".join(fields))
+
+
+def protobuf():
+    file = pb_bytes(1, "foxglove/Pose.proto") + pb_bytes(2, "foxglove")
+    file += pb_message("Vector3", [pb_field(n, i + 1, 1) for i, n in enumerate("xyz")])
+    file += pb_message("Quaternion", [pb_field(n, i + 1, 1) for i, n in enumerate("xyzw")])
+    file += pb_message("Pose", [
+        pb_field("position", 1, 11, ".foxglove.Vector3"),
+        pb_field("orientation", 2, 11, ".foxglove.Quaternion"),
+        pb_field("frame_id", 3, 9),
+        pb_field("covariance", 4, 1, repeated=True),
+    ])
+    descriptor_set = pb_bytes(1, file)
+
+    records = [schema(1, "foxglove.Pose", "protobuf", descriptor_set),
+               channel(1, 1, "/robot/pose", "protobuf")]
+    for i, time in enumerate(TIMES):
+        position = pb_double(1, 1.0 + i) + pb_double(2, 2.0) + pb_double(3, 0.5)
+        orientation = pb_double(4, 1.0)
+        packed = b"".join(struct.pack("<d", v) for v in [0.1 * i, 0.2, 0.3])
+        data = pb_bytes(1, position) + pb_bytes(2, orientation) + pb_bytes(3, "field")
+        data += pb_bytes(4, packed)
+        records.append(message(1, i, time, data))
+    write("protobuf.mcap", records)
+
+
+# --- ROS ---
+
+ROS_DEFINITION = """string name
+float64 speed
+float64[] currents
+geometry_msgs/Point offset
+bool enabled
+================================================================================
+MSG: geometry_msgs/Point
+float64 x
+float64 y
+float64 z
+"""
+
+
+def ros1():
+    records = [schema(1, "frc_msgs/ModuleState", "ros1msg", ROS_DEFINITION),
+               channel(1, 1, "/swerve/front_left", "ros1")]
+    for i, time in enumerate(TIMES):
+        data = string("front_left") + struct.pack("<d", 2.0 * i)
+        data += u32(2) + struct.pack("<dd", 10.0 + i, 11.0 + i)
+        data += struct.pack("<ddd", 0.3, 0.3, 0.0) + bytes([i % 2])
+        records.append(message(1, i, time, data))
+    write("ros1.mcap", records)
+
+
+class Cdr:
+    """Little-endian CDR, aligned relative to the end of the header."""
+
+    def __init__(self):
+        self.data = bytearray(b"\x00\x01\x00\x00")
+
+    def align(self, size):
+        while (len(self.data) - 4) % size:
+            self.data.append(0)
+
+    def u32(self, v):
+        self.align(4)
+        self.data += struct.pack("<I", v)
+
+    def f64(self, v):
+        self.align(8)
+        self.data += struct.pack("<d", v)
+
+    def string(self, s):
+        self.u32(len(s) + 1)
+        self.data += s.encode() + b"\x00"
+
+
+def ros2():
+    records = [schema(1, "frc_msgs/msg/ModuleState", "ros2msg", ROS_DEFINITION),
+               channel(1, 1, "/swerve/front_left", "cdr")]
+    for i, time in enumerate(TIMES):
+        cdr = Cdr()
+        cdr.string("front_left")
+        cdr.f64(2.0 * i)
+        cdr.u32(2)
+        cdr.f64(10.0 + i)
+        cdr.f64(11.0 + i)
+        for v in (0.3, 0.3, 0.0):
+            cdr.f64(v)
+        cdr.data.append(i % 2)
+        records.append(message(1, i, time, bytes(cdr.data)))
+    write("ros2.mcap", records)
+
+
+# --- JSON and CBOR ---
+
+def json_fixture():
+    records = [schema(1, "Drive", "jsonschema", '{"type":"object"}'),
+               channel(1, 1, "/drive", "json")]
+    for i, time in enumerate(TIMES):
+        data = ('{"speed": %s, "enabled": %s, "mode": "auto", "modules": '
+                '[{"angle": %s}, {"angle": %s}]}'
+                % (1.5 * i, "true" if i else "false", 0.1 * i, -0.1 * i))
+        records.append(message(1, i, time, data.encode()))
+    write("json.mcap", records)
+
+
+def cbor_head(major, v):
+    if v < 24:
+        return bytes([major << 5 | v])
+    if v < 256:
+        return bytes([major << 5 | 24, v])
+    return bytes([major << 5 | 25]) + struct.pack(">H", v)
+
+
+def cbor(value):
+    if value is True:
+        return b"\xf5"
+    if value is False:
+        return b"\xf4"
+    if isinstance(value, int):
+        return cbor_head(0, value) if value >= 0 else cbor_head(1, -1 - value)
+    if isinstance(value, float):
+        return b"\xfb" + struct.pack(">d", value)
+    if isinstance(value, str):
+        return cbor_head(3, len(value.encode())) + value.encode()
+    if isinstance(value, bytes):
+        return cbor_head(2, len(value)) + value
+    if isinstance(value, list):
+        return cbor_head(4, len(value)) + b"".join(cbor(v) for v in value)
+    if isinstance(value, dict):
+        return cbor_head(5, len(value)) + b"".join(cbor(k) + cbor(v) for k, v in value.items())
+    raise TypeError(value)
+
+
+def cbor_fixture():
+    records = [channel(1, 0, "/arm", "cbor")]
+    for i, time in enumerate(TIMES):
+        data = cbor({"angle": 0.25 * i, "count": -3 + i, "at_goal": i == 2,
+                     "joints": [1.0, 2.0 + i], "tag": b"\x01\x02"})
+        records.append(message(1, i, time, data))
+    write("cbor.mcap", records)
+
+
+if __name__ == "__main__":
+    protobuf()
+    ros1()
+    ros2()
+    json_fixture()
+    cbor_fixture()
@@ -0,0 +1,69 @@
+#!/usr/bin/env python3
+"""Writes sparks.revlog, the REV log fixture the decoder tests read: SPARKs 3
+and 12 each sending status frames 0-2 at 0.5 s and 1 s, one periodic record
+per time, plus a frame from a CTRE device that should be skipped. Run from
+this directory."""
+
+import struct
+
+ENTRY_FIRMWARE = 1
+ENTRY_PERIODIC = 2
+
+
+def record(entry, payload):
+    # One-byte entry id, two-byte payload size
+    return bytes([0b0100]) + bytes([entry]) + struct.pack("<H", len(payload)) + payload
+
+
+def can_id(device_type, manufacturer, api, device):
+    return (device_type << 24) | (manufacturer << 16) | (api << 6) | device
+
+
+def spark(api, device):
+    return can_id(2, 5, api, device)
+
+
+def frame(time_ms, arbitration_id, data):
+    assert len(data) == 8
+    return struct.pack("<II", time_ms, arbitration_id) + data
+
+
+def status0(output, faults, sticky):
+    return struct.pack("<hHHH", round(output * 32768), faults, sticky, 0)
+
+
+def status1(velocity, temperature, voltage, current):
+    v, c = round(voltage * 128), round(current * 32)
+    packed = v | (c << 12)
+    return struct.pack("<fB", velocity, temperature) + packed.to_bytes(3, "little")
+
+
+def status2(position):
+    return struct.pack("<fI", position, 0)
+
+
+def periodic(time_ms, sparks):
+    frames = b""
+    for device, (output, faults, velocity, temperature, voltage, current, position) in sparks.items():
+        frames += frame(time_ms, spark(0x060, device), status0(output, faults, 0))
+        frames += frame(time_ms, spark(0x061, device), status1(velocity, temperature, voltage, current))
+        frames += frame(time_ms, spark(0x062, device), status2(position))
+    return frames
+
+
+def main():
+    records = [record(ENTRY_FIRMWARE, struct.pack("<IBBH", spark(0x098, 3), 25, 0, 3))]
+    records.append(record(ENTRY_PERIODIC, periodic(500, {
+        3: (0.5, 0, 1500.0, 31, 12.25, 20.0, 1.0),
+        12: (-0.25, 4, -300.0, 28, 12.0, 5.0, -2.0),
+    }) + frame(500, can_id(2, 4, 0x060, 1), bytes(8))))
+    records.append(record(ENTRY_PERIODIC, periodic(1000, {
+        3: (0.75, 0, 2000.0, 32, 11.5, 40.5, 3.0),
+        12: (-0.5, 4, -600.0, 29, 11.75, 10.0, -7.5),
+    })))
+    with open("sparks.revlog", "wb") as f:
+        f.write(b"".join(records))
+
+
+if __name__ == "__main__":
+    main()